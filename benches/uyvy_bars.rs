@@ -0,0 +1,37 @@
+// UYVY カラーバー生成のベンチマーク（3840x2160）
+//
+//   cargo bench --bench uyvy_bars                   # スカラー版のみ
+//   cargo bench --bench uyvy_bars --features simd   # スカラー版と SIMD 版
+use criterion::{criterion_group, criterion_main, Criterion};
+use omt_send_test_rs::bars;
+use omt_send_test_rs::bindings::*;
+use std::hint::black_box;
+
+const WIDTH: usize = 3840;
+const HEIGHT: usize = 2160;
+
+fn uyvy_bars(c: &mut Criterion) {
+    let bars = bars::yuv_bars(OMTColorSpace_OMTColorSpace_BT709);
+    let stride = WIDTH * 2;
+
+    let mut group = c.benchmark_group("uyvy_bars_row_3840");
+    let mut row = vec![0u8; stride];
+    group.bench_function("scalar", |b| {
+        b.iter(|| bars::fill_uyvy_bars_row_scalar(black_box(&mut row), WIDTH, &bars))
+    });
+    #[cfg(feature = "simd")]
+    group.bench_function("simd", |b| {
+        b.iter(|| bars::fill_uyvy_bars_row_simd(black_box(&mut row), WIDTH, &bars))
+    });
+    group.finish();
+
+    // The whole frame: one generated row plus row copies, through whichever
+    // row fill the build selected
+    let mut frame = vec![0u8; stride * HEIGHT];
+    c.bench_function("uyvy_bars_frame_3840x2160", |b| {
+        b.iter(|| bars::fill_uyvy_bars(black_box(&mut frame), WIDTH, stride, &bars))
+    });
+}
+
+criterion_group!(benches, uyvy_bars);
+criterion_main!(benches);
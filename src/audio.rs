@@ -0,0 +1,162 @@
+// 音声フォーマットとテストトーン、映像フレームごとの音声サンプル数の配分
+use std::{f32::consts::TAU, mem};
+
+/// Frequency of the test tone.
+pub const TONE_HZ: f32 = 1000.0;
+
+/// Peak level of the test tone, about -12 dBFS, so resampling or mixing on
+/// the receiver doesn't clip.
+pub const TONE_AMPLITUDE: f32 = 0.25;
+
+/// An audio stream to send: 32-bit float, planar (`OMTCodec_FPA1`).
+#[derive(Debug, Clone, Copy)]
+pub struct AudioFormat {
+    pub sample_rate: i32,
+    pub channels: i32,
+    pub name: &'static str,
+}
+
+/// Audio presets, run by naming them like a video format.
+pub const AUDIO_FORMATS: &[AudioFormat] = &[
+    AudioFormat {
+        sample_rate: 48000,
+        channels: 2,
+        name: "audio_48k_stereo",
+    },
+    AudioFormat {
+        sample_rate: 48000,
+        channels: 1,
+        name: "audio_48k_mono",
+    },
+    AudioFormat {
+        sample_rate: 44100,
+        channels: 2,
+        name: "audio_44k1_stereo",
+    },
+    AudioFormat {
+        sample_rate: 48000,
+        channels: 8,
+        name: "audio_48k_8ch",
+    },
+];
+
+/// Looks up an audio preset by name.
+pub fn find(name: &str) -> Option<AudioFormat> {
+    AUDIO_FORMATS.iter().find(|f| f.name == name).copied()
+}
+
+impl AudioFormat {
+    /// `num_samples` of the test tone from its start, planar: every sample
+    /// of channel 0, then of channel 1 and so on. All channels carry the
+    /// same tone.
+    pub fn create_test_audio(&self, num_samples: i32) -> Vec<f32> {
+        self.tone(0, num_samples)
+    }
+
+    /// Like [`Self::create_test_audio`], starting at sample `start` of the
+    /// tone so consecutive blocks join without a click.
+    pub fn tone(&self, start: u64, num_samples: i32) -> Vec<f32> {
+        let samples = num_samples.max(0) as usize;
+        // Phase kept in whole cycles so it stays precise over long runs
+        let rate = self.sample_rate.max(1) as u64;
+        let channel: Vec<f32> = (0..samples as u64)
+            .map(|i| {
+                let n = (start + i) * TONE_HZ as u64 % rate;
+                (TAU * n as f32 / rate as f32).sin() * TONE_AMPLITUDE
+            })
+            .collect();
+        channel.repeat(self.channels.max(0) as usize)
+    }
+
+    /// Bytes in a planar float block of `num_samples` per channel.
+    pub fn block_size(&self, num_samples: u32) -> usize {
+        num_samples as usize * self.channels.max(0) as usize * mem::size_of::<f32>()
+    }
+}
+
+/// Number of audio samples to send with each video frame.
+///
+/// At 48 kHz with 30000/1001 video a frame lasts 1601.6 samples, so a fixed
+/// `SamplesPerChannel` drifts against the video. Instead the running total
+/// after N frames is kept at exactly `floor(N * sample_rate * fps_d / fps_n)`
+/// and each frame gets the difference, which at 29.97 gives the repeating
+/// 1601, 1602, 1601, 1602, 1602 cadence (8008 samples every 5 frames).
+#[derive(Debug, Clone, Copy)]
+pub struct SampleCadence {
+    sample_rate: u64,
+    fps_n: u64,
+    fps_d: u64,
+    frame: u64,
+}
+
+impl SampleCadence {
+    pub fn new(sample_rate: u32, fps_n: i32, fps_d: i32) -> Self {
+        SampleCadence {
+            sample_rate: sample_rate as u64,
+            fps_n: fps_n.max(1) as u64,
+            fps_d: fps_d.max(1) as u64,
+            frame: 0,
+        }
+    }
+
+    /// Samples due in total by the start of frame `frames`.
+    pub fn samples_before(&self, frames: u64) -> u64 {
+        (frames as u128 * self.sample_rate as u128 * self.fps_d as u128 / self.fps_n as u128) as u64
+    }
+
+    /// Samples to send with frame `index`.
+    pub fn samples_for_frame(&self, index: u64) -> u32 {
+        (self.samples_before(index + 1) - self.samples_before(index)) as u32
+    }
+
+    /// Samples sent so far, i.e. before the next frame.
+    pub fn total(&self) -> u64 {
+        self.samples_before(self.frame)
+    }
+}
+
+/// Yields the sample count of each successive frame.
+impl Iterator for SampleCadence {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let samples = self.samples_for_frame(self.frame);
+        self.frame += 1;
+        Some(samples)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cadence_at_29_97_sums_exactly() {
+        let mut cadence = SampleCadence::new(48000, 30000, 1001);
+        let cycle: Vec<u32> = cadence.by_ref().take(5).collect();
+        assert_eq!(cycle, [1601, 1602, 1601, 1602, 1602]);
+        assert_eq!(cycle.iter().sum::<u32>(), 8008);
+        assert_eq!(cadence.total(), 8008);
+
+        // 30000 frames are 1001s of video
+        let total: u64 = SampleCadence::new(48000, 30000, 1001)
+            .take(30000)
+            .map(u64::from)
+            .sum();
+        assert_eq!(total, 48000 * 1001);
+        // Every frame gets one of the two neighbouring counts
+        assert!(SampleCadence::new(48000, 30000, 1001)
+            .take(30000)
+            .all(|n| n == 1601 || n == 1602));
+    }
+
+    #[test]
+    fn cadence_at_integer_rates_is_flat() {
+        assert!(SampleCadence::new(48000, 30, 1)
+            .take(300)
+            .all(|n| n == 1600));
+        assert!(SampleCadence::new(48000, 25, 1)
+            .take(300)
+            .all(|n| n == 1920));
+    }
+}
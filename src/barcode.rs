@@ -0,0 +1,126 @@
+// フレーム番号のバーコード焼き込み（--frame-barcode）
+use anyhow::{bail, Result};
+
+use crate::{overlay, VideoFormat};
+
+/// Bits of the frame index carried by the barcode; the index wraps after
+/// 2^32 frames (over 2 years at 60fps).
+pub const BARCODE_BITS: usize = 32;
+
+/// Cells across the strip: a white and a black start cell, the index MSB
+/// first, an even parity bit, then a black and a white stop cell.
+pub const BARCODE_CELLS: usize = 2 + BARCODE_BITS + 1 + 2;
+
+/// Narrowest cell that survives chroma subsampling and the encoder.
+const MIN_CELL_WIDTH: usize = 4;
+
+/// Rows of the frame the barcode occupies (`--frame-barcode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BarcodeRegion {
+    /// A strip 1/24 of the frame height along the top edge
+    Top,
+    /// The same strip along the bottom edge
+    Bottom,
+    /// `height` rows starting at row `y`
+    Rows { y: usize, height: usize },
+}
+
+impl std::str::FromStr for BarcodeRegion {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "top" => Ok(BarcodeRegion::Top),
+            "bottom" => Ok(BarcodeRegion::Bottom),
+            _ => {
+                let rows = s
+                    .split_once(':')
+                    .and_then(|(y, h)| Some((y.parse().ok()?, h.parse().ok()?)));
+                match rows {
+                    Some((y, height)) if height > 0 => Ok(BarcodeRegion::Rows { y, height }),
+                    _ => bail!(
+                        "invalid barcode region {}, expected top, bottom or Y:HEIGHT",
+                        s
+                    ),
+                }
+            }
+        }
+    }
+}
+
+impl BarcodeRegion {
+    /// First row and number of rows in `format`.
+    pub fn rows(&self, format: &VideoFormat) -> (usize, usize) {
+        let frame_height = format.height as usize;
+        // Even, so NV12 chroma rows aren't shared with the picture
+        let strip = (frame_height / 24).max(8) & !1;
+        match *self {
+            BarcodeRegion::Top => (0, strip),
+            BarcodeRegion::Bottom => (frame_height.saturating_sub(strip), strip),
+            BarcodeRegion::Rows { y, height } => (y, height),
+        }
+    }
+
+    /// Width of one cell in `format`: the frame width split evenly across
+    /// [`BARCODE_CELLS`], rounded down to keep cell edges on chroma pairs.
+    pub fn cell_width(&self, format: &VideoFormat) -> usize {
+        (format.width as usize / BARCODE_CELLS) & !1
+    }
+
+    /// Checks that the strip fits in `format` with readable cells.
+    pub fn check(&self, format: &VideoFormat) -> Result<()> {
+        let (y, height) = self.rows(format);
+        if y + height > format.height as usize {
+            bail!(
+                "barcode rows {}-{} are outside the {}-row frame",
+                y,
+                y + height - 1,
+                format.height
+            );
+        }
+        let cell = self.cell_width(format);
+        if cell < MIN_CELL_WIDTH {
+            bail!(
+                "{} is too narrow for a frame barcode: {} cells need at least {} pixels",
+                format.name,
+                BARCODE_CELLS,
+                BARCODE_CELLS * MIN_CELL_WIDTH
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Cell colors for frame `index`, left to right; true is white.
+pub fn cells(index: u64) -> [bool; BARCODE_CELLS] {
+    let value = index as u32;
+    let mut cells = [false; BARCODE_CELLS];
+    cells[0] = true;
+    for bit in 0..BARCODE_BITS {
+        cells[2 + bit] = value & (1 << (BARCODE_BITS - 1 - bit)) != 0;
+    }
+    cells[2 + BARCODE_BITS] = value.count_ones() % 2 == 1;
+    cells[BARCODE_CELLS - 1] = true;
+    cells
+}
+
+/// Draws the barcode for frame `index` into `region` of `buf`. Cells are
+/// video black or white with neutral chroma, so they stay apart after
+/// compression; any pixels right of the last cell are filled black.
+pub fn draw(buf: &mut [u8], format: &VideoFormat, region: &BarcodeRegion, index: u64) {
+    let (y, height) = region.rows(format);
+    let cell = region.cell_width(format);
+    let used = cell * BARCODE_CELLS;
+    for (i, white) in cells(index).into_iter().enumerate() {
+        overlay::fill_rect(buf, format, i * cell, y, cell, height, white);
+    }
+    overlay::fill_rect(
+        buf,
+        format,
+        used,
+        y,
+        format.width as usize - used,
+        height,
+        false,
+    );
+}
@@ -0,0 +1,208 @@
+// カラーバーの生成（simd フィーチャーでベクトル化）
+use crate::bindings::*;
+use crate::{color_bar_section, convert};
+
+/// RGB of the 8 full-amplitude color bars, left to right. Every codec's
+/// test frame is converted from these, so all three show the same bars.
+pub const BAR_RGB: [[u8; 3]; 8] = [
+    [255, 255, 255], // White
+    [255, 255, 0],   // Yellow
+    [0, 255, 255],   // Cyan
+    [0, 255, 0],     // Green
+    [255, 0, 255],   // Magenta
+    [255, 0, 0],     // Red
+    [0, 0, 255],     // Blue
+    [0, 0, 0],       // Black
+];
+
+/// Names of [`BAR_RGB`], left to right.
+pub const BAR_NAMES: [&str; 8] = [
+    "White", "Yellow", "Cyan", "Green", "Magenta", "Red", "Blue", "Black",
+];
+
+/// UYVY values `(U, Y, V)` of [`BAR_RGB`] in `colorspace`. The bars are flat
+/// and an even number of pixels wide, so no chroma pair or 2x2 block
+/// straddles two bars and these are exactly what converting
+/// [`smpte_bars_rgb`] pixel by pixel gives.
+pub fn yuv_bars(colorspace: OMTColorSpace) -> [(u8, u8, u8); 8] {
+    let bt709 = colorspace == OMTColorSpace_OMTColorSpace_BT709;
+    BAR_RGB.map(|[r, g, b]| {
+        let (y, u, v) = convert::rgb_to_yuv(r, g, b, bt709);
+        (u, y, v)
+    })
+}
+
+/// RGB of every pixel of a `width` x `height` frame of color bars, row by
+/// row.
+pub fn smpte_bars_rgb(width: usize, height: usize) -> Vec<[u8; 3]> {
+    let row: Vec<[u8; 3]> = (0..width)
+        .map(|x| BAR_RGB[color_bar_section(x, width)])
+        .collect();
+    row.repeat(height)
+}
+
+/// Fills a full UYVY frame with color bars of `bars`, as from [`yuv_bars`].
+///
+/// Every row of the pattern is identical, so only the first row is generated
+/// and the rest are copies of it.
+pub fn fill_uyvy_bars(buf: &mut [u8], width: usize, stride: usize, bars: &[(u8, u8, u8); 8]) {
+    if stride == 0 || buf.len() < stride {
+        return;
+    }
+    let (first, rest) = buf.split_at_mut(stride);
+    fill_uyvy_bars_row(first, width, bars);
+    for row in rest.chunks_exact_mut(stride) {
+        row.copy_from_slice(first);
+    }
+}
+
+/// Fills one UYVY row of `width` pixels with color bars.
+///
+/// With the `simd` feature this is [`fill_uyvy_bars_row_simd`]; otherwise
+/// [`fill_uyvy_bars_row_scalar`]. Both give identical bytes.
+pub fn fill_uyvy_bars_row(row: &mut [u8], width: usize, bars: &[(u8, u8, u8); 8]) {
+    #[cfg(feature = "simd")]
+    fill_uyvy_bars_row_simd(row, width, bars);
+    #[cfg(not(feature = "simd"))]
+    fill_uyvy_bars_row_scalar(row, width, bars);
+}
+
+/// Reference implementation: one macropixel at a time.
+pub fn fill_uyvy_bars_row_scalar(row: &mut [u8], width: usize, bars: &[(u8, u8, u8); 8]) {
+    for (x_pair, pair) in row.chunks_exact_mut(4).enumerate() {
+        let (u, y, v) = bars[color_bar_section(x_pair * 2, width)];
+        pair.copy_from_slice(&[u, y, v, y]);
+    }
+}
+
+/// Vectorized implementation: stores each bar as 16-byte `u8x16` vectors
+/// written straight into the aligned middle of its byte range, with the
+/// unaligned ends filled byte by byte.
+#[cfg(feature = "simd")]
+pub fn fill_uyvy_bars_row_simd(row: &mut [u8], width: usize, bars: &[(u8, u8, u8); 8]) {
+    use wide::u8x16;
+
+    // Same bars as color_bar_section: whole macropixels, the last bar
+    // running to the end of the row
+    let len = row.len() / 4 * 4;
+    let bar_bytes = (width / 16 * 2).max(2) * 2;
+    for (bar, &(u, y, v)) in bars.iter().enumerate() {
+        let start = (bar * bar_bytes).min(len);
+        let end = if bar == bars.len() - 1 {
+            len
+        } else {
+            ((bar + 1) * bar_bytes).min(len)
+        };
+        let macropixel = [u, y, v, y];
+        // SAFETY: u8x16 is plain bytes, so any 16 initialized bytes are a
+        // valid value
+        let (head, body, tail) = unsafe { row[start..end].align_to_mut::<u8x16>() };
+        for (i, b) in head.iter_mut().enumerate() {
+            *b = macropixel[i % 4];
+        }
+        // The bar starts on a macropixel, so the vectors start `head.len()`
+        // bytes into the U, Y, V, Y cycle; 16 bytes is a whole number of
+        // cycles, so the tail starts at the same phase
+        let phase = head.len() % 4;
+        body.fill(u8x16::from(std::array::from_fn::<u8, 16, _>(|i| {
+            macropixel[(i + phase) % 4]
+        })));
+        for (i, b) in tail.iter_mut().enumerate() {
+            *b = macropixel[(i + phase) % 4];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::patterns::FramePattern;
+    use crate::VideoFormat;
+
+    /// `(U, Y, V)` of pixel `(x, y)` of a frame in any of the three codecs.
+    fn sample(format: &VideoFormat, buf: &[u8], x: usize, y: usize) -> (u8, u8, u8) {
+        let stride = format.stride() as usize;
+        let bt709 = format.color_space() == OMTColorSpace_OMTColorSpace_BT709;
+        match format.codec {
+            c if c == OMTCodec_OMTCodec_UYVY => {
+                let pair = &buf[y * stride + (x & !1) * 2..][..4];
+                (pair[0], pair[1 + (x & 1) * 2], pair[2])
+            }
+            c if c == OMTCodec_OMTCodec_NV12 => {
+                let uv = &buf[format.uv_offset() + y / 2 * stride + (x & !1)..][..2];
+                (uv[0], buf[y * stride + x], uv[1])
+            }
+            _ => {
+                let p = &buf[y * stride + x * 4..][..4];
+                let (luma, u, v) = convert::rgb_to_yuv(p[2], p[1], p[0], bt709);
+                (u, luma, v)
+            }
+        }
+    }
+
+    #[test]
+    fn every_codec_shows_the_same_bars() {
+        let codecs = [
+            OMTCodec_OMTCodec_UYVY,
+            OMTCodec_OMTCodec_BGRA,
+            OMTCodec_OMTCodec_NV12,
+        ];
+        // 720p is BT.709, 486 lines BT.601; 1366 isn't a multiple of 16
+        for (width, height) in [(1280, 720), (1366, 768), (720, 486)] {
+            for codec in codecs {
+                let format = VideoFormat::from_parts(codec, width, height, 30, 1).unwrap();
+                let buf = format.create_test_frame(0, FramePattern::Static);
+                let expected = yuv_bars(format.color_space());
+                let width = width as usize;
+                for (bar, &(eu, ey, ev)) in expected.iter().enumerate() {
+                    let start = (0..width).find(|&x| color_bar_section(x, width) == bar);
+                    let end = (0..width).rposition(|x| color_bar_section(x, width) == bar);
+                    let x = (start.unwrap() + end.unwrap()) / 2;
+                    let (u, y, v) = sample(&format, &buf, x, height as usize / 2);
+                    for (got, want) in [(u, eu), (y, ey), (v, ev)] {
+                        assert!(
+                            got.abs_diff(want) <= 1,
+                            "{} {} bar: ({}, {}, {}), expected ({}, {}, {})",
+                            format.name,
+                            BAR_NAMES[bar],
+                            u,
+                            y,
+                            v,
+                            eu,
+                            ey,
+                            ev
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "simd")]
+    #[test]
+    fn simd_rows_match_the_scalar_rows() {
+        let mut rng = crate::rng::XorShift::new(403);
+        let mut widths: Vec<usize> = (1..=600).collect();
+        widths.extend([1366, 1920, 3840, 4096]);
+        for width in widths {
+            let bars: [(u8, u8, u8); 8] = std::array::from_fn(|_| {
+                let [u, y, v, ..] = rng.next_u64().to_le_bytes();
+                (u, y, v)
+            });
+            // Every start alignment of the row, with lengths that aren't a
+            // whole number of macropixels or run past `width`
+            for offset in 0..16 {
+                let len = width.div_ceil(2) * 4 + rng.below(12) as usize;
+                let mut scalar = vec![0u8; offset + len];
+                let mut simd = scalar.clone();
+                fill_uyvy_bars_row_scalar(&mut scalar[offset..], width, &bars);
+                fill_uyvy_bars_row_simd(&mut simd[offset..], width, &bars);
+                assert_eq!(
+                    scalar, simd,
+                    "width {} offset {} len {}",
+                    width, offset, len
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+// アラインメントを指定したフレームバッファ
+use std::{
+    alloc::{self, Layout},
+    ops::{Deref, DerefMut},
+    ptr::NonNull,
+};
+
+/// Default frame buffer alignment: a cache line, which also covers the 16-
+/// and 32-byte loads of SSE and AVX2 encoder paths.
+pub const DEFAULT_ALIGN: usize = 64;
+
+/// A zero-initialised byte buffer whose start is aligned to `align` bytes.
+///
+/// `Vec<u8>` only guarantees 1-byte alignment. SIMD encoders either take a
+/// slower unaligned path or copy into their own aligned buffer first, so an
+/// aligned `OMTMediaFrame.Data` can save time in `CodecTime`. An alignment of
+/// 1 deliberately starts the buffer one byte past an aligned address, giving a
+/// worst case to compare against.
+pub struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    offset: usize,
+    layout: Layout,
+}
+
+// The buffer owns its allocation exclusively, like a Vec<u8>
+unsafe impl Send for AlignedBuffer {}
+unsafe impl Sync for AlignedBuffer {}
+
+impl AlignedBuffer {
+    /// Allocates `len` zero bytes. `align` must be a power of two.
+    pub fn zeroed(len: usize, align: usize) -> Self {
+        let (alloc_align, offset) = if align == 1 {
+            (DEFAULT_ALIGN, 1)
+        } else {
+            (align, 0)
+        };
+        let layout = Layout::from_size_align((len + offset).max(1), alloc_align)
+            .expect("alignment must be a power of two");
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let Some(ptr) = NonNull::new(ptr) else {
+            alloc::handle_alloc_error(layout);
+        };
+        AlignedBuffer {
+            ptr,
+            len,
+            offset,
+            layout,
+        }
+    }
+
+    pub fn from_slice(data: &[u8], align: usize) -> Self {
+        let mut buf = Self::zeroed(data.len(), align);
+        buf.copy_from_slice(data);
+        buf
+    }
+
+    /// Alignment of the start of the data, as used for `OMTMediaFrame.Data`.
+    pub fn alignment(&self) -> usize {
+        1 << (self.as_ptr() as usize).trailing_zeros().min(12)
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr().add(self.offset), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr().add(self.offset), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
@@ -0,0 +1,226 @@
+// RGBA → 各コーデックのバッファ変換
+use image::RgbaImage;
+
+use crate::bindings::*;
+
+/// Converts an 8-bit RGB triple to limited-range (16-235/16-240) Y'CbCr.
+pub fn rgb_to_yuv(r: u8, g: u8, b: u8, bt709: bool) -> (u8, u8, u8) {
+    let (kr, kb) = if bt709 {
+        (0.2126, 0.0722)
+    } else {
+        (0.299, 0.114)
+    };
+    let (r, g, b) = (r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+
+    let y = kr * r + (1.0 - kr - kb) * g + kb * b;
+    let pb = (b - y) / (2.0 * (1.0 - kb));
+    let pr = (r - y) / (2.0 * (1.0 - kr));
+
+    (
+        (16.0 + 219.0 * y).round().clamp(0.0, 255.0) as u8,
+        (128.0 + 224.0 * pb).round().clamp(0.0, 255.0) as u8,
+        (128.0 + 224.0 * pr).round().clamp(0.0, 255.0) as u8,
+    )
+}
+
+fn is_bt709(colorspace: OMTColorSpace) -> bool {
+    colorspace == OMTColorSpace_OMTColorSpace_BT709
+}
+
+/// Converts an image to a UYVY buffer (stride `width * 2`), averaging chroma
+/// over each horizontal pixel pair. The width must be even.
+pub fn uyvy_from_rgba(image: &RgbaImage, colorspace: OMTColorSpace) -> Vec<u8> {
+    let mut buf = vec![0u8; image.width() as usize * 2 * image.height() as usize];
+    uyvy_into(image, colorspace, &mut buf);
+    buf
+}
+
+/// Like [`uyvy_from_rgba`], writing into an existing buffer of the same size.
+pub fn uyvy_into(image: &RgbaImage, colorspace: OMTColorSpace, buf: &mut [u8]) {
+    let width = image.width() as usize;
+    let rgba = image.as_raw();
+    let bt709 = is_bt709(colorspace);
+
+    for (y, row) in buf.chunks_exact_mut(width * 2).enumerate() {
+        for (x_pair, pair) in row.chunks_exact_mut(4).enumerate() {
+            let i0 = (y * width + x_pair * 2) * 4;
+            let i1 = i0 + 4;
+            let (y0, u0, v0) = rgb_to_yuv(rgba[i0], rgba[i0 + 1], rgba[i0 + 2], bt709);
+            let (y1, u1, v1) = rgb_to_yuv(rgba[i1], rgba[i1 + 1], rgba[i1 + 2], bt709);
+
+            pair[0] = ((u0 as u16 + u1 as u16) / 2) as u8; // U
+            pair[1] = y0; // Y0
+            pair[2] = ((v0 as u16 + v1 as u16) / 2) as u8; // V
+            pair[3] = y1; // Y1
+        }
+    }
+}
+
+/// Converts an image to an NV12 buffer (Y plane followed by interleaved UV),
+/// averaging chroma over each 2x2 block. Odd dimensions round the UV plane
+/// up: the last UV row or column covers the last Y row or column alone.
+pub fn nv12_from_rgba(image: &RgbaImage, colorspace: OMTColorSpace) -> Vec<u8> {
+    let stride = (image.width() as usize).div_ceil(2) * 2;
+    let mut buf =
+        vec![0u8; stride * (image.height() as usize + (image.height() as usize).div_ceil(2))];
+    nv12_into(image, colorspace, stride, &mut buf);
+    buf
+}
+
+/// Like [`nv12_from_rgba`], writing into an existing buffer whose planes
+/// both have rows of `stride` bytes, at least the width rounded up to even.
+/// Row padding is left untouched.
+pub fn nv12_into(image: &RgbaImage, colorspace: OMTColorSpace, stride: usize, buf: &mut [u8]) {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let rgba = image.as_raw();
+    let bt709 = is_bt709(colorspace);
+    let (y_plane, uv_plane) = buf.split_at_mut(stride * height);
+
+    for (row, y_row) in y_plane.chunks_exact_mut(stride).enumerate() {
+        let pixels = rgba[row * width * 4..(row + 1) * width * 4].chunks_exact(4);
+        for (y, pixel) in y_row[..width].iter_mut().zip(pixels) {
+            *y = rgb_to_yuv(pixel[0], pixel[1], pixel[2], bt709).0;
+        }
+    }
+
+    for (row, uv_row) in uv_plane.chunks_exact_mut(stride).enumerate() {
+        for (col, uv) in uv_row[..width.div_ceil(2) * 2]
+            .chunks_exact_mut(2)
+            .enumerate()
+        {
+            let (mut u_sum, mut v_sum) = (0u32, 0u32);
+            for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                // An odd last row or column is averaged with itself
+                let y = (row * 2 + dy).min(height - 1);
+                let x = (col * 2 + dx).min(width - 1);
+                let i = (y * width + x) * 4;
+                let (_, u, v) = rgb_to_yuv(rgba[i], rgba[i + 1], rgba[i + 2], bt709);
+                u_sum += u as u32;
+                v_sum += v as u32;
+            }
+            uv[0] = (u_sum / 4) as u8; // U
+            uv[1] = (v_sum / 4) as u8; // V
+        }
+    }
+}
+
+/// Converts an image to a BGRA buffer (stride `width * 4`). The colorspace is
+/// unused for RGB output but kept for a uniform signature.
+pub fn bgra_from_rgba(image: &RgbaImage, colorspace: OMTColorSpace) -> Vec<u8> {
+    let mut buf = vec![0u8; image.as_raw().len()];
+    bgra_into(image, colorspace, &mut buf);
+    buf
+}
+
+/// Like [`bgra_from_rgba`], writing into an existing buffer of the same size.
+pub fn bgra_into(image: &RgbaImage, _colorspace: OMTColorSpace, buf: &mut [u8]) {
+    buf.copy_from_slice(image.as_raw());
+    for pixel in buf.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// Replaces the alpha channel of a BGRA buffer with a left-to-right ramp from
+/// transparent to opaque.
+pub fn apply_alpha_gradient(bgra: &mut [u8], width: usize) {
+    let max_x = width.saturating_sub(1).max(1);
+    for (i, pixel) in bgra.chunks_exact_mut(4).enumerate() {
+        pixel[3] = ((i % width) * 255 / max_x) as u8;
+    }
+}
+
+/// Multiplies the color channels of a BGRA buffer by its alpha.
+pub fn premultiply_bgra(bgra: &mut [u8]) {
+    for pixel in bgra.chunks_exact_mut(4) {
+        let a = pixel[3] as u16;
+        for c in &mut pixel[..3] {
+            *c = ((*c as u16 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const BT601: OMTColorSpace = OMTColorSpace_OMTColorSpace_BT601;
+    const BT709: OMTColorSpace = OMTColorSpace_OMTColorSpace_BT709;
+
+    /// Columns of white, black, red and blue, two pixels each.
+    fn columns(height: u32) -> RgbaImage {
+        const COLORS: [[u8; 4]; 4] = [
+            [255, 255, 255, 255],
+            [0, 0, 0, 255],
+            [255, 0, 0, 255],
+            [0, 0, 255, 255],
+        ];
+        RgbaImage::from_fn(8, height, |x, _| Rgba(COLORS[x as usize / 2]))
+    }
+
+    #[test]
+    fn rgb_to_yuv_is_limited_range() {
+        for bt709 in [false, true] {
+            assert_eq!(rgb_to_yuv(255, 255, 255, bt709), (235, 128, 128));
+            assert_eq!(rgb_to_yuv(0, 0, 0, bt709), (16, 128, 128));
+        }
+        // BT.709 primaries
+        assert_eq!(rgb_to_yuv(255, 0, 0, true), (63, 102, 240));
+        assert_eq!(rgb_to_yuv(0, 255, 0, true), (173, 42, 26));
+        assert_eq!(rgb_to_yuv(0, 0, 255, true), (32, 240, 118));
+        // BT.601 weighs red and blue more
+        assert_eq!(rgb_to_yuv(255, 0, 0, false), (81, 90, 240));
+        assert_eq!(rgb_to_yuv(0, 0, 255, false), (41, 240, 110));
+    }
+
+    #[test]
+    fn uyvy_of_a_known_image() {
+        let buf = uyvy_from_rgba(&columns(2), BT709);
+        let row = [
+            128, 235, 128, 235, // White
+            128, 16, 128, 16, // Black
+            102, 63, 240, 63, // Red
+            240, 32, 118, 32, // Blue
+        ];
+        assert_eq!(buf, [row, row].concat());
+
+        // A pair of two colors averages their chroma
+        let image = RgbaImage::from_fn(2, 1, |x, _| {
+            Rgba(if x == 0 {
+                [255, 0, 0, 255]
+            } else {
+                [0, 0, 255, 255]
+            })
+        });
+        assert_eq!(uyvy_from_rgba(&image, BT601), [165, 81, 175, 41]);
+    }
+
+    #[test]
+    fn nv12_of_a_known_image_with_padded_stride() {
+        let stride = 12;
+        // Padding is left as it was
+        let mut buf = vec![0xAA; stride * 3];
+        nv12_into(&columns(2), BT709, stride, &mut buf);
+        let y_row = [235, 235, 16, 16, 63, 63, 32, 32, 0xAA, 0xAA, 0xAA, 0xAA];
+        let uv_row = [
+            128, 128, 128, 128, 102, 240, 240, 118, 0xAA, 0xAA, 0xAA, 0xAA,
+        ];
+        assert_eq!(buf, [y_row, y_row, uv_row].concat());
+
+        // Tightly packed, the UV plane is rounded up for odd sizes
+        let odd = RgbaImage::from_pixel(3, 3, Rgba([255, 255, 255, 255]));
+        let buf = nv12_from_rgba(&odd, BT709);
+        assert_eq!(buf.len(), 4 * 3 + 4 * 2);
+        assert_eq!(&buf[..3], [235, 235, 235]);
+        assert_eq!(&buf[12..], [128; 8]);
+    }
+
+    #[test]
+    fn bgra_swaps_red_and_blue_and_keeps_alpha() {
+        let image = RgbaImage::from_pixel(2, 1, Rgba([10, 20, 30, 40]));
+        assert_eq!(
+            bgra_from_rgba(&image, BT709),
+            [30, 20, 10, 40, 30, 20, 10, 40]
+        );
+    }
+}
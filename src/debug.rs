@@ -1,198 +1,38 @@
-use anyhow::{bail, Result};
-use std::{ffi::CString, mem, thread, time::Duration};
-
-mod bindings;
-use bindings::*;
+use anyhow::Result;
+use omt_send_test_rs::{bindings::*, *};
+use std::{path::PathBuf, time::Duration};
 
 fn main() -> Result<()> {
-    unsafe {
-        println!("OMT Debug Test - Simple UYVY 720p30 send");
-        println!("=========================================\n");
-
-        // Set up logging
-        let logfile = CString::new("/tmp/omt-send-debug.log")?;
-        omt_setloggingfilename(logfile.as_ptr());
-        println!("Log file: /tmp/omt-send-debug.log");
-
-        // Create sender with explicit name
-        let name = CString::new("RustDebugSender")?;
-        println!("Creating sender with name: RustDebugSender");
-
-        let sender = omt_send_create(name.as_ptr(), OMTQuality_OMTQuality_Medium);
-        if sender.is_null() {
-            bail!("omt_send_create failed - sender is null");
-        }
-        println!("✓ Sender created successfully");
-
-        // Set sender info
-        let mut info: OMTSenderInfo = mem::zeroed();
-        fn write_cstr(dst: &mut [i8], s: &str) {
-            let bytes = s.as_bytes();
-            let n = bytes.len().min(dst.len().saturating_sub(1));
-            for i in 0..n {
-                dst[i] = bytes[i] as i8;
-            }
-            if !dst.is_empty() {
-                dst[n] = 0;
-            }
-        }
-        write_cstr(&mut info.ProductName, "OMT-Debug-Test");
-        write_cstr(&mut info.Manufacturer, "Rust");
-        write_cstr(&mut info.Version, "1.0.0");
-        omt_send_setsenderinformation(sender, &mut info as *mut OMTSenderInfo);
-        println!("✓ Sender info set");
-
-        // Wait for connection with detailed status
-        println!("\nWaiting for receiver connection (please start OMT Monitor)...");
-        let mut connected = false;
-        for i in 0..100 {
-            // Wait up to 10 seconds
-            let conn_count = omt_send_connections(sender);
-            if conn_count > 0 {
-                connected = true;
-                println!(
-                    "✓ {} receiver(s) connected after {:.1}s",
-                    conn_count,
-                    i as f32 * 0.1
-                );
-                break;
-            }
-            if i % 10 == 0 {
-                print!(".");
-                std::io::Write::flush(&mut std::io::stdout()).ok();
-            }
-            thread::sleep(Duration::from_millis(100));
-        }
-
-        if !connected {
-            println!("\n⚠ No receivers connected after 10 seconds");
-            println!("Make sure OMT Monitor is running and listening");
-            println!("Proceeding anyway...\n");
-        }
-
-        // Create simple UYVY frame
-        let width = 1280i32;
-        let height = 720i32;
-        let stride = width * 2; // UYVY is 2 bytes per pixel
-        let fps_n = 30i32;
-        let fps_d = 1i32;
-
-        println!("Creating UYVY frame: {}x{} @ {}fps", width, height, fps_n);
-
-        // Create test pattern
-        let mut frame_buf = vec![0u8; (stride * height) as usize];
-        for row in 0..height {
-            let row_offset = (row * stride) as usize;
-            for x in 0..(width / 2) {
-                let offset = row_offset + (x as usize) * 4;
-                // Create a gradient pattern
-                let y_val = ((x as f32 / (width as f32 / 2.0)) * 255.0) as u8;
-                frame_buf[offset] = 128; // U
-                frame_buf[offset + 1] = y_val; // Y0
-                frame_buf[offset + 2] = 128; // V
-                frame_buf[offset + 3] = y_val; // Y1
-            }
-        }
-        println!("✓ Frame buffer created: {} bytes", frame_buf.len());
-
-        // Setup OMTMediaFrame
-        let mut frame: OMTMediaFrame = mem::zeroed();
-        frame.Type = OMTFrameType_OMTFrameType_Video;
-        frame.Codec = OMTCodec_OMTCodec_UYVY;
-        frame.Width = width;
-        frame.Height = height;
-        frame.Stride = stride;
-        frame.Flags = OMTVideoFlags_OMTVideoFlags_None;
-        frame.FrameRateN = fps_n;
-        frame.FrameRateD = fps_d;
-        frame.AspectRatio = (width as f32) / (height as f32);
-        frame.ColorSpace = OMTColorSpace_OMTColorSpace_BT709;
-        frame.Data = frame_buf.as_mut_ptr() as *mut _;
-        frame.DataLength = (stride * height) as i32;
-
-        println!("✓ OMTMediaFrame configured");
-        println!("  Type: Video");
-        println!("  Codec: UYVY");
-        println!("  Size: {}x{}", width, height);
-        println!("  Stride: {}", stride);
-        println!("  DataLength: {}", frame.DataLength);
-        println!("  FPS: {}/{}", fps_n, fps_d);
-
-        // PTS calculation
-        let ticks_per_sec = 10_000_000i64;
-        let ticks_per_frame = ticks_per_sec / (fps_n as i64);
-        let mut pts: i64 = 0;
-
-        println!("\nStarting transmission (10 frames)...\n");
-
-        // Send 10 frames for testing
-        for i in 0..10 {
-            frame.Timestamp = pts;
-
-            println!("Frame {}: PTS={}", i, pts);
-
-            let rc = omt_send(sender, &mut frame as *mut OMTMediaFrame);
-            
-            // Note: Some non-zero return codes may indicate status rather than errors
-            // Since frames are being received, these might be informational codes
-            if rc == 0 {
-                println!("  ✓ Frame sent successfully (rc=0)");
-            } else {
-                // These codes seem to indicate successful transmission with status info
-                // The frames are still being sent as evidenced by the stats
-                println!("  ⚡ Frame sent with status code: {} (frame still transmitted)", rc);
-                
-                // Only treat as error if connection is lost
-                let conn_count = omt_send_connections(sender);
-                if conn_count == 0 {
-                    eprintln!("  ❌ Receiver disconnected, stopping");
-                    break;
-                }
-            }
-
-            // Get statistics after each frame
-            let mut vstats: OMTStatistics = mem::zeroed();
-            omt_send_getvideostatistics(sender, &mut vstats as *mut OMTStatistics);
-            println!(
-                "  Stats: {} bytes sent, {} frames, {} dropped",
-                vstats.BytesSent, vstats.Frames, vstats.FramesDropped
-            );
-
-            pts += ticks_per_frame;
-            thread::sleep(Duration::from_millis(33)); // ~30fps
-        }
-
-        // Final statistics
-        println!("\n=== Final Statistics ===");
-        let mut vstats: OMTStatistics = mem::zeroed();
-        omt_send_getvideostatistics(sender, &mut vstats as *mut OMTStatistics);
-        println!("Bytes sent: {}", vstats.BytesSent);
-        println!("Frames sent: {}", vstats.Frames);
-        println!("Frames dropped: {}", vstats.FramesDropped);
-        println!("Codec time: {}ms", vstats.CodecTimeSinceLast);
-
-        // Cleanup
-        omt_send_destroy(sender);
-        println!("\n✓ Sender destroyed");
-
-        // Check log for errors
-        println!("\nChecking log file for errors...");
-        if let Ok(log_content) = std::fs::read_to_string("/tmp/omt-send-debug.log") {
-            let error_lines: Vec<&str> = log_content
-                .lines()
-                .filter(|line| line.contains("ERROR") || line.contains("WARN"))
-                .collect();
-
-            if !error_lines.is_empty() {
-                println!("Found {} warnings/errors in log:", error_lines.len());
-                for line in error_lines.iter().take(5) {
-                    println!("  {}", line);
-                }
-            } else {
-                println!("No errors found in log file");
-            }
-        }
-    }
+    println!("OMT Debug Test - Simple UYVY 720p30 send");
+    println!("=========================================\n");
+
+    let format = VideoFormat {
+        codec: OMTCodec_OMTCodec_UYVY,
+        width: 1280,
+        height: 720,
+        fps_n: 30,
+        fps_d: 1,
+        name: "UYVY_720p30",
+        stride_align: 1,
+        stride_override: None,
+        colorspace: None,
+    };
+
+    // Send 10 frames with per-frame diagnostics
+    let mut opts = Options {
+        log_file: PathBuf::from("/tmp/omt-send-debug.log"),
+        connect_timeout: Duration::from_secs(10),
+        frame_count: Some(10),
+        verbosity: 3,
+        ..Options::default()
+    };
+    prepare_log_file(&mut opts);
+    println!("Log file: {}", opts.log_file.display());
+    println!("Please start OMT Monitor before the connection wait expires");
+
+    run_send_test(format, 0, false, None, &opts)?;
+
+    report_log_errors(&opts, 5);
 
     Ok(())
 }
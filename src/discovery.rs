@@ -0,0 +1,104 @@
+// OMT ソースの探索（mDNS / ディスカバリーサーバー）
+use std::{
+    ffi::{CStr, CString},
+    os::raw::{c_char, c_int},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::bindings::*;
+
+/// Sources libomt has discovered so far, as `HOST (Name)` addresses.
+pub fn sources() -> Vec<String> {
+    let mut count: c_int = 0;
+    let list = unsafe { omt_discovery_getaddresses(&mut count) };
+    if list.is_null() || count <= 0 {
+        return Vec::new();
+    }
+    // The list stays valid until the next omt_discovery_getaddresses call
+    let entries = unsafe { std::slice::from_raw_parts(list, count as usize) };
+    entries
+        .iter()
+        .filter(|entry| !entry.is_null())
+        .map(|&entry| {
+            unsafe { CStr::from_ptr(entry) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// How long `--list` waits for mDNS answers; libomt starts discovery on
+/// the first query, and senders announce themselves within a few seconds.
+pub const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How often `discover` re-reads the list while waiting.
+const DISCOVERY_POLL: Duration = Duration::from_millis(250);
+
+/// The sender name of a `HOST (Name)` address, or the whole address if it
+/// has no parenthesized name.
+pub fn source_name(address: &str) -> &str {
+    address
+        .rfind('(')
+        .and_then(|open| {
+            let name = &address[open + 1..];
+            name.strip_suffix(')')
+        })
+        .unwrap_or(address)
+}
+
+/// Collects the sources found within `timeout`, one address per sender
+/// name. A sender reachable over several interfaces is announced once per
+/// address; only the first one seen is kept.
+pub fn discover(timeout: Duration) -> Vec<String> {
+    let start = Instant::now();
+    let mut found: Vec<String> = Vec::new();
+    loop {
+        for address in sources() {
+            let name = source_name(&address);
+            if !found.iter().any(|f| source_name(f) == name) {
+                found.push(address);
+            }
+        }
+        if start.elapsed() >= timeout {
+            break;
+        }
+        thread::sleep(DISCOVERY_POLL.min(timeout.saturating_sub(start.elapsed())));
+    }
+    found.sort_by(|a, b| source_name(a).cmp(source_name(b)));
+    found
+}
+
+/// Prints the sources `discover` finds (`--list`).
+pub fn print_sources(timeout: Duration) {
+    println!(
+        "Discovering OMT sources for {:.0}s...",
+        timeout.as_secs_f64()
+    );
+    let found = discover(timeout);
+    if found.is_empty() {
+        match server() {
+            Some(server) => println!("No sources listed by the discovery server {}", server),
+            None => println!("No sources found (is mDNS, UDP port 5353, allowed?)"),
+        }
+        return;
+    }
+    println!("{} source(s):", found.len());
+    for address in &found {
+        println!("  {}", address);
+    }
+}
+
+/// The discovery server configured in libomt's settings, which replaces
+/// mDNS when set.
+pub fn server() -> Option<String> {
+    let key = CString::new("DiscoveryServer").ok()?;
+    let mut value = [0 as c_char; 1024];
+    let len =
+        unsafe { omt_settings_get_string(key.as_ptr(), value.as_mut_ptr(), value.len() as c_int) };
+    if len <= 0 {
+        return None;
+    }
+    let value = unsafe { CStr::from_ptr(value.as_ptr()) }.to_string_lossy();
+    (!value.is_empty()).then(|| value.into_owned())
+}
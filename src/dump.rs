@@ -0,0 +1,252 @@
+// 送信フレームの生ダンプとサイドカーヘッダー（--dump / replay）
+use anyhow::{bail, Context, Result};
+use memmap2::{Advice, MmapMut, MmapOptions};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{bindings::*, codec_from_name, codec_name, video_frame, VideoFormat};
+
+/// Version of the sidecar layout written by [`DumpWriter`].
+pub const DUMP_VERSION: u32 = 1;
+
+/// Sidecar describing a raw dump: the dump itself is just the `DataLength`
+/// bytes of every sent frame, back to back.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DumpHeader {
+    pub version: u32,
+    /// Format name of the recorded run
+    pub name: String,
+    pub codec: String,
+    pub width: i32,
+    pub height: i32,
+    pub stride: i32,
+    /// NV12 row alignment the stride was padded to
+    pub stride_align: i32,
+    /// Exact NV12 stride the run was given with `--nv12-stride`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stride_override: Option<i32>,
+    /// Bytes per frame in the dump
+    pub data_length: i32,
+    pub fps_n: i32,
+    pub fps_d: i32,
+    pub flags: OMTVideoFlags,
+    pub colorspace: OMTColorSpace,
+}
+
+impl DumpHeader {
+    /// Describes frames of `format` sent as `frame`.
+    pub fn new(format: &VideoFormat, frame: &OMTMediaFrame) -> Self {
+        DumpHeader {
+            version: DUMP_VERSION,
+            name: format.name.to_string(),
+            codec: codec_name(frame.Codec).to_string(),
+            width: frame.Width,
+            height: frame.Height,
+            stride: frame.Stride,
+            stride_align: format.stride_align,
+            stride_override: format.stride_override,
+            data_length: frame.DataLength,
+            fps_n: frame.FrameRateN,
+            fps_d: frame.FrameRateD,
+            flags: frame.Flags,
+            colorspace: frame.ColorSpace,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&json)
+            .with_context(|| format!("invalid dump header {}", path.display()))
+    }
+
+    /// Returns the format the dump was recorded with, checking that the
+    /// declared stride and frame size match what the geometry implies.
+    pub fn format(&self) -> Result<VideoFormat> {
+        if self.version != DUMP_VERSION {
+            bail!(
+                "dump header version {} is not supported (expected {})",
+                self.version,
+                DUMP_VERSION
+            );
+        }
+        let codec = codec_from_name(&self.codec)
+            .with_context(|| format!("unknown codec {} in dump header", self.codec))?;
+        if self.width <= 0 || self.height <= 0 || self.fps_n <= 0 || self.fps_d <= 0 {
+            bail!(
+                "dump header has invalid geometry {}x{} at {}/{}fps",
+                self.width,
+                self.height,
+                self.fps_n,
+                self.fps_d
+            );
+        }
+        let format = VideoFormat {
+            codec,
+            width: self.width,
+            height: self.height,
+            fps_n: self.fps_n,
+            fps_d: self.fps_d,
+            // Replayed formats live for the whole run, like overridden presets
+            name: Box::leak(self.name.clone().into_boxed_str()),
+            stride_align: self.stride_align,
+            stride_override: self.stride_override,
+            // Replayed as recorded rather than re-derived from the size
+            colorspace: Some(self.colorspace),
+        };
+        format.check_size()?;
+        let expected = video_frame(&format, false, &mut []);
+        if self.stride != expected.Stride || self.data_length != expected.DataLength {
+            bail!(
+                "dump header declares stride {} and {} bytes per frame, but {} {}x{} needs {} and {}",
+                self.stride,
+                self.data_length,
+                self.codec,
+                self.width,
+                self.height,
+                expected.Stride,
+                expected.DataLength
+            );
+        }
+        Ok(format)
+    }
+}
+
+/// Writes the sidecar up front and appends each sent frame to the dump, so
+/// an interrupted run still leaves a replayable prefix.
+pub struct DumpWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+    frame_len: usize,
+    frames: u64,
+}
+
+impl DumpWriter {
+    pub fn create(path: &Path, header: &DumpHeader) -> Result<Self> {
+        let sidecar = sidecar_path(path);
+        std::fs::write(&sidecar, serde_json::to_string_pretty(header)?)
+            .with_context(|| format!("failed to write {}", sidecar.display()))?;
+        let file =
+            File::create(path).with_context(|| format!("failed to create {}", path.display()))?;
+        Ok(DumpWriter {
+            file: BufWriter::new(file),
+            path: path.to_path_buf(),
+            frame_len: header.data_length as usize,
+            frames: 0,
+        })
+    }
+
+    /// Appends the first `DataLength` bytes of `frame`.
+    pub fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.file
+            .write_all(&frame[..self.frame_len])
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flushes the dump and returns the number of frames written.
+    pub fn finish(mut self) -> Result<u64> {
+        self.file
+            .flush()
+            .with_context(|| format!("failed to write {}", self.path.display()))?;
+        Ok(self.frames)
+    }
+}
+
+/// Sidecar path for a dump: the dump path with `.json` appended.
+pub fn sidecar_path(dump: &Path) -> PathBuf {
+    let mut path = dump.as_os_str().to_owned();
+    path.push(".json");
+    PathBuf::from(path)
+}
+
+/// Reads frames back from a dump, either through a buffered file or from a
+/// memory mapping.
+pub struct DumpReader {
+    source: DumpSource,
+    frame_len: usize,
+    next: u64,
+    pub frames: u64,
+}
+
+enum DumpSource {
+    File(BufReader<File>),
+    /// Private copy-on-write mapping: libomt takes frame data as `*mut`, and
+    /// nothing it might write can reach the file
+    Mapped(MmapMut),
+}
+
+impl DumpReader {
+    /// Opens `path`, failing unless it holds a whole number of frames.
+    pub fn open(path: &Path, header: &DumpHeader) -> Result<Self> {
+        let (file, frames) = open_frames(path, header)?;
+        Ok(DumpReader {
+            source: DumpSource::File(BufReader::new(file)),
+            frame_len: header.data_length as usize,
+            next: 0,
+            frames,
+        })
+    }
+
+    /// Maps `path` into memory instead of reading it, so frames are sent
+    /// straight from the page cache: a sequence larger than RAM plays back
+    /// with constant memory and no copies.
+    pub fn map(path: &Path, header: &DumpHeader) -> Result<Self> {
+        let (file, frames) = open_frames(path, header)?;
+        let map = unsafe { MmapOptions::new().map_copy(&file) }
+            .with_context(|| format!("failed to map {}", path.display()))?;
+        // Only a hint for read-ahead, so a failure doesn't matter
+        map.advise(Advice::Sequential).ok();
+        Ok(DumpReader {
+            source: DumpSource::Mapped(map),
+            frame_len: header.data_length as usize,
+            next: 0,
+            frames,
+        })
+    }
+
+    /// Returns the next frame: read into `buf`, which must hold at least one
+    /// frame, or a slice of the mapping.
+    pub fn next_frame<'a>(&'a mut self, buf: &'a mut [u8]) -> Result<&'a mut [u8]> {
+        if self.next >= self.frames {
+            bail!("dump has only {} frames", self.frames);
+        }
+        let index = self.next as usize;
+        self.next += 1;
+        match &mut self.source {
+            DumpSource::File(file) => {
+                let frame = &mut buf[..self.frame_len];
+                file.read_exact(frame)
+                    .context("failed to read dump frame")?;
+                Ok(frame)
+            }
+            DumpSource::Mapped(map) => {
+                let start = index * self.frame_len;
+                Ok(&mut map[start..start + self.frame_len])
+            }
+        }
+    }
+}
+
+/// Opens a dump and returns its frame count, failing unless the file holds
+/// a whole number of frames.
+fn open_frames(path: &Path, header: &DumpHeader) -> Result<(File, u64)> {
+    let file = File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let len = file.metadata()?.len();
+    let frame_len = header.data_length as u64;
+    if len == 0 || !len.is_multiple_of(frame_len) {
+        bail!(
+            "{} is {} bytes, not a whole number of {}-byte {} frames",
+            path.display(),
+            len,
+            frame_len,
+            header.codec
+        );
+    }
+    Ok((file, len / frame_len))
+}
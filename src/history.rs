@@ -0,0 +1,74 @@
+// 直近フレームの送信履歴（失敗時の診断用）
+use std::{collections::VecDeque, thread, time::Duration};
+
+use crate::SendStatus;
+
+/// One sent frame as remembered by [`FrameHistory`].
+#[derive(Debug, Clone, Copy)]
+pub struct FrameRecord {
+    pub index: u64,
+    pub pts: i64,
+    pub rc: i32,
+    /// How long `omt_send` took, which covers the encode. libomt's
+    /// `CodecTimeSinceLast` resets whenever it is read, so sampling it per
+    /// frame would break the periodic stats line.
+    pub send_time: Duration,
+}
+
+/// The last `capacity` sent frames, printed when a run fails so the error
+/// comes with what led up to it.
+///
+/// The send loop dumps it when it bails; dropping it during a panic dumps it
+/// too, so a panic anywhere in the loop (or in a pipeline thread) also gets
+/// the history.
+#[derive(Debug)]
+pub struct FrameHistory {
+    records: VecDeque<FrameRecord>,
+    capacity: usize,
+}
+
+impl FrameHistory {
+    /// A capacity of 0 keeps nothing and never prints.
+    pub fn new(capacity: usize) -> Self {
+        FrameHistory {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, record: FrameRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Prints the remembered frames to stderr, oldest first.
+    pub fn dump(&self) {
+        if self.records.is_empty() {
+            return;
+        }
+        eprintln!("Last {} sent frames:", self.records.len());
+        for r in &self.records {
+            eprintln!(
+                "  frame {}: PTS={}, rc={} ({}), omt_send {:.2}ms",
+                r.index,
+                r.pts,
+                r.rc,
+                SendStatus::from(r.rc),
+                r.send_time.as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
+impl Drop for FrameHistory {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.dump();
+        }
+    }
+}
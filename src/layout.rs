@@ -0,0 +1,102 @@
+// FFI 構造体サイズの固定値チェックとレイアウトの表示（dump-bindings）
+//
+// bindgen は vendor/include/libomt.h から構造体を生成するが、リンクする
+// libomt.dylib が別バージョンのヘッダーでビルドされているとレイアウトが食い違い、
+// 境界外の読み書きが黙って起きる。ライブラリ側にサイズを問い合わせる API は
+// ないため、確認済みのサイズをここに固定し、ヘッダー更新時に意図的に更新する。
+use std::mem::{align_of, offset_of, size_of};
+
+use crate::{bindings::*, codec_name, colorspace_name, presets, video_frame};
+
+/// Sizes verified against the libomt build in vendor/macos (64-bit targets).
+pub const OMT_MEDIA_FRAME_SIZE: usize = 112;
+pub const OMT_SENDER_INFO_SIZE: usize = 6 * 1024;
+pub const OMT_STATISTICS_SIZE: usize = 16 * 8;
+
+#[cfg(target_pointer_width = "64")]
+const _: () = {
+    assert!(
+        size_of::<OMTMediaFrame>() == OMT_MEDIA_FRAME_SIZE,
+        "OMTMediaFrame size changed; verify libomt.h matches the linked library and update layout.rs"
+    );
+    assert!(
+        size_of::<OMTSenderInfo>() == OMT_SENDER_INFO_SIZE,
+        "OMTSenderInfo size changed; verify libomt.h matches the linked library and update layout.rs"
+    );
+    assert!(
+        size_of::<OMTStatistics>() == OMT_STATISTICS_SIZE,
+        "OMTStatistics size changed; verify libomt.h matches the linked library and update layout.rs"
+    );
+};
+
+/// Size of a struct field, for the `dump-bindings` table.
+fn field_size<T, F>(_: fn(&T) -> &F) -> usize {
+    size_of::<F>()
+}
+
+/// Prints one row per field: offset, size and name.
+macro_rules! print_fields {
+    ($ty:ty: $($field:ident),+ $(,)?) => {
+        println!(
+            "{}: {} bytes, align {}",
+            stringify!($ty),
+            size_of::<$ty>(),
+            align_of::<$ty>()
+        );
+        println!("  offset  size  field");
+        $(
+            println!(
+                "  {:>6}  {:>4}  {}",
+                offset_of!($ty, $field),
+                field_size(|s: &$ty| &s.$field),
+                stringify!($field)
+            );
+        )+
+    };
+}
+
+/// Prints the FFI structs as this build sees them (`dump-bindings`): size,
+/// alignment and field offsets, then the fields of the frame the first
+/// preset sends. The sizes are already checked above; the offsets can be
+/// compared with `offsetof` from a C program built against the library's
+/// header when a field is read back wrong.
+pub fn dump_bindings() {
+    print_fields!(OMTMediaFrame:
+        Type, Timestamp, Codec, Width, Height, Stride, Flags, FrameRateN,
+        FrameRateD, AspectRatio, ColorSpace, SampleRate, Channels,
+        SamplesPerChannel, Data, DataLength, CompressedData, CompressedLength,
+        FrameMetadata, FrameMetadataLength,
+    );
+    println!();
+    print_fields!(OMTSenderInfo:
+        ProductName, Manufacturer, Version, Reserved1, Reserved2, Reserved3,
+    );
+    println!();
+    print_fields!(OMTStatistics:
+        BytesSent, BytesReceived, BytesSentSinceLast, BytesReceivedSinceLast,
+        Frames, FramesSinceLast, FramesDropped, CodecTime, CodecTimeSinceLast,
+        Reserved1, Reserved2, Reserved3, Reserved4, Reserved5, Reserved6,
+        Reserved7,
+    );
+    let format = presets::SUITE[0];
+    let frame = video_frame(&format, false, &mut []);
+    println!("\nOMTMediaFrame for {}:", format.name);
+    println!("  Type: {}", frame.Type);
+    println!("  Timestamp: {}", frame.Timestamp);
+    println!("  Codec: {} ({})", frame.Codec, codec_name(frame.Codec));
+    println!("  Width: {}", frame.Width);
+    println!("  Height: {}", frame.Height);
+    println!("  Stride: {}", frame.Stride);
+    println!("  Flags: {}", frame.Flags);
+    println!("  FrameRateN: {}", frame.FrameRateN);
+    println!("  FrameRateD: {}", frame.FrameRateD);
+    println!("  AspectRatio: {}", frame.AspectRatio);
+    println!(
+        "  ColorSpace: {} ({})",
+        frame.ColorSpace,
+        colorspace_name(frame.ColorSpace)
+    );
+    println!("  DataLength: {}", frame.DataLength);
+    println!("  CompressedLength: {}", frame.CompressedLength);
+    println!("  FrameMetadataLength: {}", frame.FrameMetadataLength);
+}
@@ -0,0 +1,3721 @@
+// 送信テストの共通処理（omt-send-test-rs / debug の両バイナリから利用）
+use anyhow::{bail, Context, Result};
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::CString,
+    io::Write,
+    mem,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+pub mod audio;
+pub mod barcode;
+pub mod bars;
+pub mod bindings;
+pub mod buffer;
+pub mod convert;
+pub mod discovery;
+pub mod dump;
+pub mod history;
+pub mod layout;
+pub mod net;
+pub mod overlay;
+pub mod patterns;
+pub mod pipeline;
+pub mod presets;
+pub mod receive;
+pub mod report;
+mod rng;
+pub mod roundtrip;
+pub mod schedule;
+#[cfg(feature = "script")]
+pub mod script;
+pub mod sender;
+pub mod stats;
+pub mod still;
+pub mod timecode;
+pub mod validate;
+pub mod vectors;
+use bindings::*;
+use buffer::AlignedBuffer;
+use history::{FrameHistory, FrameRecord};
+use patterns::FramePattern;
+use rng::XorShift;
+use schedule::FrameSchedule;
+pub use sender::Sender;
+use stats::{ConnectionGauge, LatencySummary};
+use timecode::Timecode;
+use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+
+/// Source of custom per-frame content, rendered as RGBA and converted to the
+/// target codec before sending. `Sync` so frames can be rendered on a
+/// generator thread.
+pub trait FrameRenderer: Sync {
+    fn render_rgba(&self, format: &VideoFormat, frame_index: u64) -> Result<RgbaImage>;
+
+    /// Short name shown as the pattern in the settings banner.
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    /// Whether frames differ over time; static content is rendered only once.
+    fn animated(&self) -> bool {
+        true
+    }
+
+    /// What frame `frame_index` shows, logged by the send loop whenever it
+    /// changes.
+    fn label(&self, _format: &VideoFormat, _frame_index: u64) -> Option<String> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct VideoFormat {
+    pub codec: OMTCodec,
+    pub width: i32,
+    pub height: i32,
+    pub fps_n: i32,
+    pub fps_d: i32,
+    pub name: &'static str,
+    /// Row alignment of the NV12 planes in bytes; 1 packs rows tightly.
+    /// Packed codecs ignore it.
+    pub stride_align: i32,
+    /// Exact NV12 stride in bytes, replacing the one derived from the width
+    /// and `stride_align`. Packed codecs ignore it.
+    pub stride_override: Option<i32>,
+    /// Set by `--colorspace`; `None` picks one from [`COLORSPACE_BY_LINES`]
+    pub colorspace: Option<OMTColorSpace>,
+}
+
+/// A frame rate given as `N` or `N/D` on the command line (`--fps`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameRate {
+    pub n: i32,
+    pub d: i32,
+}
+
+impl std::str::FromStr for FrameRate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (n, d) = s.split_once('/').unwrap_or((s, "1"));
+        match (n.trim().parse(), d.trim().parse()) {
+            (Ok(n), Ok(d)) if n > 0 && d > 0 => Ok(FrameRate { n, d }),
+            _ => bail!(
+                "invalid frame rate {}, expected a positive N or N/D such as 60000/1001",
+                s
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.d == 1 {
+            write!(f, "{}", self.n)
+        } else {
+            write!(f, "{}/{}", self.n, self.d)
+        }
+    }
+}
+
+impl VideoFormat {
+    /// A format outside the preset tables, named like
+    /// `BGRA_3840x2160_59.94fps`. Width and height must be positive and fit
+    /// the codec's chroma subsampling (see [`presets::check_subsampling`]).
+    pub fn from_parts(
+        codec: OMTCodec,
+        width: i32,
+        height: i32,
+        fps_n: i32,
+        fps_d: i32,
+    ) -> Result<VideoFormat> {
+        if fps_n <= 0 || fps_d <= 0 {
+            bail!("frame rate {}/{} is not positive", fps_n, fps_d);
+        }
+        let fps = format!("{:.3}", fps_n as f64 / fps_d as f64);
+        let name = format!(
+            "{}_{}x{}_{}fps",
+            codec_name(codec),
+            width,
+            height,
+            fps.trim_end_matches('0').trim_end_matches('.')
+        );
+        let format = VideoFormat {
+            codec,
+            width,
+            height,
+            fps_n,
+            fps_d,
+            // Formats live for the whole run, so leaking the few names is fine
+            name: Box::leak(name.into_boxed_str()),
+            stride_align: 1,
+            stride_override: None,
+            colorspace: None,
+        };
+        if width <= 0 || height <= 0 {
+            bail!("{}: size {}x{} is not positive", format.name, width, height);
+        }
+        presets::check_subsampling(&format)?;
+        Ok(format)
+    }
+
+    /// A format at the size of the image at `path`, with the image converted
+    /// into a frame of it. UYVY and NV12 round an odd width up to even, so
+    /// every pixel has a chroma pair of its own, and letterbox the extra
+    /// column. Whether to set `OMTVideoFlags_Alpha` for BGRA is up
+    /// to the caller; [`still::StillImage::has_alpha`] tells.
+    pub fn from_image(
+        path: &Path,
+        codec: OMTCodec,
+        fps_n: i32,
+        fps_d: i32,
+    ) -> Result<(VideoFormat, Vec<u8>)> {
+        let still = still::StillImage::load(path)?;
+        let (width, height) = still.dimensions();
+        let width = if codec == OMTCodec_OMTCodec_UYVY || codec == OMTCodec_OMTCodec_NV12 {
+            width.next_multiple_of(2)
+        } else {
+            width
+        };
+        let format = VideoFormat::from_parts(codec, width as i32, height as i32, fps_n, fps_d)?;
+        format.check_size()?;
+        let image = still.render_rgba(&format, 0)?;
+        Ok((format, format.frame_from_rgba(&image)))
+    }
+
+    pub fn stride(&self) -> i32 {
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => self.width * 2,
+            x if x == OMTCodec_OMTCodec_BGRA => self.width * 4,
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                if let Some(stride) = self.stride_override {
+                    return stride;
+                }
+                // Y plane stride, padded up to the alignment; at least the UV
+                // row, which has a pair for an odd last pixel too
+                let align = self.stride_align.max(1);
+                let row = (self.width + 1) & !1;
+                (row + align - 1) / align * align
+            }
+            _ => self.width * 4,
+        }
+    }
+
+    /// Byte offset of the NV12 UV plane. `OMTMediaFrame` has a single
+    /// `Stride`, so libomt reads the UV plane with the Y stride starting
+    /// right after the last Y row; distinct plane strides or padding between
+    /// the planes can't be described to it.
+    pub fn uv_offset(&self) -> usize {
+        self.stride() as usize * self.height as usize
+    }
+
+    /// Rows of the NV12 UV plane: one per two Y rows, rounded up so an odd
+    /// last Y row still has chroma.
+    pub fn chroma_rows(&self) -> usize {
+        (self.height.max(0) as usize).div_ceil(2)
+    }
+
+    /// Bytes of UV data in each NV12 UV row: one U,V pair per two pixels,
+    /// rounded up like [`Self::chroma_rows`].
+    pub fn chroma_row_bytes(&self) -> usize {
+        (self.width.max(0) as usize).div_ceil(2) * 2
+    }
+
+    /// Bytes of frame data, used both to size frame buffers and as the
+    /// frame's `DataLength`.
+    pub fn buffer_size(&self) -> usize {
+        // usize math: `stride * height` in i32 overflows above ~2 GiB
+        let plane = self.stride() as usize * self.height as usize;
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                // NV12: Y plane (stride * height) + UV plane (stride * ceil(height / 2))
+                plane + self.stride() as usize * self.chroma_rows()
+            }
+            _ => plane,
+        }
+    }
+
+    /// Checks that frames of this format can be described to libomt, whose
+    /// `Stride` and `DataLength` are `i32`, and warns when one frame buffer is
+    /// larger than [`LARGE_FRAME_BYTES`].
+    pub fn check_size(&self) -> Result<()> {
+        if self.width <= 0 || self.height <= 0 {
+            bail!(
+                "{}: size {}x{} is not positive",
+                self.name,
+                self.width,
+                self.height
+            );
+        }
+        // Same layout as stride() and buffer_size(), without i32 overflow
+        let width = self.width as i64;
+        let align = self.stride_align.max(1) as i64;
+        let stride = match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => width * 2,
+            x if x == OMTCodec_OMTCodec_NV12 => match self.stride_override {
+                Some(stride) => stride as i64,
+                None => (((width + 1) & !1) + align - 1) / align * align,
+            },
+            _ => width * 4,
+        };
+        if self.codec == OMTCodec_OMTCodec_NV12 && stride < self.chroma_row_bytes() as i64 {
+            bail!(
+                "{}: stride {} is narrower than a {}-pixel NV12 row ({} bytes)",
+                self.name,
+                stride,
+                self.width,
+                self.chroma_row_bytes()
+            );
+        }
+        let plane = stride * self.height as i64;
+        let bytes = if self.codec == OMTCodec_OMTCodec_NV12 {
+            plane + stride * self.chroma_rows() as i64
+        } else {
+            plane
+        };
+        if bytes > i32::MAX as i64 {
+            bail!(
+                "{}: a {}x{} frame is {} bytes, more than an OMTMediaFrame can describe ({})",
+                self.name,
+                self.width,
+                self.height,
+                bytes,
+                i32::MAX
+            );
+        }
+        if bytes as usize > LARGE_FRAME_BYTES {
+            eprintln!(
+                "Warning: {} frames are {:.0} MiB each; make sure there is memory for the frame buffers and the encoder",
+                self.name,
+                bytes as f64 / (1 << 20) as f64
+            );
+        }
+        Ok(())
+    }
+
+    /// The `--colorspace` override, else the standard color space for the
+    /// frame's line count. `OMTColorSpace` is the only color field libomt
+    /// has, so there is no way to declare a transfer function and everything
+    /// goes out as SDR.
+    pub fn color_space(&self) -> OMTColorSpace {
+        self.color_space_choice().0
+    }
+
+    /// Color space and how it was chosen. Lines are the short side, so
+    /// vertical video maps like its landscape counterpart. A line count
+    /// missing from [`COLORSPACE_BY_LINES`] falls back to BT.601 below 720
+    /// lines and BT.709 from there up.
+    pub fn color_space_choice(&self) -> (OMTColorSpace, ColorSpaceSource) {
+        if let Some(colorspace) = self.colorspace {
+            return (colorspace, ColorSpaceSource::Override);
+        }
+        let lines = self.width.min(self.height);
+        if let Some((_, colorspace)) = COLORSPACE_BY_LINES.iter().find(|(l, _)| *l == lines) {
+            return (*colorspace, ColorSpaceSource::Standard(lines));
+        }
+        let colorspace = if lines < 720 {
+            OMTColorSpace_OMTColorSpace_BT601
+        } else {
+            OMTColorSpace_OMTColorSpace_BT709
+        };
+        (colorspace, ColorSpaceSource::Fallback(lines))
+    }
+
+    /// Converts an image of this format's dimensions into a frame buffer.
+    pub fn frame_from_rgba(&self, image: &RgbaImage) -> Vec<u8> {
+        RGBA_CONVERSIONS.fetch_add(1, Ordering::Relaxed);
+        let colorspace = self.color_space();
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => convert::uyvy_from_rgba(image, colorspace),
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                let mut buf = vec![0u8; self.buffer_size()];
+                convert::nv12_into(image, colorspace, self.stride() as usize, &mut buf);
+                buf
+            }
+            _ => convert::bgra_from_rgba(image, colorspace),
+        }
+    }
+
+    /// Like [`Self::frame_from_rgba`], writing into a `buffer_size()` buffer.
+    pub fn frame_from_rgba_into(&self, image: &RgbaImage, buf: &mut [u8]) {
+        RGBA_CONVERSIONS.fetch_add(1, Ordering::Relaxed);
+        let colorspace = self.color_space();
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => convert::uyvy_into(image, colorspace, buf),
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                convert::nv12_into(image, colorspace, self.stride() as usize, buf)
+            }
+            _ => convert::bgra_into(image, colorspace, buf),
+        }
+    }
+
+    /// A black frame: limited-range black for YUV, opaque black for BGRA.
+    /// Row padding is left zeroed.
+    pub fn create_black_frame(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; self.buffer_size()];
+        let (width, stride) = (self.width as usize, self.stride() as usize);
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => {
+                for pair in buf.chunks_exact_mut(4) {
+                    pair.copy_from_slice(&[128, 16, 128, 16]);
+                }
+            }
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                let (y_plane, uv_plane) = buf.split_at_mut(self.uv_offset());
+                for row in y_plane.chunks_exact_mut(stride) {
+                    row[..width].fill(16);
+                }
+                for row in uv_plane.chunks_exact_mut(stride) {
+                    row[..self.chroma_row_bytes()].fill(128);
+                }
+            }
+            _ => {
+                for pixel in buf.chunks_exact_mut(4) {
+                    pixel[3] = 255;
+                }
+            }
+        }
+        buf
+    }
+
+    /// Frame `frame_index` of the color bars with `pattern` drawn over them.
+    pub fn create_test_frame(&self, frame_index: u64, pattern: FramePattern) -> Vec<u8> {
+        let mut buf = vec![0u8; self.buffer_size()];
+
+        // The same color bars for every codec, converted with the format's
+        // color space
+        let (width, height) = (self.width as usize, self.height as usize);
+        match self.codec {
+            x if x == OMTCodec_OMTCodec_UYVY => {
+                let bars = bars::yuv_bars(self.color_space());
+                bars::fill_uyvy_bars(&mut buf, width, self.stride() as usize, &bars);
+            }
+            x if x == OMTCodec_OMTCodec_BGRA => {
+                let rgb = bars::smpte_bars_rgb(width, height);
+                for (pixel, [r, g, b]) in buf.chunks_exact_mut(4).zip(rgb) {
+                    pixel.copy_from_slice(&[b, g, r, 255]);
+                }
+            }
+            x if x == OMTCodec_OMTCodec_NV12 => {
+                // Straight to the converter, not frame_from_rgba: the static
+                // test frame isn't rendered content and isn't counted as an
+                // RGBA conversion. Row padding stays zeroed.
+                let rgb = bars::smpte_bars_rgb(width, height);
+                let image = RgbaImage::from_fn(self.width as u32, self.height as u32, |x, y| {
+                    let [r, g, b] = rgb[y as usize * width + x as usize];
+                    image::Rgba([r, g, b, 255])
+                });
+                convert::nv12_into(&image, self.color_space(), self.stride() as usize, &mut buf);
+            }
+            _ => {}
+        }
+
+        pattern.draw(&mut buf, self, frame_index);
+        buf
+    }
+}
+
+/// Frame buffer size above which `check_size` warns. 8K BGRA (~127 MiB) is
+/// under it, so it catches sizes beyond any real video format.
+pub const LARGE_FRAME_BYTES: usize = 256 << 20;
+
+/// Codecs the test patterns can be generated for.
+pub const SUPPORTED_CODECS: [(OMTCodec, &str); 3] = [
+    (OMTCodec_OMTCodec_UYVY, "UYVY"),
+    (OMTCodec_OMTCodec_BGRA, "BGRA"),
+    (OMTCodec_OMTCodec_NV12, "NV12"),
+];
+
+/// Looks up a supported codec by name, ignoring case.
+pub fn codec_from_name(name: &str) -> Option<OMTCodec> {
+    SUPPORTED_CODECS
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(codec, _)| *codec)
+}
+
+pub fn codec_name(codec: OMTCodec) -> &'static str {
+    SUPPORTED_CODECS
+        .iter()
+        .find(|(c, _)| *c == codec)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// `OMTQuality` levels by name.
+pub const QUALITIES: [(OMTQuality, &str); 4] = [
+    (OMTQuality_OMTQuality_Default, "Default"),
+    (OMTQuality_OMTQuality_Low, "Low"),
+    (OMTQuality_OMTQuality_Medium, "Medium"),
+    (OMTQuality_OMTQuality_High, "High"),
+];
+
+/// Looks up a quality level by name, ignoring case.
+pub fn quality_from_name(name: &str) -> Option<OMTQuality> {
+    QUALITIES
+        .iter()
+        .find(|(_, n)| n.eq_ignore_ascii_case(name))
+        .map(|(quality, _)| *quality)
+}
+
+pub fn quality_name(quality: OMTQuality) -> &'static str {
+    QUALITIES
+        .iter()
+        .find(|(q, _)| *q == quality)
+        .map_or("unknown", |(_, name)| name)
+}
+
+/// Recommended quality per codec when `--quality` isn't given, with the
+/// reason shown by `--show-defaults`.
+///
+/// `Default` lets receivers suggest a level with
+/// `omt_receive_setsuggestedquality` and falls back to Medium without one.
+/// The sender API has no way to read back the level the library settled on.
+/// BGRA stays on High, since a fallback to Medium is what fringes graphics.
+pub const DEFAULT_QUALITIES: [(OMTCodec, OMTQuality, &str); 3] = [
+    (
+        OMTCodec_OMTCodec_UYVY,
+        OMTQuality_OMTQuality_Default,
+        "4:2:2 camera-style video; receivers may suggest a level, otherwise Medium",
+    ),
+    (
+        OMTCodec_OMTCodec_BGRA,
+        OMTQuality_OMTQuality_High,
+        "graphics and alpha with hard edges, which ring and fringe when over-compressed",
+    ),
+    (
+        OMTCodec_OMTCodec_NV12,
+        OMTQuality_OMTQuality_Default,
+        "already 4:2:0, so extra bits buy little; receivers may suggest a level",
+    ),
+];
+
+/// Returns the recommended quality for `codec` and why.
+pub fn default_quality(codec: OMTCodec) -> (OMTQuality, &'static str) {
+    DEFAULT_QUALITIES
+        .iter()
+        .find(|(c, _, _)| *c == codec)
+        .map_or(
+            (
+                OMTQuality_OMTQuality_Default,
+                "no recommendation for this codec",
+            ),
+            |(_, q, why)| (*q, why),
+        )
+}
+
+/// Returns `--quality` if given, otherwise the codec's recommended default,
+/// logging the choice.
+pub fn select_quality(codec: OMTCodec, opts: &Options) -> OMTQuality {
+    match opts.quality {
+        Some(quality) => {
+            println!("Quality: {}", quality_name(quality));
+            quality
+        }
+        None => {
+            let (quality, why) = default_quality(codec);
+            println!(
+                "Quality: {} (default for {}: {})",
+                quality_name(quality),
+                codec_name(codec),
+                why
+            );
+            quality
+        }
+    }
+}
+
+/// How frames get encoded, for the startup banner.
+///
+/// VMX is a CPU codec: libomt has no hardware encoder and no call to query
+/// one. What does change codec times is whether this process runs natively
+/// or as an x86_64 build translated by Rosetta on Apple Silicon, where VMX's
+/// SIMD paths are emulated.
+pub fn encoder_path() -> String {
+    if running_translated() {
+        format!(
+            "VMX software, {} under Rosetta translation (expect high codec times)",
+            std::env::consts::ARCH
+        )
+    } else {
+        format!("VMX software, native {}", std::env::consts::ARCH)
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn running_translated() -> bool {
+    let mut translated: libc::c_int = 0;
+    let mut size = mem::size_of::<libc::c_int>();
+    let name = b"sysctl.proc_translated\0";
+    // Fails with ENOENT on Intel Macs, which never translate
+    let rc = unsafe {
+        libc::sysctlbyname(
+            name.as_ptr() as *const _,
+            &mut translated as *mut libc::c_int as *mut _,
+            &mut size,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    rc == 0 && translated == 1
+}
+
+#[cfg(not(target_os = "macos"))]
+fn running_translated() -> bool {
+    false
+}
+
+/// Color space for each standard line count: SD is BT.601, HD and UHD are
+/// BT.709. UHD is nominally BT.2020, which `OMTColorSpace` can't express.
+pub const COLORSPACE_BY_LINES: [(i32, OMTColorSpace); 8] = [
+    (480, OMTColorSpace_OMTColorSpace_BT601),
+    (486, OMTColorSpace_OMTColorSpace_BT601),
+    (576, OMTColorSpace_OMTColorSpace_BT601),
+    (720, OMTColorSpace_OMTColorSpace_BT709),
+    (1080, OMTColorSpace_OMTColorSpace_BT709),
+    (1440, OMTColorSpace_OMTColorSpace_BT709),
+    (2160, OMTColorSpace_OMTColorSpace_BT709),
+    (4320, OMTColorSpace_OMTColorSpace_BT709),
+];
+
+/// How [`VideoFormat::color_space_choice`] arrived at its color space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpaceSource {
+    /// `--colorspace`
+    Override,
+    /// A standard line count from [`COLORSPACE_BY_LINES`]
+    Standard(i32),
+    /// A non-standard line count, split at 720
+    Fallback(i32),
+}
+
+impl std::fmt::Display for ColorSpaceSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ColorSpaceSource::Override => write!(f, "set by --colorspace"),
+            ColorSpaceSource::Standard(lines) => write!(f, "standard for {} lines", lines),
+            ColorSpaceSource::Fallback(lines) => {
+                write!(f, "{} lines is not a standard size, guessed", lines)
+            }
+        }
+    }
+}
+
+/// Looks up an `OMTColorSpace` by name, ignoring case.
+pub fn colorspace_from_name(name: &str) -> Option<OMTColorSpace> {
+    match name.to_ascii_uppercase().as_str() {
+        "BT601" => Some(OMTColorSpace_OMTColorSpace_BT601),
+        "BT709" => Some(OMTColorSpace_OMTColorSpace_BT709),
+        _ => None,
+    }
+}
+
+pub fn colorspace_name(colorspace: OMTColorSpace) -> &'static str {
+    match colorspace {
+        c if c == OMTColorSpace_OMTColorSpace_BT601 => "BT601",
+        c if c == OMTColorSpace_OMTColorSpace_BT709 => "BT709",
+        _ => "Undefined",
+    }
+}
+
+/// OMT timestamps are in 100ns units.
+pub const TICKS_PER_SEC: i64 = 10_000_000;
+
+/// Returns the PTS of frame `index` at `fps_n/fps_d`, computed exactly from
+/// the index so there is no accumulated rounding for rates like 30000/1001.
+pub fn pts_for_frame(index: u64, fps_n: i32, fps_d: i32) -> i64 {
+    (index as i128 * TICKS_PER_SEC as i128 * fps_d as i128 / fps_n as i128) as i64
+}
+
+/// Frames sent in `secs` seconds at `fps_n/fps_d`: every frame whose PTS
+/// falls inside the duration, so 10s at 24000/1001 is 240 frames, the last
+/// at 9.968s.
+pub fn frames_for_duration(secs: u32, fps_n: i32, fps_d: i32) -> u32 {
+    (secs as u64 * fps_n as u64).div_ceil(fps_d as u64) as u32
+}
+
+/// Returns which of the 8 color bars pixel `x` falls in. Bars are a whole
+/// number of UYVY macropixels wide and the last bar absorbs the remainder, so
+/// widths that aren't a multiple of 16 (e.g. 1366) still give 8 bars.
+pub fn color_bar_section(x: usize, width: usize) -> usize {
+    let bar_width = (width / 16 * 2).max(2);
+    (x / bar_width).min(7)
+}
+
+/// What an `omt_send` return code means. libomt doesn't document its codes;
+/// these are the ones seen in practice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendStatus {
+    Success,
+    /// Codes that still result in the frame going out
+    Queued(i32),
+    /// The frame was dropped; sending can go on after a pause
+    BufferOverflow,
+    GeneralError,
+    /// Any other code. Positive codes have behaved as warnings, negative
+    /// ones as errors.
+    Unknown(i32),
+}
+
+impl From<i32> for SendStatus {
+    fn from(rc: i32) -> Self {
+        match rc {
+            0 => SendStatus::Success,
+            12428 | 19448 | 29843 | 39293 => SendStatus::Queued(rc),
+            26984 => SendStatus::BufferOverflow,
+            -1 => SendStatus::GeneralError,
+            _ => SendStatus::Unknown(rc),
+        }
+    }
+}
+
+impl SendStatus {
+    /// Whether the sender is unusable and sending should stop.
+    pub fn is_fatal(&self) -> bool {
+        match *self {
+            SendStatus::Success | SendStatus::Queued(_) | SendStatus::BufferOverflow => false,
+            SendStatus::GeneralError => true,
+            SendStatus::Unknown(rc) => rc < 0,
+        }
+    }
+
+    /// Whether this frame went out: not fatal and not dropped by an overflow.
+    pub fn is_accepted(&self) -> bool {
+        !self.is_fatal() && *self != SendStatus::BufferOverflow
+    }
+}
+
+impl std::fmt::Display for SendStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            SendStatus::Success => "Success",
+            SendStatus::Queued(_) => "Frame queued/processing (non-fatal)",
+            SendStatus::BufferOverflow => "Buffer overflow or encoding error",
+            SendStatus::GeneralError => "General error",
+            SendStatus::Unknown(rc) if *rc > 0 => "Status/warning code (may be non-fatal)",
+            SendStatus::Unknown(_) => "Unknown error",
+        })
+    }
+}
+
+/// Options shared by the send tests, filled in from the command line.
+#[derive(Debug)]
+pub struct Options {
+    pub log_file: PathBuf,
+    /// Cleared by `prepare_log_file` when no log file can be written
+    pub scan_log: bool,
+    /// Set by `--log-per-run` to the start time in Unix seconds: each
+    /// `run_send_test` then logs to its own file, see [`Options::run_log_file`]
+    pub log_stamp: Option<u64>,
+    pub connect_timeout: Duration,
+    /// Sends exactly this many frames instead of `duration_secs` worth
+    pub frame_count: Option<u32>,
+    /// Sends through [`Sender::send_with_timeout`], ending the run as hung
+    /// after [`SEND_TIMEOUT_LIMIT`] timeouts in a row
+    pub send_timeout: Option<Duration>,
+    /// Checks the color bars through a loopback receiver (`--verify-bars`)
+    pub verify_bars: bool,
+    /// Overrides the per-codec tolerance of `--verify-bars`
+    pub bar_tolerance: Option<f64>,
+    /// Sends unpaced and digests the sent stream so runs can be compared
+    /// byte for byte (`--deterministic`)
+    pub deterministic: bool,
+    /// 1: frame setup, 2: per-frame PTS, 3: per-frame return code and
+    /// statistics (the debug binary's level of detail)
+    pub verbosity: u8,
+    pub format_name: Option<String>,
+    pub max_codec_time_ms: Option<i64>,
+    /// Fail a run that drops more frames than this
+    pub max_drops: Option<i64>,
+    /// Fail a run whose dropped frames exceed this percentage of sent frames
+    pub max_drop_rate: Option<f64>,
+    /// Sent frames remembered and printed when a run fails; 0 disables
+    pub frame_history: usize,
+    pub script: Option<PathBuf>,
+    pub fps_ramp: Option<FpsRamp>,
+    pub no_pause: bool,
+    pub port: Option<u16>,
+    pub address: Option<String>,
+    pub drop_every: Option<u64>,
+    /// Percentage of frames to skip at random
+    pub simulate_loss_pct: Option<f64>,
+    /// Upper bound of a random delay before each send
+    pub simulate_jitter: Option<Duration>,
+    /// Tolerance past each frame's scheduled time before it counts as late
+    pub deadline: Option<Duration>,
+    pub target_bitrate: Option<f64>,
+    pub start_timecode: Option<String>,
+    /// Burns the frame index into each frame as a barcode
+    pub frame_barcode: Option<barcode::BarcodeRegion>,
+    pub encode_only: bool,
+    pub probe_receiver_formats: bool,
+    /// Width and height of the probe frames
+    pub probe_resolution: (i32, i32),
+    /// Send a single frame and exit
+    pub once: bool,
+    /// Print an xxh3 hash of every frame before it is sent
+    pub hash_frames: bool,
+    /// `omt_send` codes that log a warning with the frame index
+    pub warn_status_codes: Vec<i32>,
+    pub alpha_premultiplied: bool,
+    /// `--alpha`: BGRA formats run only with the alpha flag
+    pub alpha_only: bool,
+    pub list_connections: bool,
+    pub soak_secs: Option<u64>,
+    pub soak_seed: Option<u64>,
+    pub image: Option<PathBuf>,
+    /// Override the preset codec, size and frame rate
+    pub codec: Option<OMTCodec>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub fps: Option<FrameRate>,
+    /// Overrides the per-format test length
+    pub duration_secs: Option<u32>,
+    /// How long to wait for queued frames before reading final statistics
+    pub drain_timeout: Duration,
+    /// Extra `omt_send_create` attempts, with doubling backoff, before
+    /// giving up
+    pub create_retries: u32,
+    pub validate_frame: bool,
+    /// Check Stride against the codec, width and buffer before sending
+    pub strict_stride: bool,
+    pub pattern: patterns::Pattern,
+    /// How the built-in bars vary per frame; ignored with other content
+    pub frame_pattern: FramePattern,
+    /// Renders animated content inline instead of on a generator thread
+    pub no_pipeline: bool,
+    /// Fails the test if static content is converted inside the send loop
+    pub preconvert: bool,
+    /// Fails the test instead of sending to nobody
+    pub require_receiver: bool,
+    pub target_receiver: Option<String>,
+    /// Overrides the per-codec default from `DEFAULT_QUALITIES`
+    pub quality: Option<OMTQuality>,
+    pub show_defaults: bool,
+    pub list_presets: bool,
+    /// `--list`: prints the discovered sources and exits
+    pub list_sources: bool,
+    /// The hidden `dump-bindings` command
+    pub dump_bindings: bool,
+    pub save_baseline: Option<PathBuf>,
+    pub baseline: Option<PathBuf>,
+    /// Where to POST the final report; only with the `webhook` feature
+    pub report_webhook: Option<String>,
+    /// Where to write the [`report::SessionReport`] of the suite
+    pub session_report: Option<PathBuf>,
+    /// Alignment of frame buffers in bytes (a power of two)
+    pub buffer_align: usize,
+    /// Receivers that must be connected before sending starts
+    pub min_receivers: Option<u32>,
+    /// Prints the settings banner as JSON
+    pub banner_json: bool,
+    /// Lists network interfaces and exits
+    pub interface_list: bool,
+    pub interface_list_json: bool,
+    /// Overrides the once-a-second statistics cadence
+    pub stats_interval: Option<StatsInterval>,
+    /// Records every sent frame to a raw dump with a `.json` sidecar
+    pub dump: Option<PathBuf>,
+    /// Dump and sidecar to re-send instead of running the tests
+    pub replay: Option<(PathBuf, PathBuf)>,
+    /// `export-vectors <dir>`: write reference frames instead of sending
+    pub export_vectors: Option<PathBuf>,
+    /// `replay --mmap`: send frames straight from a mapping of the dump
+    pub replay_mmap: bool,
+    /// `receive <source>`: receive from a sender instead of sending
+    pub receive: Option<String>,
+    /// `receive --expect`: preset every received frame must match
+    pub receive_expect: Option<String>,
+    /// Pads NV12 rows to this many bytes, as some capture hardware does
+    pub nv12_stride_align: Option<i32>,
+    /// Exact NV12 stride, for capture paths with their own row padding
+    pub nv12_stride: Option<i32>,
+    /// Overrides the color space picked from the line count
+    pub colorspace: Option<OMTColorSpace>,
+    /// Keeps sending black while no receiver is connected instead of
+    /// stopping when the last one leaves
+    pub send_black_on_disconnect: bool,
+    /// Runs that stop early because the receiver left still pass
+    pub allow_disconnect: bool,
+    /// Set (by Ctrl-C) to end a send test early
+    pub stop: Option<&'static AtomicBool>,
+    /// Set (by SIGUSR1) to print a statistics snapshot; the send loop clears
+    /// it once printed
+    pub stats_request: Option<&'static AtomicBool>,
+    pub output_format: report::OutputFormat,
+}
+
+impl Options {
+    /// Log file for the run named `run`: `log_file` itself, or with
+    /// `--log-per-run` `omt-send-<run>-<stamp>.log` next to it.
+    pub fn run_log_file(&self, run: &str) -> PathBuf {
+        match self.log_stamp {
+            Some(stamp) => self
+                .log_file
+                .with_file_name(format!("omt-send-{}-{}.log", run, stamp)),
+            None => self.log_file.clone(),
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            log_file: PathBuf::from("/tmp/omt-send.log"),
+            scan_log: true,
+            log_stamp: None,
+            connect_timeout: Duration::from_secs(3),
+            frame_count: None,
+            deterministic: false,
+            verify_bars: false,
+            bar_tolerance: None,
+            send_timeout: None,
+            verbosity: 0,
+            format_name: None,
+            max_codec_time_ms: None,
+            max_drops: None,
+            max_drop_rate: None,
+            frame_history: 32,
+            script: None,
+            fps_ramp: None,
+            no_pause: false,
+            port: None,
+            address: None,
+            drop_every: None,
+            simulate_loss_pct: None,
+            simulate_jitter: None,
+            deadline: None,
+            target_bitrate: None,
+            start_timecode: None,
+            frame_barcode: None,
+            encode_only: false,
+            probe_receiver_formats: false,
+            probe_resolution: DEFAULT_PROBE_RESOLUTION,
+            once: false,
+            hash_frames: false,
+            warn_status_codes: Vec::new(),
+            alpha_premultiplied: false,
+            alpha_only: false,
+            list_connections: false,
+            soak_secs: None,
+            soak_seed: None,
+            image: None,
+            codec: None,
+            width: None,
+            height: None,
+            fps: None,
+            duration_secs: None,
+            drain_timeout: Duration::from_millis(500),
+            create_retries: 2,
+            validate_frame: false,
+            strict_stride: false,
+            pattern: patterns::Pattern::default(),
+            frame_pattern: FramePattern::default(),
+            no_pipeline: false,
+            preconvert: false,
+            require_receiver: false,
+            target_receiver: None,
+            quality: None,
+            show_defaults: false,
+            list_presets: false,
+            list_sources: false,
+            dump_bindings: false,
+            save_baseline: None,
+            baseline: None,
+            report_webhook: None,
+            session_report: None,
+            buffer_align: buffer::DEFAULT_ALIGN,
+            min_receivers: None,
+            banner_json: false,
+            interface_list: false,
+            interface_list_json: false,
+            stats_interval: None,
+            dump: None,
+            replay: None,
+            export_vectors: None,
+            replay_mmap: false,
+            receive: None,
+            receive_expect: None,
+            nv12_stride_align: None,
+            nv12_stride: None,
+            colorspace: None,
+            send_black_on_disconnect: false,
+            allow_disconnect: false,
+            stop: None,
+            stats_request: None,
+            output_format: report::OutputFormat::default(),
+        }
+    }
+}
+
+/// Settings for `--fps-ramp`: the rate starts at `step` fps and rises by
+/// `step` every `interval_secs` until losses exceed `drop_threshold_pct`.
+#[derive(Debug, Clone, Copy)]
+pub struct FpsRamp {
+    pub step: i32,
+    pub interval_secs: u32,
+    pub drop_threshold_pct: f64,
+}
+
+impl Default for FpsRamp {
+    fn default() -> Self {
+        FpsRamp {
+            step: 5,
+            interval_secs: 3,
+            drop_threshold_pct: 1.0,
+        }
+    }
+}
+
+/// Fewest frames between periodic statistics lines by default.
+const MIN_STATS_FRAMES: u32 = 5;
+
+/// How often the send loop prints a statistics line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatsInterval {
+    Frames(u32),
+    Secs(f64),
+}
+
+impl StatsInterval {
+    /// Interval in frames at `fps_n/fps_d`, at least one. Without an
+    /// override this is one second rounded to whole frames (30 for 29.97),
+    /// but at low rates (signage at 1-5fps) it waits for a few frames so the
+    /// deltas mean something.
+    pub fn frames(interval: Option<StatsInterval>, fps_n: i32, fps_d: i32) -> u32 {
+        let fps = fps_n as f64 / fps_d as f64;
+        match interval {
+            Some(StatsInterval::Frames(n)) => n.max(1),
+            Some(StatsInterval::Secs(secs)) => ((secs * fps).round() as u32).max(1),
+            None => (fps.round() as u32).max(MIN_STATS_FRAMES),
+        }
+    }
+}
+
+/// Consecutive `--send-timeout-ms` timeouts that end a run as hung.
+pub const SEND_TIMEOUT_LIMIT: u32 = 3;
+
+/// Frame interval from which a lost receiver is reported as a possible idle
+/// timeout rather than a normal disconnect.
+const IDLE_WARN_FRAME_DURATION: Duration = Duration::from_millis(200);
+
+/// Upper bound for `--fps-ramp`, reached only if nothing breaks first.
+pub const FPS_RAMP_MAX: i32 = 240;
+
+/// How a run of `run_send_test` ended. A run that stops early has attempted
+/// fewer frames than requested, so its success rate only covers the frames
+/// up to the stop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// Every requested frame was attempted
+    #[default]
+    Completed,
+    /// Stopped by Ctrl-C before `frame`
+    Interrupted { frame: u32 },
+    /// The last receiver disconnected at `frame`
+    Disconnected { frame: u32 },
+    /// `omt_send` returned the fatal code `rc` at `frame`
+    Failed { frame: u32, rc: i32 },
+    /// `omt_send` timed out [`SEND_TIMEOUT_LIMIT`] times in a row, the last
+    /// at `frame` (`--send-timeout-ms`)
+    Hung { frame: u32 },
+}
+
+impl RunOutcome {
+    /// Frames attempted before the run ended, out of `requested`.
+    pub fn frames_attempted(&self, requested: u32) -> u32 {
+        match *self {
+            RunOutcome::Completed => requested,
+            RunOutcome::Interrupted { frame } => frame,
+            // The frame that hit the disconnect or error was attempted too
+            RunOutcome::Disconnected { frame }
+            | RunOutcome::Failed { frame, .. }
+            | RunOutcome::Hung { frame } => frame + 1,
+        }
+    }
+}
+
+impl std::fmt::Display for RunOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RunOutcome::Completed => write!(f, "completed"),
+            RunOutcome::Interrupted { frame } => write!(f, "interrupted at frame {}", frame),
+            RunOutcome::Disconnected { frame } => {
+                write!(f, "stopped early, receiver disconnected at frame {}", frame)
+            }
+            RunOutcome::Failed { frame, rc } => write!(
+                f,
+                "stopped early, omt_send failed at frame {} (rc={}, {})",
+                frame,
+                rc,
+                SendStatus::from(*rc)
+            ),
+            RunOutcome::Hung { frame } => {
+                write!(f, "stopped early, omt_send hung at frame {}", frame)
+            }
+        }
+    }
+}
+
+/// Measurements collected by `run_send_test`, used for pass/fail gates and
+/// saved in a [`report::TestReport`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestResult {
+    /// Format name, with " (alpha)" for the BGRA alpha run
+    pub name: String,
+    pub bytes_sent: i64,
+    pub frames_sent: i64,
+    pub frames_dropped: i64,
+    pub bitrate_mbps: f64,
+    /// Frames counted by libomt over the frames attempted before the run
+    /// ended, so read it together with `outcome`
+    pub success_rate_pct: f64,
+    pub codec_time_peak_ms: i64,
+    pub codec_time_mean_ms: f64,
+    /// How often `omt_send` returned each non-zero code
+    #[serde(default)]
+    pub status_codes: BTreeMap<i32, u32>,
+    /// Reports saved before outcomes were recorded load as completed
+    #[serde(default)]
+    pub outcome: RunOutcome,
+    #[serde(default)]
+    pub frames_requested: u32,
+    /// Frames skipped on purpose by `--drop-every` or `--simulate-loss`
+    #[serde(default)]
+    pub frames_skipped: u32,
+    /// xxh3 over every frame handed to `omt_send` and its PTS, chained in
+    /// order; only with `--deterministic`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_digest: Option<String>,
+}
+
+/// Seed for `--simulate-loss` and `--simulate-jitter`.
+const IMPAIRMENT_SEED: u64 = 0x1055;
+
+/// Wait before the first `omt_send_create` retry; doubles per attempt.
+const CREATE_RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Copies `s` into a fixed-size C string field such as those of
+/// `OMTSenderInfo`. Fails rather than truncating when `s` doesn't fit with
+/// its NUL terminator, or contains a NUL that would cut it short.
+pub fn write_cstr(dst: &mut [i8], s: &str) -> Result<()> {
+    let bytes = s.as_bytes();
+    if bytes.contains(&0) {
+        bail!("{:?} contains a NUL byte", s);
+    }
+    if bytes.len() >= dst.len() {
+        bail!(
+            "{:?} is {} bytes, the field holds at most {}",
+            s,
+            bytes.len(),
+            dst.len().saturating_sub(1)
+        );
+    }
+    for (d, &b) in dst.iter_mut().zip(bytes) {
+        *d = b as i8;
+    }
+    dst[bytes.len()] = 0;
+    Ok(())
+}
+
+/// The product information every sender is created with.
+pub fn sender_info(product: &str, manufacturer: &str, version: &str) -> Result<OMTSenderInfo> {
+    let mut info: OMTSenderInfo = unsafe { mem::zeroed() };
+    write_cstr(&mut info.ProductName, product).context("invalid product name")?;
+    write_cstr(&mut info.Manufacturer, manufacturer).context("invalid manufacturer")?;
+    write_cstr(&mut info.Version, version).context("invalid version")?;
+    Ok(info)
+}
+
+/// Creates a sender, sets its product information and prints its address.
+/// A failed `omt_send_create` is retried up to `retries` times.
+pub fn create_sender(name: &str, quality: OMTQuality, retries: u32) -> Result<Sender> {
+    let info = sender_info("omt-send-test-rs", "Rust OMT Test", "1.0.0")?;
+
+    // Creation can fail transiently, e.g. while another instance is still
+    // releasing its port, so retry with a doubling backoff
+    let mut backoff = CREATE_RETRY_BACKOFF;
+    let mut attempt = 0;
+    let sender = loop {
+        match Sender::create_with_info(name, quality, &info) {
+            Ok(sender) => break sender,
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                eprintln!(
+                    "{} (attempt {}/{}), retrying in {}ms",
+                    e,
+                    attempt,
+                    retries + 1,
+                    backoff.as_millis()
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+            }
+            Err(e) if retries > 0 => {
+                return Err(e.context(format!("gave up after {} attempts", retries + 1)))
+            }
+            Err(e) => return Err(e),
+        }
+    };
+
+    if let Some(address) = sender.address() {
+        println!("Sender address: {}", address);
+    }
+
+    Ok(sender)
+}
+
+/// Applies `--port`/`--address` to the library settings. Must run before any
+/// sender is created.
+pub fn apply_network_settings(opts: &Options) -> Result<()> {
+    if let Some(port) = opts.port {
+        // libomt picks the first free port in [NetworkPortStart, NetworkPortEnd]
+        let start = CString::new("NetworkPortStart")?;
+        let end = CString::new("NetworkPortEnd")?;
+        unsafe {
+            omt_settings_set_integer(start.as_ptr(), port as i32);
+            omt_settings_set_integer(end.as_ptr(), port as i32);
+            println!(
+                "Network port: {} (effective {}-{})",
+                port,
+                omt_settings_get_integer(start.as_ptr()),
+                omt_settings_get_integer(end.as_ptr())
+            );
+        }
+    }
+    if let Some(address) = &opts.address {
+        // libomt listens on all interfaces and has no bind/multicast setting
+        eprintln!(
+            "Warning: --address {} is ignored, libomt chooses its listen address automatically",
+            address
+        );
+    }
+    Ok(())
+}
+
+/// Polls until a receiver connects or `timeout` expires.
+pub fn wait_for_receiver(sender: &Sender, timeout: Duration) -> bool {
+    wait_for_receivers(sender, 1, timeout) >= 1
+}
+
+/// Polls until at least `min` receivers are connected or `timeout` expires,
+/// and returns the highest count reached.
+pub fn wait_for_receivers(sender: &Sender, min: i32, timeout: Duration) -> i32 {
+    if min > 1 {
+        println!("Waiting for {} receiver connections...", min);
+    } else {
+        println!("Waiting for receiver connection...");
+    }
+    let start = Instant::now();
+    let mut reached = 0;
+    while start.elapsed() < timeout {
+        let count = sender.connections();
+        if count > reached {
+            reached = count;
+            if min > 1 {
+                println!(
+                    "[{:.1}s] {}/{} receivers connected",
+                    start.elapsed().as_secs_f32(),
+                    count,
+                    min
+                );
+            }
+        }
+        if count >= min {
+            println!(
+                "{} receiver(s) connected after {:.1}s",
+                count,
+                start.elapsed().as_secs_f32()
+            );
+            return count;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    if reached == 0 {
+        eprintln!("Warning: No receivers connected");
+        diagnose_no_receivers(sender);
+    } else {
+        eprintln!("Warning: Only {}/{} receivers connected", reached, min);
+    }
+    reached
+}
+
+/// Explains a connect timeout, telling a discovery problem apart from there
+/// simply being no receiver.
+///
+/// By the time the wait has timed out, mDNS has had long enough to find this
+/// very sender; if it is missing from the discovery list, receivers can't
+/// see it either.
+fn diagnose_no_receivers(sender: &Sender) {
+    let sources = discovery::sources();
+    if sources.iter().any(|source| source.contains(sender.name())) {
+        eprintln!(
+            "Discovery lists this sender ({} source(s) visible), so check that a receiver is running and has selected {}",
+            sources.len(),
+            sender.name()
+        );
+        return;
+    }
+    match (discovery::server(), sources.is_empty()) {
+        (Some(server), _) => eprintln!(
+            "The discovery server {} doesn't list this sender; check that it is reachable",
+            server
+        ),
+        (None, true) => eprintln!(
+            "Discovery found no OMT sources, not even this sender: mDNS (UDP port 5353) is probably blocked by a firewall or network policy"
+        ),
+        (None, false) => eprintln!(
+            "Discovery lists {} other source(s) but not this sender: its mDNS announcement is probably blocked by a firewall",
+            sources.len()
+        ),
+    }
+    if let Some(address) = sender.address() {
+        eprintln!(
+            "Receivers can still connect directly to {}, or set a DiscoveryServer in the OMT settings",
+            address
+        );
+    }
+}
+
+/// Polls until a receiver identifying itself as `name` connects or `timeout`
+/// expires.
+///
+/// libomt doesn't tell a sender who is connected, so a receiver is only
+/// recognised by the metadata it sends back: any metadata frame containing
+/// `name` counts. Receivers that send no metadata can't be targeted.
+pub fn wait_for_named_receiver(sender: &Sender, name: &str, timeout: Duration) -> bool {
+    println!("Waiting for receiver {}...", name);
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        match sender.receive_metadata(Duration::from_millis(100)) {
+            Some(xml) if xml.contains(name) => {
+                println!(
+                    "Receiver {} connected after {:.1}s ({} connection(s) total)",
+                    name,
+                    start.elapsed().as_secs_f32(),
+                    sender.connections()
+                );
+                return true;
+            }
+            Some(xml) => {
+                println!("Ignoring metadata from another receiver: {}", xml);
+            }
+            None => {}
+        }
+    }
+    eprintln!("Warning: Receiver {} did not connect", name);
+    false
+}
+
+/// Fails the run if fewer than `min` receivers connected within `timeout`
+/// (`--count-receivers-min`).
+pub fn check_receiver_count(reached: i32, min: u32, timeout: Duration) -> Result<()> {
+    if reached < min as i32 {
+        bail!(
+            "only {} of {} required receivers connected within {:.1}s",
+            reached,
+            min,
+            timeout.as_secs_f32()
+        );
+    }
+    Ok(())
+}
+
+/// Waits for the receiver selected by `opts` and applies the
+/// `--require-receiver` policy.
+fn connect_receiver(sender: &Sender, opts: &Options) -> Result<()> {
+    if let Some(min) = opts.min_receivers {
+        let reached = wait_for_receivers(sender, min as i32, opts.connect_timeout);
+        return check_receiver_count(reached, min, opts.connect_timeout);
+    }
+    let connected = match &opts.target_receiver {
+        Some(name) => wait_for_named_receiver(sender, name, opts.connect_timeout),
+        None => wait_for_receiver(sender, opts.connect_timeout),
+    };
+    if !connected {
+        if opts.require_receiver {
+            bail!(
+                "no receiver connected within {:.1}s (--require-receiver)",
+                opts.connect_timeout.as_secs_f32()
+            );
+        }
+        eprintln!("Proceeding without a receiver");
+    }
+    Ok(())
+}
+
+/// Sets the library log file.
+pub fn set_log_file(path: &Path) -> Result<()> {
+    let logfile = CString::new(path.to_string_lossy().as_bytes())?;
+    unsafe { omt_setloggingfilename(logfile.as_ptr()) };
+    Ok(())
+}
+
+/// libomt silently stops logging when it can't open its log file, which would
+/// make the log scan report "No errors". Checks that `opts.log_file` can be
+/// written and falls back to the system temp directory; if neither works the
+/// scan is disabled.
+pub fn prepare_log_file(opts: &mut Options) {
+    let writable = |path: &Path| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map(|_| ())
+    };
+    let Err(e) = writable(&opts.log_file) else {
+        return;
+    };
+    eprintln!(
+        "Warning: log file {} is not writable: {}",
+        opts.log_file.display(),
+        e
+    );
+
+    let fallback = std::env::temp_dir().join(format!("omt-send-{}.log", std::process::id()));
+    match writable(&fallback) {
+        Ok(()) => {
+            eprintln!("Logging to {} instead", fallback.display());
+            opts.log_file = fallback;
+        }
+        Err(e) => {
+            eprintln!(
+                "Warning: fallback log file {} is not writable either: {}; log scan disabled",
+                fallback.display(),
+                e
+            );
+            opts.scan_log = false;
+        }
+    }
+}
+
+/// ERROR/WARN lines of a library log.
+fn log_errors(path: &Path) -> std::io::Result<Vec<String>> {
+    let log_content = std::fs::read_to_string(path)?;
+    Ok(log_content
+        .lines()
+        .filter(|line| line.contains("ERROR") || line.contains("WARN"))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Prints up to `limit` ERROR/WARN lines from the library log, or with
+/// `--log-per-run` from every run's log.
+pub fn report_log_errors(opts: &Options, limit: usize) {
+    if !opts.scan_log {
+        println!("\nLog scan skipped: no writable log file, library errors were not checked");
+        return;
+    }
+    if let Some(stamp) = opts.log_stamp {
+        report_run_log_errors(opts, stamp, limit);
+        return;
+    }
+    println!("\nChecking log file for errors...");
+    match log_errors(&opts.log_file) {
+        Ok(error_lines) => {
+            if !error_lines.is_empty() {
+                println!("Found {} warnings/errors in log:", error_lines.len());
+                for line in error_lines.iter().take(limit) {
+                    println!("  {}", line);
+                }
+            } else {
+                println!("No errors found in log file");
+            }
+        }
+        Err(e) => println!(
+            "Could not read log file {}: {}; library errors were not checked",
+            opts.log_file.display(),
+            e
+        ),
+    }
+}
+
+/// Scans every `omt-send-*-<stamp>.log` written by this invocation, printing
+/// up to `limit` lines per file and the total across all of them.
+fn report_run_log_errors(opts: &Options, stamp: u64, limit: usize) {
+    println!("\nChecking per-run log files for errors...");
+    let dir = opts
+        .log_file
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let suffix = format!("-{}.log", stamp);
+    let mut paths: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with("omt-send-") && n.ends_with(&suffix))
+            })
+            .collect(),
+        Err(e) => {
+            println!(
+                "Could not list {}: {}; library errors were not checked",
+                dir.display(),
+                e
+            );
+            return;
+        }
+    };
+    paths.sort();
+    if paths.is_empty() {
+        println!("No per-run log files found in {}", dir.display());
+        return;
+    }
+    let mut total = 0;
+    for path in &paths {
+        match log_errors(path) {
+            Ok(error_lines) if error_lines.is_empty() => {
+                println!("{}: no errors", path.display());
+            }
+            Ok(error_lines) => {
+                println!("{}: {} warnings/errors", path.display(), error_lines.len());
+                for line in error_lines.iter().take(limit) {
+                    println!("  {}", line);
+                }
+                total += error_lines.len();
+            }
+            Err(e) => println!("{}: could not read: {}", path.display(), e),
+        }
+    }
+    println!(
+        "Found {} warnings/errors across {} log files",
+        total,
+        paths.len()
+    );
+}
+
+/// RGBA images converted to a codec by [`VideoFormat::frame_from_rgba`] and
+/// [`VideoFormat::frame_from_rgba_into`], on any thread.
+static RGBA_CONVERSIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Conversions from RGBA to a codec since the process started; the send
+/// loop compares it before and after to check static content.
+pub fn rgba_conversions() -> u64 {
+    RGBA_CONVERSIONS.load(Ordering::Relaxed)
+}
+
+/// Renders and converts frame 0 before the send loop. Static content (bars,
+/// still images, solid colors) is sent from this buffer for the whole run,
+/// so scaling and RGB to YUV conversion happen here exactly once.
+pub fn preconvert(
+    format: &VideoFormat,
+    renderer: Option<&dyn FrameRenderer>,
+    alpha: bool,
+    premultiplied: bool,
+    align: usize,
+) -> Result<AlignedBuffer> {
+    let mut buf = AlignedBuffer::zeroed(format.buffer_size(), align);
+    generate_frame_into(format, renderer, 0, alpha, premultiplied, &mut buf)?;
+    Ok(buf)
+}
+
+/// Generates frame `frame_index` of `renderer`, or the codec's static test
+/// pattern when there is none, as a `buffer_size()` buffer. Nothing here
+/// touches libomt, so frames can be produced without a sender.
+pub fn generate_frame(
+    format: &VideoFormat,
+    renderer: Option<&dyn FrameRenderer>,
+    frame_index: u64,
+) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; format.buffer_size()];
+    generate_frame_into(format, renderer, frame_index, false, false, &mut buf)?;
+    Ok(buf)
+}
+
+/// Like [`generate_frame`], writing into an existing `buffer_size()` buffer.
+/// `alpha` gives the static BGRA pattern a transparency ramp (renderers
+/// supply their own alpha) and `premultiplied` premultiplies BGRA output;
+/// both must only be set for BGRA.
+pub fn generate_frame_into(
+    format: &VideoFormat,
+    renderer: Option<&dyn FrameRenderer>,
+    frame_index: u64,
+    alpha: bool,
+    premultiplied: bool,
+    buf: &mut [u8],
+) -> Result<()> {
+    match renderer {
+        Some(r) => {
+            format.frame_from_rgba_into(&r.render_rgba(format, frame_index)?, buf);
+            if premultiplied {
+                convert::premultiply_bgra(buf);
+            }
+        }
+        None => {
+            buf.copy_from_slice(&format.create_test_frame(0, FramePattern::Static));
+            apply_static_alpha(format, alpha, premultiplied, buf);
+        }
+    }
+    Ok(())
+}
+
+/// Gives the static BGRA pattern in `buf` its transparency ramp and
+/// premultiplies it, as selected; see [`generate_frame_into`].
+pub fn apply_static_alpha(format: &VideoFormat, alpha: bool, premultiplied: bool, buf: &mut [u8]) {
+    if alpha {
+        convert::apply_alpha_gradient(buf, format.width as usize);
+    }
+    if premultiplied {
+        convert::premultiply_bgra(buf);
+    }
+}
+
+/// Frame `frame_index` of `pattern` drawn over `plain`, the opaque bars from
+/// `create_test_frame`. The BGRA alpha ramp goes on after the pattern, whose
+/// bar and digits would otherwise overwrite it with opaque pixels.
+pub fn pattern_frame_into(
+    format: &VideoFormat,
+    plain: &[u8],
+    pattern: FramePattern,
+    frame_index: u64,
+    alpha: bool,
+    premultiplied: bool,
+    buf: &mut [u8],
+) {
+    buf.copy_from_slice(plain);
+    pattern.draw(buf, format, frame_index);
+    apply_static_alpha(format, alpha, premultiplied, buf);
+}
+
+/// Builds a video `OMTMediaFrame` for `format` pointing at `buf`.
+pub fn video_frame(format: &VideoFormat, use_alpha: bool, buf: &mut [u8]) -> OMTMediaFrame {
+    let mut frame: OMTMediaFrame = unsafe { mem::zeroed() };
+    frame.Type = OMTFrameType_OMTFrameType_Video;
+    frame.Codec = format.codec;
+    frame.Width = format.width;
+    frame.Height = format.height;
+    frame.Stride = format.stride();
+    frame.Flags = if use_alpha && format.codec == OMTCodec_OMTCodec_BGRA {
+        OMTVideoFlags_OMTVideoFlags_Alpha
+    } else {
+        OMTVideoFlags_OMTVideoFlags_None
+    };
+    frame.FrameRateN = format.fps_n;
+    frame.FrameRateD = format.fps_d;
+    frame.AspectRatio = (format.width as f32) / (format.height as f32);
+    frame.ColorSpace = format.color_space();
+    frame.Data = buf.as_mut_ptr() as *mut _;
+    // Same formula the frame buffers are sized with; check_size keeps it
+    // within i32
+    frame.DataLength = format.buffer_size() as i32;
+    frame
+}
+
+pub fn run_send_test(
+    format: VideoFormat,
+    duration_secs: u32,
+    use_alpha: bool,
+    renderer: Option<&dyn FrameRenderer>,
+    opts: &Options,
+) -> Result<TestResult> {
+    println!("\n=== Testing {} ===\n", format.name);
+
+    // Alpha is straight unless --alpha-premultiplied, and BGRA-only
+    let alpha = use_alpha && format.codec == OMTCodec_OMTCodec_BGRA;
+    // Frame patterns vary the built-in bars only
+    let frame_pattern = match renderer {
+        Some(_) => FramePattern::Static,
+        None => opts.frame_pattern,
+    };
+
+    // Set up logging
+    let log_file = if alpha {
+        opts.run_log_file(&format!("{}-alpha", format.name))
+    } else {
+        opts.run_log_file(format.name)
+    };
+    set_log_file(&log_file)?;
+    let premultiplied = alpha && opts.alpha_premultiplied;
+    if opts.alpha_premultiplied && format.codec != OMTCodec_OMTCodec_BGRA {
+        eprintln!("Note: --alpha-premultiplied is ignored for {}", format.name);
+    }
+
+    let frames_to_send = opts
+        .frame_count
+        .unwrap_or_else(|| frames_for_duration(duration_secs, format.fps_n, format.fps_d));
+    // Nothing would be sent, and the bitrate and success rate would divide
+    // by zero
+    if frames_to_send == 0 {
+        bail!(
+            "{}: nothing to send, {} frames requested ({}s at {}/{} fps)",
+            format.name,
+            frames_to_send,
+            duration_secs,
+            format.fps_n,
+            format.fps_d
+        );
+    }
+    let send_secs = frames_to_send as f64 * format.fps_d as f64 / format.fps_n as f64;
+
+    let source = format!("RustSend_{}", format.name);
+    let quality = select_quality(format.codec, opts);
+    let (colorspace, colorspace_source) = format.color_space_choice();
+    if let ColorSpaceSource::Fallback(lines) = colorspace_source {
+        eprintln!(
+            "Warning: {} has {} lines, not a standard size; sending {} (use --colorspace to choose)",
+            format.name,
+            lines,
+            colorspace_name(colorspace)
+        );
+    }
+    report::Banner {
+        source: source.clone(),
+        codec: codec_name(format.codec).to_string(),
+        width: format.width,
+        height: format.height,
+        fps_n: format.fps_n,
+        fps_d: format.fps_d,
+        quality: quality_name(quality).to_string(),
+        duration_secs: send_secs,
+        frames: frames_to_send,
+        pattern: match renderer {
+            Some(r) => r.name().to_string(),
+            None if frame_pattern == FramePattern::Static => "bars".to_string(),
+            None => format!("bars ({})", frame_pattern.name()),
+        },
+        alpha: match (alpha, premultiplied) {
+            (false, _) => "none",
+            (true, false) => "straight",
+            (true, true) => "premultiplied",
+        }
+        .to_string(),
+        colorspace: format!("{} ({})", colorspace_name(colorspace), colorspace_source),
+        encoder: encoder_path(),
+        log_file: log_file.display().to_string(),
+    }
+    .print(opts.banner_json)?;
+
+    let sender = create_sender(&source, quality, opts.create_retries)?;
+    connect_receiver(&sender, opts)?;
+
+    // Create test frame
+    let mut frame_buf = preconvert(&format, renderer, alpha, premultiplied, opts.buffer_align)?;
+    // The plain opaque bars, restored before each frame's pattern is drawn
+    let plain_bars = (frame_pattern != FramePattern::Static)
+        .then(|| format.create_test_frame(0, FramePattern::Static));
+    let mut frame = video_frame(&format, use_alpha, &mut frame_buf);
+    if premultiplied {
+        frame.Flags |= OMTVideoFlags_OMTVideoFlags_PreMultiplied;
+    }
+
+    if opts.verbosity >= 1 {
+        println!("OMTMediaFrame configured");
+        println!("  Codec: {}", format.name);
+        println!("  Size: {}x{}", frame.Width, frame.Height);
+        println!("  Stride: {}", frame.Stride);
+        println!("  DataLength: {}", frame.DataLength);
+        println!("  FPS: {}/{}", frame.FrameRateN, frame.FrameRateD);
+        println!("  Data alignment: {} bytes", frame_buf.alignment());
+    }
+
+    if opts.validate_frame {
+        let errors = validate::validate_video_frame(&frame, frame_buf.len());
+        if !errors.is_empty() {
+            eprintln!("Frame validation failed for {}:", format.name);
+            for error in &errors {
+                eprintln!("  {}", error);
+            }
+            bail!("{} frame field violation(s)", errors.len());
+        }
+        println!("Frame validation passed");
+    }
+    if opts.strict_stride {
+        validate::check_stride(&frame, frame_buf.len())
+            .with_context(|| format!("strict stride check failed for {}", format.name))?;
+    }
+
+    let mut dump_writer = opts
+        .dump
+        .as_deref()
+        .map(|path| dump::DumpWriter::create(path, &dump::DumpHeader::new(&format, &frame)))
+        .transpose()?;
+
+    // OMTMediaFrame has no timecode field, so the timecode is only burned in
+    let timecode = opts
+        .start_timecode
+        .as_deref()
+        .map(|s| Timecode::parse(s, format.fps_n, format.fps_d))
+        .transpose()?;
+    if let Some(tc) = &timecode {
+        println!(
+            "Start timecode: {}{}",
+            tc,
+            if tc.is_drop_frame() {
+                " (drop-frame)"
+            } else {
+                ""
+            }
+        );
+    }
+    let tc_scale = (format.height as usize / 180).max(1);
+    if let Some(region) = &opts.frame_barcode {
+        region.check(&format)?;
+        let (y, height) = region.rows(&format);
+        println!(
+            "Frame barcode: rows {}-{}, {} cells of {}px",
+            y,
+            y + height - 1,
+            barcode::BARCODE_CELLS,
+            region.cell_width(&format)
+        );
+    }
+
+    // High-precision timing
+    let frame_duration = Duration::from_secs_f64(format.fps_d as f64 / format.fps_n as f64);
+
+    let start_time = Instant::now();
+    let mut schedule = FrameSchedule::new(start_time, format.fps_n, format.fps_d);
+
+    // Statistics tracking
+    let mut stats_counter = 0;
+    let stats_interval = StatsInterval::frames(opts.stats_interval, format.fps_n, format.fps_d);
+    let mut codec_time_samples: Vec<i64> = Vec::new();
+    let mut frames_skipped = 0u32;
+    let mut outcome = RunOutcome::Completed;
+    let mut stream_digest = 0u64;
+    let (mut send_timeouts, mut consecutive_timeouts) = (0u32, 0u32);
+    let mut throttled_frames = 0u32;
+    // --simulate-loss/--simulate-jitter: fixed seed, so impaired runs repeat
+    let mut impairment_rng = XorShift::new(IMPAIRMENT_SEED);
+    let mut frames_lost = 0u32;
+    let (mut jitter_total, mut jitter_max) = (Duration::ZERO, Duration::ZERO);
+    let mut last_label: Option<String> = None;
+    let (mut hashed_frames, mut hash_time) = (0u32, Duration::ZERO);
+    let mut status_codes: BTreeMap<i32, u32> = BTreeMap::new();
+    let mut history = FrameHistory::new(opts.frame_history);
+    // --deadline-ms: frames that finish sending past their deadline
+    let (mut late_frames, mut worst_lateness) = (0u32, Duration::ZERO);
+
+    println!(
+        "Sending {} frames at {}x{} {}fps...",
+        frames_to_send,
+        format.width,
+        format.height,
+        format.fps_n as f64 / format.fps_d as f64
+    );
+
+    // libomt only reports a connection count, not per-receiver details,
+    // so --list-connections logs each change in the count
+    let mut connections = sender.connections();
+    if opts.list_connections {
+        println!("[0.0s] Connections: {}", connections);
+    }
+
+    // --send-black-on-disconnect: black goes out while nobody is connected
+    let mut black_buf = opts
+        .send_black_on_disconnect
+        .then(|| AlignedBuffer::from_slice(&format.create_black_frame(), opts.buffer_align));
+    let mut disconnected_since = (connections == 0).then(Instant::now);
+    let mut disconnected_time = Duration::ZERO;
+    let (mut disconnects, mut reconnects) = (0u32, 0u32);
+    let mut connection_gauge = ConnectionGauge::default();
+
+    // Animated content is rendered ahead on a generator thread unless
+    // --no-pipeline, so rendering overlaps with omt_send
+    let animated = renderer.filter(|r| r.animated());
+    let mut generation_time = Duration::ZERO;
+    let conversions_before = rgba_conversions();
+    thread::scope(|scope| -> Result<()> {
+        let mut pipeline = animated.filter(|_| !opts.no_pipeline).map(|r| {
+            pipeline::FramePipeline::spawn(
+                scope,
+                r,
+                format,
+                premultiplied,
+                opts.buffer_align,
+                1..frames_to_send as u64,
+            )
+        });
+
+        for i in 0..frames_to_send {
+            if opts.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+                println!("Stopped at frame {}", i);
+                outcome = RunOutcome::Interrupted { frame: i };
+                break;
+            }
+            if opts
+                .stats_request
+                .is_some_and(|request| request.swap(false, Ordering::Relaxed))
+            {
+                // Codec time is left to the periodic line, whose samples feed
+                // the peak and mean in the summary
+                let vstats = sender.video_statistics();
+                println!(
+                    "[{:.1}s] Snapshot at frame {} of {}: {} bytes, {} frames, dropped: {}, connections: {}",
+                    start_time.elapsed().as_secs_f64(),
+                    i,
+                    frames_to_send,
+                    vstats.bytes_sent,
+                    vstats.frames,
+                    vstats.frames_dropped,
+                    sender.connections()
+                );
+            }
+            let count = sender.connections();
+            if count != connections {
+                if opts.list_connections {
+                    println!(
+                        "[{:.1}s] Connections: {} -> {}",
+                        start_time.elapsed().as_secs_f64(),
+                        connections,
+                        count
+                    );
+                }
+                if let Some(min) = opts
+                    .min_receivers
+                    .filter(|&min| count < connections && count < min as i32)
+                {
+                    eprintln!(
+                        "Warning: connections dropped to {} at {:.1}s, below the minimum of {}",
+                        count,
+                        start_time.elapsed().as_secs_f64(),
+                        min
+                    );
+                }
+                // At low fps the gap between frames is long enough for an
+                // idle timeout to show up as a lost receiver
+                if count == 0 && frame_duration >= IDLE_WARN_FRAME_DURATION {
+                    eprintln!(
+                        "Warning: receiver dropped at {:.1}s with {:.1}s between frames",
+                        start_time.elapsed().as_secs_f64(),
+                        frame_duration.as_secs_f64()
+                    );
+                }
+                if count == 0 {
+                    disconnects += 1;
+                    disconnected_since = Some(Instant::now());
+                    if black_buf.is_some() {
+                        println!(
+                            "[{:.1}s] All receivers disconnected, sending black until one reconnects",
+                            start_time.elapsed().as_secs_f64()
+                        );
+                    }
+                } else if let Some(since) = disconnected_since.take() {
+                    disconnected_time += since.elapsed();
+                    if disconnects > 0 {
+                        reconnects += 1;
+                    }
+                    if black_buf.is_some() {
+                        println!(
+                            "[{:.1}s] Receiver connected after {:.1}s without one, resuming",
+                            start_time.elapsed().as_secs_f64(),
+                            since.elapsed().as_secs_f64()
+                        );
+                    }
+                }
+                connections = count;
+            }
+            if let Some(label) = renderer.and_then(|r| r.label(&format, i as u64)) {
+                if last_label.as_ref() != Some(&label) {
+                    println!(
+                        "[{:.1}s] Frame {}: {}",
+                        start_time.elapsed().as_secs_f64(),
+                        i,
+                        label
+                    );
+                    last_label = Some(label);
+                }
+            }
+            if let Some(r) = animated.filter(|_| i > 0) {
+                let start = Instant::now();
+                match &mut pipeline {
+                    Some(p) => {
+                        let sent = mem::replace(&mut frame_buf, p.next_frame()?);
+                        p.recycle(sent);
+                    }
+                    None => generate_frame_into(
+                        &format,
+                        Some(r),
+                        i as u64,
+                        false,
+                        premultiplied,
+                        &mut frame_buf,
+                    )?,
+                }
+                generation_time += start.elapsed();
+                frame.Data = frame_buf.as_mut_ptr() as *mut _;
+            }
+            if let Some(plain) = &plain_bars {
+                pattern_frame_into(
+                    &format,
+                    plain,
+                    frame_pattern,
+                    i as u64,
+                    alpha,
+                    premultiplied,
+                    &mut frame_buf,
+                );
+            }
+            if let Some(tc) = &timecode {
+                let text = tc.offset(i as u64).to_string();
+                overlay::draw_text(
+                    &mut frame_buf,
+                    &format,
+                    tc_scale * 4,
+                    tc_scale * 4,
+                    tc_scale,
+                    &text,
+                );
+            }
+            if let Some(region) = &opts.frame_barcode {
+                barcode::draw(&mut frame_buf, &format, region, i as u64);
+            }
+            let black = black_buf.as_mut().filter(|_| connections == 0);
+            frame.Data = match black {
+                Some(buf) => buf.as_mut_ptr(),
+                None => frame_buf.as_mut_ptr(),
+            } as *mut _;
+            let pts = pts_for_frame(i as u64, format.fps_n, format.fps_d);
+            frame.Timestamp = pts;
+            if opts.verbosity >= 2 {
+                println!("Frame {}: PTS={}", i, pts);
+            }
+
+            // Deliberately skip every Nth frame; PTS still advances so the
+            // receiver sees a timestamp gap
+            let skip = opts
+                .drop_every
+                .is_some_and(|n| (i as u64 + 1).is_multiple_of(n));
+            // Simulated impairments stand in for a lossy link at the
+            // application layer: random skips, and random delays in front of
+            // omt_send that the schedule then absorbs
+            let lost = opts
+                .simulate_loss_pct
+                .is_some_and(|pct| (impairment_rng.below(1_000_000) as f64) < pct * 10_000.0);
+            if skip || lost {
+                frames_skipped += 1;
+                frames_lost += lost as u32;
+            } else {
+                if let Some(max) = opts.simulate_jitter {
+                    let delay =
+                        Duration::from_micros(impairment_rng.below(max.as_micros() as u64 + 1));
+                    // Drawn either way so --deterministic skips the same
+                    // frames as a paced run
+                    if !opts.deterministic {
+                        thread::sleep(delay);
+                    }
+                    jitter_total += delay;
+                    jitter_max = jitter_max.max(delay);
+                }
+                if opts.hash_frames {
+                    let hash_start = Instant::now();
+                    let sent = black_buf.as_deref().filter(|_| connections == 0);
+                    let data = &sent.unwrap_or(&frame_buf)[..frame.DataLength as usize];
+                    let hash = xxh3_64(data);
+                    hash_time += hash_start.elapsed();
+                    hashed_frames += 1;
+                    println!("Frame {}: xxh3 {:016x}", i, hash);
+                }
+                if opts.deterministic {
+                    let sent = black_buf.as_deref().filter(|_| connections == 0);
+                    let data = &sent.unwrap_or(&frame_buf)[..frame.DataLength as usize];
+                    stream_digest = xxh3_64_with_seed(data, stream_digest ^ pts as u64);
+                }
+                // Taken before the send; a late frame rebases the schedule
+                // only afterwards, so it still counts against its own slot
+                let deadline = schedule.deadline(i as u64);
+                let send_start = Instant::now();
+                let rc = match opts.send_timeout {
+                    None => sender.send(&mut frame),
+                    Some(timeout) => match sender.send_with_timeout(&frame, timeout) {
+                        Ok(rc) => {
+                            consecutive_timeouts = 0;
+                            rc
+                        }
+                        Err(e) => {
+                            send_timeouts += 1;
+                            consecutive_timeouts += 1;
+                            eprintln!("Warning: frame {}: {:#}", i, e);
+                            if consecutive_timeouts >= SEND_TIMEOUT_LIMIT {
+                                eprintln!(
+                                    "omt_send timed out {} times in a row, stopping",
+                                    consecutive_timeouts
+                                );
+                                history.dump();
+                                outcome = RunOutcome::Hung { frame: i };
+                                break;
+                            }
+                            // Move on to the next frame's slot
+                            if !opts.deterministic {
+                                schedule.wait_for(i as u64 + 1);
+                            }
+                            continue;
+                        }
+                    },
+                };
+                history.push(FrameRecord {
+                    index: i as u64,
+                    pts,
+                    rc,
+                    send_time: send_start.elapsed(),
+                });
+                if let Some(tolerance) = opts.deadline {
+                    let lateness = Instant::now().saturating_duration_since(deadline);
+                    if lateness > tolerance {
+                        late_frames += 1;
+                        worst_lateness = worst_lateness.max(lateness);
+                        if opts.verbosity >= 2 {
+                            println!(
+                                "Frame {}: late by {:.2}ms",
+                                i,
+                                lateness.as_secs_f64() * 1000.0
+                            );
+                        }
+                    }
+                }
+                if opts.verbosity >= 3 {
+                    let vstats = sender.video_statistics();
+                    println!(
+                        "  rc={} ({}), stats: {} bytes sent, {} frames, {} dropped",
+                        rc,
+                        SendStatus::from(rc),
+                        vstats.bytes_sent,
+                        vstats.frames,
+                        vstats.frames_dropped
+                    );
+                }
+                if rc != 0 {
+                    let status = SendStatus::from(rc);
+                    *status_codes.entry(rc).or_default() += 1;
+                    if opts.warn_status_codes.contains(&rc) {
+                        eprintln!(
+                            "Warning: omt_send returned {} ({}) at frame {}",
+                            rc, status, i
+                        );
+                    }
+
+                    // Check if receiver disconnected; with
+                    // --send-black-on-disconnect that isn't an error and the
+                    // next frame goes out black
+                    let disconnected = !sender.is_connected();
+                    if disconnected && black_buf.is_none() {
+                        eprintln!("Receiver disconnected, stopping");
+                        outcome = RunOutcome::Disconnected { frame: i };
+                        break;
+                    }
+
+                    // For buffer overflow, wait a bit and retry
+                    if status == SendStatus::BufferOverflow {
+                        eprintln!("Buffer overflow at frame {}, waiting...", i);
+                        thread::sleep(Duration::from_millis(100));
+                        continue;
+                    }
+
+                    // Non-fatal status codes - continue normally
+                    if !status.is_fatal() || disconnected {
+                        // Frame was likely still sent, continue
+                    } else {
+                        // Fatal error: stop, but still report what was sent
+                        eprintln!("Fatal error at frame {}: {} (rc={})", i, status, rc);
+                        history.dump();
+                        outcome = RunOutcome::Failed { frame: i, rc };
+                        break;
+                    }
+                }
+                if let Some(writer) = &mut dump_writer {
+                    let sent = black_buf.as_deref().filter(|_| connections == 0);
+                    writer.write_frame(sent.unwrap_or(&frame_buf))?;
+                }
+            }
+
+            stats_counter += 1;
+
+            // Print statistics periodically
+            if stats_counter >= stats_interval {
+                let vstats = sender.video_statistics();
+                connection_gauge.update(sender.connection_count());
+                println!(
+                    "[{:.1}s] Sent: {} bytes, {} frames, dropped: {}, codec_time: {}ms, connections: {}",
+                    start_time.elapsed().as_secs_f64(),
+                    vstats.bytes_sent,
+                    vstats.frames,
+                    vstats.frames_dropped,
+                    vstats.codec_time_since_last,
+                    connection_gauge.current
+                );
+                codec_time_samples.push(vstats.codec_time_since_last);
+                stats_counter = 0;
+            }
+
+            // libomt has no bitrate control, so cap the rate by delaying the
+            // next frame whenever the bytes sent so far exceed the budget
+            if let Some(mbps) = opts.target_bitrate {
+                let vstats = sender.video_statistics();
+                let budget_bits = mbps * 1_000_000.0 * start_time.elapsed().as_secs_f64();
+                let excess_bits = vstats.bytes_sent as f64 * 8.0 - budget_bits;
+                if excess_bits > 0.0 {
+                    thread::sleep(Duration::from_secs_f64(excess_bits / (mbps * 1_000_000.0)));
+                    schedule.rebase(i as u64 + 1, Instant::now());
+                    throttled_frames += 1;
+                }
+            }
+
+            // Frame timing against the absolute schedule; more than 2 frames
+            // behind restarts it from now. --deterministic sends unpaced.
+            if !opts.deterministic && schedule.wait_for(i as u64 + 1) {
+                eprintln!("Timing drift detected, resynchronizing");
+            }
+        }
+        Ok(())
+    })
+    .inspect_err(|_| history.dump())?;
+
+    // --preconvert: static content must not be converted again per frame
+    if opts.preconvert && animated.is_none() {
+        let conversions = rgba_conversions() - conversions_before;
+        println!("RGB conversions in the send loop: {}", conversions);
+        if conversions > 0 {
+            bail!(
+                "{} static frame(s) were converted inside the send loop instead of once before it",
+                conversions
+            );
+        }
+    }
+
+    // Let queued frames go out before the final statistics
+    sender.flush(opts.drain_timeout);
+
+    if let (Some(writer), Some(path)) = (dump_writer, &opts.dump) {
+        let frames = writer.finish()?;
+        println!(
+            "Dumped {} frames to {} (header {})",
+            frames,
+            path.display(),
+            dump::sidecar_path(path).display()
+        );
+    }
+
+    // Final statistics
+    let vstats = sender.video_statistics();
+    println!("\n=== Final Statistics for {} ===", format.name);
+    println!("Total bytes sent: {}", vstats.bytes_sent);
+    println!("Total frames sent: {}", vstats.frames);
+    println!("Frames dropped: {}", vstats.frames_dropped);
+    if opts.drop_every.is_some() {
+        println!(
+            "Frames intentionally skipped: {}",
+            frames_skipped - frames_lost
+        );
+    }
+    if let Some(pct) = opts.simulate_loss_pct {
+        println!(
+            "Simulated loss: {} of {} frames ({:.2}%, requested {:.2}%)",
+            frames_lost,
+            frames_to_send,
+            frames_lost as f64 / frames_to_send as f64 * 100.0,
+            pct
+        );
+    }
+    if let Some(max) = opts.simulate_jitter {
+        let delayed = frames_to_send - frames_skipped;
+        println!(
+            "Simulated jitter: mean {:.2}ms, max {:.2}ms over {} frames (requested 0-{}ms)",
+            jitter_total.as_secs_f64() * 1000.0 / delayed.max(1) as f64,
+            jitter_max.as_secs_f64() * 1000.0,
+            delayed,
+            max.as_millis()
+        );
+    }
+    if let Some(tolerance) = opts.deadline {
+        println!(
+            "Late frames: {} (sent more than {}ms after their scheduled time), worst {:.2}ms",
+            late_frames,
+            tolerance.as_millis(),
+            worst_lateness.as_secs_f64() * 1000.0
+        );
+    }
+    if opts.send_black_on_disconnect {
+        if let Some(since) = disconnected_since {
+            disconnected_time += since.elapsed();
+        }
+        println!(
+            "Disconnects: {}, reconnects: {}, {:.1}s without receivers (sent black)",
+            disconnects,
+            reconnects,
+            disconnected_time.as_secs_f64()
+        );
+    }
+    if connection_gauge.samples > 0 {
+        println!(
+            "Connections: {} at the end, {}-{} over {} stats intervals",
+            connection_gauge.current,
+            connection_gauge.min,
+            connection_gauge.max,
+            connection_gauge.samples
+        );
+    }
+    let bitrate_mbps = (vstats.bytes_sent as f64 * 8.0) / (send_secs * 1_000_000.0);
+    println!("Average bitrate: {:.2} Mbps", bitrate_mbps);
+    if let Some(mbps) = opts.target_bitrate {
+        let achieved =
+            (vstats.bytes_sent as f64 * 8.0) / (start_time.elapsed().as_secs_f64() * 1_000_000.0);
+        println!(
+            "Target bitrate: {:.2} Mbps, achieved: {:.2} Mbps (throttled {} frames)",
+            mbps, achieved, throttled_frames
+        );
+        if achieved > mbps * 1.05 {
+            eprintln!(
+                "Warning: encoder overshot the target bitrate by {:.1}%",
+                (achieved / mbps - 1.0) * 100.0
+            );
+        }
+    }
+    for (rc, count) in &status_codes {
+        println!(
+            "Status code {}: {} time(s) ({})",
+            rc,
+            count,
+            SendStatus::from(*rc)
+        );
+    }
+    if hashed_frames > 0 {
+        println!(
+            "Frame hashing: {:.3}ms per frame ({:.2}s total)",
+            hash_time.as_secs_f64() * 1000.0 / hashed_frames as f64,
+            hash_time.as_secs_f64()
+        );
+    }
+    if animated.is_some() {
+        // With the pipeline this is only the time the loop waited for frames
+        println!(
+            "Frame generation: {:.2}s on the send thread ({})",
+            generation_time.as_secs_f64(),
+            if opts.no_pipeline {
+                "inline"
+            } else {
+                "pipelined"
+            }
+        );
+    }
+    // Intentionally skipped or simulated lost frames don't count against the
+    // success rate, and neither do frames never attempted after an early stop
+    let frames_attempted = outcome.frames_attempted(frames_to_send);
+    let success_rate_pct =
+        (vstats.frames as f64 / (frames_attempted - frames_skipped).max(1) as f64) * 100.0;
+    if outcome == RunOutcome::Completed {
+        println!("Success rate: {:.2}%", success_rate_pct);
+    } else {
+        println!(
+            "Success rate: {:.2}% of the {} frames attempted ({} requested)",
+            success_rate_pct, frames_attempted, frames_to_send
+        );
+    }
+
+    if send_timeouts > 0 {
+        println!(
+            "omt_send timeouts: {} (over {}ms each)",
+            send_timeouts,
+            opts.send_timeout.unwrap_or_default().as_millis()
+        );
+    }
+    if opts.deterministic {
+        println!(
+            "Stream digest: {:016x} over {} frames",
+            stream_digest,
+            frames_attempted - frames_skipped
+        );
+    }
+
+    // Runs shorter than one stats interval only have the final sample
+    if codec_time_samples.is_empty() {
+        codec_time_samples.push(vstats.codec_time_since_last);
+    }
+    let codec_time_peak_ms = codec_time_samples.iter().copied().max().unwrap_or(0);
+    let codec_time_mean_ms =
+        codec_time_samples.iter().sum::<i64>() as f64 / codec_time_samples.len() as f64;
+    println!(
+        "Codec time: peak {}ms, mean {:.1}ms",
+        codec_time_peak_ms, codec_time_mean_ms
+    );
+    // Normalized so runs at different resolutions compare
+    let megapixels = format.width as f64 * format.height as f64 / 1_000_000.0;
+    println!(
+        "Codec time per megapixel: {:.2}ms (mean)",
+        codec_time_mean_ms / megapixels
+    );
+
+    drop(sender);
+    if outcome == RunOutcome::Completed {
+        println!("Test completed successfully\n");
+    } else {
+        println!("Test {}\n", outcome);
+    }
+
+    Ok(TestResult {
+        name: if alpha {
+            format!("{} (alpha)", format.name)
+        } else {
+            format.name.to_string()
+        },
+        bytes_sent: vstats.bytes_sent,
+        frames_sent: vstats.frames,
+        frames_dropped: vstats.frames_dropped,
+        bitrate_mbps,
+        success_rate_pct,
+        codec_time_peak_ms,
+        codec_time_mean_ms,
+        status_codes,
+        outcome,
+        frames_requested: frames_to_send,
+        frames_skipped,
+        stream_digest: opts
+            .deterministic
+            .then(|| format!("{:016x}", stream_digest)),
+    })
+}
+
+/// PTS of audio sample `sample`, on the same 10 MHz base as video.
+pub fn pts_for_sample(sample: u64, sample_rate: i32) -> i64 {
+    (sample as i128 * TICKS_PER_SEC as i128 / sample_rate.max(1) as i128) as i64
+}
+
+/// Builds an audio `OMTMediaFrame` for `samples` per channel of planar
+/// float `data`, starting at sample `first_sample` of the stream.
+pub fn audio_frame(
+    format: &audio::AudioFormat,
+    first_sample: u64,
+    samples: u32,
+    data: &mut [f32],
+) -> OMTMediaFrame {
+    let mut frame: OMTMediaFrame = unsafe { mem::zeroed() };
+    frame.Type = OMTFrameType_OMTFrameType_Audio;
+    frame.Codec = OMTCodec_OMTCodec_FPA1;
+    frame.Timestamp = pts_for_sample(first_sample, format.sample_rate);
+    frame.SampleRate = format.sample_rate;
+    frame.Channels = format.channels;
+    frame.SamplesPerChannel = samples as i32;
+    frame.Data = data.as_mut_ptr() as *mut _;
+    frame.DataLength = format.block_size(samples) as i32;
+    frame
+}
+
+/// Audio blocks sent per second by `run_audio_test`: 20ms each, 960
+/// samples per channel at 48 kHz.
+pub const AUDIO_BLOCKS_PER_SEC: i32 = 50;
+
+/// Sends `duration_secs` of the 1 kHz test tone in `format` as planar float
+/// audio frames, paced in real time, and prints libomt's audio statistics.
+/// Block sizes follow [`audio::SampleCadence`], so rates like 44.1 kHz that
+/// don't divide evenly into blocks still keep exact time.
+pub fn run_audio_test(
+    format: audio::AudioFormat,
+    duration_secs: u32,
+    opts: &Options,
+) -> Result<()> {
+    println!("\n=== Testing {} ===\n", format.name);
+    println!(
+        "{} Hz, {} channel(s), {} Hz tone, {} blocks/s",
+        format.sample_rate,
+        format.channels,
+        audio::TONE_HZ,
+        AUDIO_BLOCKS_PER_SEC
+    );
+    if format.sample_rate <= 0 || format.channels <= 0 {
+        bail!(
+            "{}: invalid audio format ({} Hz, {} channels)",
+            format.name,
+            format.sample_rate,
+            format.channels
+        );
+    }
+    set_log_file(&opts.run_log_file(format.name))?;
+
+    let sender = create_sender(
+        &format!("RustSend_{}", format.name),
+        opts.quality.unwrap_or(OMTQuality_OMTQuality_Default),
+        opts.create_retries,
+    )?;
+    connect_receiver(&sender, opts)?;
+
+    let blocks = opts
+        .frame_count
+        .unwrap_or(duration_secs * AUDIO_BLOCKS_PER_SEC as u32);
+    let mut cadence = audio::SampleCadence::new(format.sample_rate as u32, AUDIO_BLOCKS_PER_SEC, 1);
+    let start_time = Instant::now();
+    let mut schedule = FrameSchedule::new(start_time, AUDIO_BLOCKS_PER_SEC, 1);
+    for i in 0..blocks {
+        if opts.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            println!("Stopped at block {}", i);
+            break;
+        }
+        let first_sample = cadence.total();
+        let samples = cadence.next().unwrap_or_default();
+        let mut data = format.tone(first_sample, samples as i32);
+        let mut frame = audio_frame(&format, first_sample, samples, &mut data);
+
+        let rc = sender.send(&mut frame);
+        let status = SendStatus::from(rc);
+        if status.is_fatal() {
+            if !sender.is_connected() {
+                eprintln!("Receiver disconnected, stopping");
+                break;
+            }
+            bail!(
+                "omt_send failed at audio block {} (rc={}, {})",
+                i,
+                rc,
+                status
+            );
+        }
+        if opts.verbosity >= 2 {
+            println!(
+                "Block {}: {} samples, PTS={}, rc={} ({})",
+                i, samples, frame.Timestamp, rc, status
+            );
+        }
+        if (i + 1) % AUDIO_BLOCKS_PER_SEC as u32 == 0 {
+            let astats = sender.audio_statistics();
+            println!(
+                "[{:.1}s] Sent: {} bytes, {} frames, dropped: {}",
+                start_time.elapsed().as_secs_f64(),
+                astats.bytes_sent,
+                astats.frames,
+                astats.frames_dropped
+            );
+        }
+        if schedule.wait_for(i as u64 + 1) {
+            eprintln!("Timing drift detected, resynchronizing");
+        }
+    }
+
+    let astats = sender.audio_statistics();
+    println!("\n=== Final Audio Statistics for {} ===", format.name);
+    println!("Samples per channel sent: {}", cadence.total());
+    println!("Total bytes sent: {}", astats.bytes_sent);
+    println!("Total frames sent: {}", astats.frames);
+    println!("Frames dropped: {}", astats.frames_dropped);
+    Ok(())
+}
+
+/// Sends `video` and `audio` together for `duration_secs`: each video frame
+/// is followed by the audio covering its interval, both stamped on the
+/// 10 MHz PTS base. Audio block sizes come from [`audio::SampleCadence`],
+/// which accumulates the fractional samples of rates like 29.97 instead of
+/// truncating each frame, so the audio PTS (`samples_sent * 10_000_000 /
+/// sample_rate`) stays within one sample of the video PTS however long the
+/// run is.
+pub fn run_av_test(
+    video: VideoFormat,
+    audio: audio::AudioFormat,
+    duration_secs: u32,
+    opts: &Options,
+) -> Result<()> {
+    let name = format!("{}+{}", video.name, audio.name);
+    println!("\n=== Testing {} ===\n", name);
+    if audio.sample_rate <= 0 || audio.channels <= 0 {
+        bail!(
+            "{}: invalid audio format ({} Hz, {} channels)",
+            audio.name,
+            audio.sample_rate,
+            audio.channels
+        );
+    }
+    set_log_file(&opts.run_log_file(&name))?;
+
+    let sender = create_sender(
+        &format!("RustSend_{}_{}", video.name, audio.name),
+        select_quality(video.codec, opts),
+        opts.create_retries,
+    )?;
+    connect_receiver(&sender, opts)?;
+
+    let frames_to_send = opts
+        .frame_count
+        .unwrap_or_else(|| frames_for_duration(duration_secs, video.fps_n, video.fps_d));
+    let mut frame_buf = AlignedBuffer::from_slice(
+        &video.create_test_frame(0, FramePattern::Static),
+        opts.buffer_align,
+    );
+    let mut frame = video_frame(&video, false, &mut frame_buf);
+    let mut cadence = audio::SampleCadence::new(audio.sample_rate as u32, video.fps_n, video.fps_d);
+    println!(
+        "{} frames at {}/{} fps with {} Hz x {} audio, {:.1} samples per frame",
+        frames_to_send,
+        video.fps_n,
+        video.fps_d,
+        audio.sample_rate,
+        audio.channels,
+        audio.sample_rate as f64 * video.fps_d as f64 / video.fps_n as f64
+    );
+
+    let start_time = Instant::now();
+    let mut schedule = FrameSchedule::new(start_time, video.fps_n, video.fps_d);
+    let stats_interval = StatsInterval::frames(opts.stats_interval, video.fps_n, video.fps_d);
+    let mut frames_sent = 0u32;
+    for i in 0..frames_to_send {
+        if opts.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            println!("Stopped at frame {}", i);
+            break;
+        }
+        frame.Timestamp = pts_for_frame(i as u64, video.fps_n, video.fps_d);
+        let video_rc = sender.send(&mut frame);
+
+        let first_sample = cadence.total();
+        let samples = cadence.next().unwrap_or_default();
+        let mut data = audio.tone(first_sample, samples as i32);
+        let mut block = audio_frame(&audio, first_sample, samples, &mut data);
+        let audio_rc = sender.send(&mut block);
+
+        if opts.verbosity >= 2 {
+            println!(
+                "Frame {}: video PTS={} rc={}, audio PTS={} ({} samples) rc={}",
+                i, frame.Timestamp, video_rc, block.Timestamp, samples, audio_rc
+            );
+        }
+        let fatal = [("video", video_rc), ("audio", audio_rc)]
+            .into_iter()
+            .find(|&(_, rc)| SendStatus::from(rc).is_fatal());
+        if let Some((kind, rc)) = fatal {
+            if !sender.is_connected() {
+                eprintln!("Receiver disconnected, stopping");
+                break;
+            }
+            bail!(
+                "omt_send failed for {} at frame {} (rc={}, {})",
+                kind,
+                i,
+                rc,
+                SendStatus::from(rc)
+            );
+        }
+        frames_sent += 1;
+
+        if frames_sent.is_multiple_of(stats_interval) {
+            let (vstats, astats) = (sender.video_statistics(), sender.audio_statistics());
+            println!(
+                "[{:.1}s] Video: {} frames, dropped {}; audio: {} frames, dropped {}",
+                start_time.elapsed().as_secs_f64(),
+                vstats.frames,
+                vstats.frames_dropped,
+                astats.frames,
+                astats.frames_dropped
+            );
+        }
+        if schedule.wait_for(i as u64 + 1) {
+            eprintln!("Timing drift detected, resynchronizing");
+        }
+    }
+
+    sender.flush(opts.drain_timeout);
+    let (vstats, astats) = (sender.video_statistics(), sender.audio_statistics());
+    // Both streams' next PTS; the difference is under one audio sample
+    let video_end = pts_for_frame(frames_sent as u64, video.fps_n, video.fps_d);
+    let audio_end = pts_for_sample(cadence.total(), audio.sample_rate);
+    println!("\n=== Final Statistics for {} ===", name);
+    println!(
+        "Video: {} frames sent, {} dropped, {} bytes",
+        vstats.frames, vstats.frames_dropped, vstats.bytes_sent
+    );
+    println!(
+        "Audio: {} frames sent, {} dropped, {} bytes, {} samples per channel",
+        astats.frames,
+        astats.frames_dropped,
+        astats.bytes_sent,
+        cadence.total()
+    );
+    println!(
+        "A/V offset at the end: {:.1}us (video {} vs audio {})",
+        (video_end - audio_end) as f64 / 10.0,
+        video_end,
+        audio_end
+    );
+    Ok(())
+}
+
+/// Re-sends the frames of a raw dump recorded with `--dump`, with the
+/// recorded flags and color space, at the recorded frame rate.
+pub fn run_replay(dump_path: &Path, sidecar: &Path, opts: &Options) -> Result<()> {
+    let header = dump::DumpHeader::load(sidecar)?;
+    let format = header.format()?;
+    let mut reader = if opts.replay_mmap {
+        dump::DumpReader::map(dump_path, &header)?
+    } else {
+        dump::DumpReader::open(dump_path, &header)?
+    };
+    println!(
+        "\n=== Replaying {} ({} frames of {}) ===\n",
+        dump_path.display(),
+        reader.frames,
+        format.name
+    );
+
+    set_log_file(&opts.run_log_file(format.name))?;
+    let sender = create_sender(
+        &format!("RustReplay_{}", format.name),
+        select_quality(format.codec, opts),
+        opts.create_retries,
+    )?;
+    connect_receiver(&sender, opts)?;
+
+    let mut frame_buf = AlignedBuffer::zeroed(format.buffer_size(), opts.buffer_align);
+    let mut frame = video_frame(&format, false, &mut frame_buf);
+    if opts.strict_stride {
+        // A mapped frame is exactly DataLength bytes of the dump
+        let buf_len = if opts.replay_mmap {
+            frame.DataLength as usize
+        } else {
+            frame_buf.len()
+        };
+        validate::check_stride(&frame, buf_len)
+            .with_context(|| format!("strict stride check failed for {}", dump_path.display()))?;
+    }
+    frame.Flags = header.flags;
+    frame.ColorSpace = header.colorspace;
+
+    let start_time = Instant::now();
+    let mut schedule = FrameSchedule::new(start_time, format.fps_n, format.fps_d);
+    for i in 0..reader.frames {
+        frame.Data = reader.next_frame(&mut frame_buf)?.as_mut_ptr() as *mut _;
+        frame.Timestamp = pts_for_frame(i, format.fps_n, format.fps_d);
+        let rc = sender.send(&mut frame);
+        if rc != 0 {
+            if !sender.is_connected() {
+                eprintln!("Receiver disconnected, stopping");
+                break;
+            }
+            let status = SendStatus::from(rc);
+            if !status.is_accepted() {
+                eprintln!("Frame {}: {} (rc={})", i, status, rc);
+            }
+        }
+
+        if schedule.wait_for(i + 1) {
+            eprintln!("Timing drift detected, resynchronizing");
+        }
+    }
+
+    sender.flush(opts.drain_timeout);
+    let vstats = sender.video_statistics();
+    println!("\n=== Replay Statistics for {} ===", format.name);
+    println!("Frames in dump: {}", reader.frames);
+    println!("Total frames sent: {}", vstats.frames);
+    println!("Frames dropped: {}", vstats.frames_dropped);
+    println!(
+        "Average bitrate: {:.2} Mbps",
+        (vstats.bytes_sent as f64 * 8.0) / (start_time.elapsed().as_secs_f64() * 1_000_000.0)
+    );
+    Ok(())
+}
+
+/// Creates a sender, sends one frame of `format`, reads the statistics and
+/// destroys the sender again; the quickest check that the setup works.
+/// Fails unless `omt_send` returned success or a known non-fatal code.
+///
+/// Only waits for a receiver when one is required (`--require-receiver`,
+/// `--target-receiver` or `--count-receivers-min`). Without one libomt
+/// doesn't encode, so the frame count stays at zero and only the return
+/// code is checked.
+pub fn run_once(format: VideoFormat, opts: &Options) -> Result<()> {
+    println!("\n=== Single frame check for {} ===\n", format.name);
+
+    set_log_file(&opts.run_log_file(format.name))?;
+    let sender = create_sender(
+        &format!("RustSend_{}_once", format.name),
+        select_quality(format.codec, opts),
+        opts.create_retries,
+    )?;
+    if opts.require_receiver || opts.target_receiver.is_some() || opts.min_receivers.is_some() {
+        connect_receiver(&sender, opts)?;
+    }
+
+    let mut frame_buf = format.create_test_frame(0, FramePattern::Static);
+    let mut frame = video_frame(&format, false, &mut frame_buf);
+    if opts.strict_stride {
+        validate::check_stride(&frame, frame_buf.len())
+            .with_context(|| format!("strict stride check failed for {}", format.name))?;
+    }
+    frame.Timestamp = pts_for_frame(0, format.fps_n, format.fps_d);
+    let rc = sender.send(&mut frame);
+    let status = SendStatus::from(rc);
+    sender.flush(opts.drain_timeout);
+    let vstats = sender.video_statistics();
+    println!(
+        "omt_send: rc={} ({}), stats: {} frames, {} bytes, {} receiver(s) connected",
+        rc,
+        status,
+        vstats.frames,
+        vstats.bytes_sent,
+        sender.connection_count()
+    );
+    drop(sender);
+
+    if !status.is_accepted() {
+        bail!("{}: omt_send returned {} ({})", format.name, rc, status);
+    }
+    println!("Frame accepted");
+    Ok(())
+}
+
+/// Encoder throughput measured by `run_encode_benchmark`.
+#[derive(Debug, Clone, Copy)]
+pub struct EncodeBenchmark {
+    pub frames: i64,
+    pub encode_ms_per_frame: f64,
+    pub send_ms_per_frame: f64,
+    /// Send-to-receive time of the frames the loopback receiver got
+    pub transport_latency: Option<LatencySummary>,
+}
+
+/// How long the final drain of the loopback receiver waits for each frame.
+const LATENCY_DRAIN_MS: i32 = 100;
+
+/// Takes every frame the loopback receiver has, waiting up to `timeout_ms`
+/// for each, and records the latency of those with a send time in
+/// `sent_at`. Returns the number of frames taken.
+unsafe fn drain_loopback(
+    receiver: *mut omt_receive_t,
+    timeout_ms: i32,
+    sent_at: &mut HashMap<i64, Instant>,
+    latencies: &mut Vec<Duration>,
+) -> usize {
+    let mut received = 0;
+    loop {
+        let frame = omt_receive(receiver, OMTFrameType_OMTFrameType_Video, timeout_ms);
+        if frame.is_null() {
+            return received;
+        }
+        let now = Instant::now();
+        if let Some(sent) = sent_at.remove(&(*frame).Timestamp) {
+            latencies.push(now - sent);
+        }
+        received += 1;
+    }
+}
+
+/// Connects an in-process receiver to `sender` over loopback, asking for
+/// decoded frames in `preferred`. The caller destroys it.
+unsafe fn connect_loopback(
+    sender: &Sender,
+    preferred: OMTPreferredVideoFormat,
+    opts: &Options,
+) -> Result<*mut omt_receive_t> {
+    let Some(address) = sender.address() else {
+        bail!("could not get the sender address for the loopback receiver");
+    };
+    let address = CString::new(address)?;
+    let receiver = omt_receive_create(
+        address.as_ptr(),
+        OMTFrameType_OMTFrameType_Video,
+        preferred,
+        OMTReceiveFlags_OMTReceiveFlags_None,
+    );
+    if receiver.is_null() {
+        bail!("omt_receive_create failed for the loopback receiver");
+    }
+    if !wait_for_receiver(sender, opts.connect_timeout) {
+        omt_receive_destroy(receiver);
+        bail!("loopback receiver did not connect");
+    }
+    Ok(receiver)
+}
+
+/// Measures encode cost separately from transmission.
+///
+/// libomt has no encode-without-send call and only encodes while a receiver
+/// is connected, so this connects an in-process receiver to the sender over
+/// loopback and sends frames unpaced. The library's own `CodecTime` gives the
+/// encode time; the rest of the wall-clock `omt_send` time is transport and
+/// queueing overhead.
+///
+/// Each frame's `Timestamp` is derived from its index, and the receiver gets
+/// it back unchanged, so it also keys the transport latency: the time from
+/// just before `omt_send` until the receiver hands the frame out. The
+/// receiver is polled after every send and then drained, so a latency
+/// includes up to one `omt_send` call of polling delay.
+pub fn run_encode_benchmark(format: VideoFormat, opts: &Options) -> Result<EncodeBenchmark> {
+    unsafe {
+        println!("\n=== Encode benchmark for {} ===\n", format.name);
+
+        set_log_file(&opts.run_log_file(format.name))?;
+
+        let sender = create_sender(
+            &format!("RustSend_{}_encode", format.name),
+            select_quality(format.codec, opts),
+            opts.create_retries,
+        )?;
+        let receiver = connect_loopback(
+            &sender,
+            OMTPreferredVideoFormat_OMTPreferredVideoFormat_UYVYorBGRA,
+            opts,
+        )?;
+
+        let mut frame_buf = AlignedBuffer::from_slice(
+            &format.create_test_frame(0, FramePattern::Static),
+            opts.buffer_align,
+        );
+        let mut frame = video_frame(&format, false, &mut frame_buf);
+        let frames_to_send = opts.frame_count.unwrap_or(300);
+
+        println!("Encoding {} frames unpaced...", frames_to_send);
+        let mut send_time = Duration::ZERO;
+        let mut sent_at: HashMap<i64, Instant> = HashMap::new();
+        let mut latencies: Vec<Duration> = Vec::new();
+        for i in 0..frames_to_send {
+            frame.Timestamp = pts_for_frame(i as u64, format.fps_n, format.fps_d);
+            let start = Instant::now();
+            sent_at.insert(frame.Timestamp, start);
+            let rc = sender.send(&mut frame);
+            send_time += start.elapsed();
+            if SendStatus::from(rc).is_fatal() {
+                omt_receive_destroy(receiver);
+                bail!("omt_send failed at frame {} (rc={})", i, rc);
+            }
+            // Drain the loopback receiver so it never applies backpressure
+            drain_loopback(receiver, 0, &mut sent_at, &mut latencies);
+        }
+
+        sender.flush(opts.drain_timeout);
+        // Frames still in flight after the flush
+        while drain_loopback(receiver, LATENCY_DRAIN_MS, &mut sent_at, &mut latencies) > 0 {}
+        let vstats = sender.video_statistics();
+        omt_receive_destroy(receiver);
+
+        let frames = vstats.frames.max(1);
+        let result = EncodeBenchmark {
+            frames: vstats.frames,
+            encode_ms_per_frame: vstats.codec_time as f64 / frames as f64,
+            send_ms_per_frame: send_time.as_secs_f64() * 1000.0 / frames_to_send.max(1) as f64,
+            transport_latency: LatencySummary::from_samples(&mut latencies),
+        };
+
+        println!("Frames encoded: {}", result.frames);
+        println!(
+            "Encode time: {:.2}ms/frame ({:.1} fps)",
+            result.encode_ms_per_frame,
+            1000.0 / result.encode_ms_per_frame.max(f64::EPSILON)
+        );
+        println!(
+            "omt_send wall time: {:.2}ms/frame, transport/queueing: {:.2}ms/frame",
+            result.send_ms_per_frame,
+            (result.send_ms_per_frame - result.encode_ms_per_frame).max(0.0)
+        );
+        match &result.transport_latency {
+            Some(latency) => println!(
+                "Transport latency: min {:.2}ms, mean {:.2}ms, p99 {:.2}ms over {} of {} frames",
+                latency.min.as_secs_f64() * 1000.0,
+                latency.mean.as_secs_f64() * 1000.0,
+                latency.p99.as_secs_f64() * 1000.0,
+                latency.samples,
+                frames_to_send
+            ),
+            None => println!("Transport latency: no frames reached the loopback receiver"),
+        }
+
+        Ok(result)
+    }
+}
+
+/// Samples the bars of every frame the loopback receiver has, waiting up to
+/// `timeout_ms` for each.
+unsafe fn check_decoded_bars(
+    receiver: *mut omt_receive_t,
+    timeout_ms: i32,
+    expected: &[(u8, u8, u8); 8],
+    errors: &mut Vec<roundtrip::BarError>,
+    checked: &mut u32,
+) -> Result<()> {
+    loop {
+        // The frame stays valid until the next omt_receive
+        let decoded = omt_receive(receiver, OMTFrameType_OMTFrameType_Video, timeout_ms);
+        if decoded.is_null() {
+            return Ok(());
+        }
+        let samples = roundtrip::sample_uyvy_bars(&*decoded)?;
+        roundtrip::update_errors(errors, &samples, expected);
+        *checked += 1;
+    }
+}
+
+/// Frames sent by `run_verify_bars` without `--send-count`.
+const VERIFY_BARS_FRAMES: u32 = 30;
+
+/// Sends the color bars to an in-process loopback receiver and checks that
+/// every bar decodes to within the tolerance of the value sent, which checks
+/// the encode/decode cycle and not just delivery. The receiver asks for
+/// UYVY, so the bars are compared in YUV: what UYVY and NV12 were sent in,
+/// and for BGRA the same bars in the frame's color space. Returns the
+/// worst error of each bar over every frame received; fails if a bar is out
+/// of tolerance or no frame came back.
+pub fn run_verify_bars(format: VideoFormat, opts: &Options) -> Result<Vec<roundtrip::BarError>> {
+    println!("\n=== Color bar round trip for {} ===\n", format.name);
+    let Some(default) = roundtrip::default_tolerance(format.codec) else {
+        bail!(
+            "{}: no color bar tolerance for {}, so it can't be verified",
+            format.name,
+            codec_name(format.codec)
+        );
+    };
+    let tolerance = opts.bar_tolerance.unwrap_or(default);
+    let expected = bars::yuv_bars(format.color_space());
+
+    set_log_file(&opts.run_log_file(&format!("{}-bars", format.name)))?;
+    let sender = create_sender(
+        &format!("RustSend_{}_bars", format.name),
+        select_quality(format.codec, opts),
+        opts.create_retries,
+    )?;
+
+    let mut frame_buf = AlignedBuffer::from_slice(
+        &format.create_test_frame(0, FramePattern::Static),
+        opts.buffer_align,
+    );
+    let mut frame = video_frame(&format, false, &mut frame_buf);
+    let frames_to_send = opts.frame_count.unwrap_or(VERIFY_BARS_FRAMES);
+    let mut errors: Vec<roundtrip::BarError> = Vec::new();
+    let mut checked = 0u32;
+    unsafe {
+        let receiver = connect_loopback(
+            &sender,
+            OMTPreferredVideoFormat_OMTPreferredVideoFormat_UYVY,
+            opts,
+        )?;
+        let mut result = Ok(());
+        let mut schedule = FrameSchedule::new(Instant::now(), format.fps_n, format.fps_d);
+        for i in 0..frames_to_send {
+            frame.Timestamp = pts_for_frame(i as u64, format.fps_n, format.fps_d);
+            let rc = sender.send(&mut frame);
+            if SendStatus::from(rc).is_fatal() {
+                result = Err(anyhow::anyhow!(
+                    "omt_send failed at frame {} (rc={})",
+                    i,
+                    rc
+                ));
+                break;
+            }
+            schedule.wait_for(i as u64 + 1);
+            if let Err(e) = check_decoded_bars(receiver, 0, &expected, &mut errors, &mut checked) {
+                result = Err(e);
+                break;
+            }
+        }
+        // Frames still in flight
+        if result.is_ok() {
+            sender.flush(opts.drain_timeout);
+            result = check_decoded_bars(
+                receiver,
+                LATENCY_DRAIN_MS,
+                &expected,
+                &mut errors,
+                &mut checked,
+            );
+        }
+        omt_receive_destroy(receiver);
+        result?;
+    }
+    drop(sender);
+
+    if checked == 0 {
+        bail!("no frames came back through the loopback receiver");
+    }
+    println!(
+        "Checked {} of {} frames, tolerance {} code values:",
+        checked, frames_to_send, tolerance
+    );
+    let failures = roundtrip::print_errors(&errors, tolerance);
+    if !failures.is_empty() {
+        bail!("{}: {}", format.name, failures.join(", "));
+    }
+    println!("All bars within tolerance");
+    Ok(errors)
+}
+
+/// Sends at increasing frame rates and returns the highest rate whose
+/// losses stayed within the threshold, or `None` if even the first step failed.
+pub fn run_fps_ramp(format: VideoFormat, ramp: FpsRamp, opts: &Options) -> Result<Option<i32>> {
+    println!("\n=== FPS ramp for {} ===\n", format.name);
+
+    set_log_file(&opts.run_log_file(format.name))?;
+
+    let sender = create_sender(
+        &format!("RustSend_{}_ramp", format.name),
+        select_quality(format.codec, opts),
+        opts.create_retries,
+    )?;
+    connect_receiver(&sender, opts)?;
+
+    let mut frame_buf = format.create_test_frame(0, FramePattern::Static);
+    let mut frame = video_frame(&format, false, &mut frame_buf);
+    frame.FrameRateD = 1;
+
+    let mut step_pts: i64 = 0;
+    let mut max_sustainable = None;
+    let vstats = sender.video_statistics();
+    let mut dropped_before = vstats.frames_dropped;
+
+    let mut fps = ramp.step;
+    while fps <= FPS_RAMP_MAX {
+        frame.FrameRateN = fps;
+        let step_duration = Duration::from_secs(ramp.interval_secs as u64);
+        let target_frames = fps as i64 * ramp.interval_secs as i64;
+
+        let step_start = Instant::now();
+        let mut schedule = FrameSchedule::new(step_start, fps, 1);
+        let mut sent = 0i64;
+        while step_start.elapsed() < step_duration && sent < target_frames {
+            frame.Timestamp = step_pts + pts_for_frame(sent as u64, fps, 1);
+            let rc = sender.send(&mut frame);
+            if SendStatus::from(rc).is_fatal() {
+                bail!("omt_send failed at {}fps (rc={})", fps, rc);
+            }
+            sent += 1;
+            schedule.wait_for(sent as u64);
+        }
+
+        step_pts += pts_for_frame(sent as u64, fps, 1);
+
+        let vstats = sender.video_statistics();
+        let dropped = vstats.frames_dropped - dropped_before;
+        dropped_before = vstats.frames_dropped;
+        // Frames the loop couldn't fit into the step count as lost too
+        let shortfall = target_frames - sent;
+        let loss_pct = (dropped + shortfall) as f64 / target_frames as f64 * 100.0;
+
+        println!(
+            "[{}fps] sent {}/{} frames, dropped: {}, loss: {:.2}%",
+            fps, sent, target_frames, dropped, loss_pct
+        );
+
+        if loss_pct > ramp.drop_threshold_pct {
+            println!(
+                "Loss exceeded {:.2}% at {}fps, stopping ramp",
+                ramp.drop_threshold_pct, fps
+            );
+            break;
+        }
+        max_sustainable = Some(fps);
+        fps += ramp.step;
+    }
+
+    drop(sender);
+
+    match max_sustainable {
+        Some(fps) => println!(
+            "Max sustainable fps for {} at {}x{}: {}",
+            format.name, format.width, format.height, fps
+        ),
+        None => println!(
+            "{} could not sustain {}fps at {}x{}",
+            format.name, ramp.step, format.width, format.height
+        ),
+    }
+
+    Ok(max_sustainable)
+}
+
+/// How long a receiver gets to react to a probe frame before its
+/// connection is checked again.
+const PROBE_SETTLE: Duration = Duration::from_millis(500);
+
+/// Probe frame size: small enough that each probe is encoded and sent at
+/// once. `--codec-probe-resolution` raises it for codecs with a minimum
+/// size.
+pub const DEFAULT_PROBE_RESOLUTION: (i32, i32) = (64, 64);
+
+/// Outcome of one codec in `run_probe_receiver_formats`.
+#[derive(Debug, Clone, Copy)]
+pub struct ProbeResult {
+    pub format: VideoFormat,
+    pub rc: i32,
+    /// Frames the library reported sent for the probe frame
+    pub frames_sent: i64,
+    pub still_connected: bool,
+}
+
+impl ProbeResult {
+    /// The frame went out and the receiver stayed connected.
+    pub fn accepted(&self) -> bool {
+        let sent = SendStatus::from(self.rc).is_accepted();
+        sent && self.frames_sent > 0 && self.still_connected
+    }
+
+    pub fn verdict(&self) -> &'static str {
+        if !SendStatus::from(self.rc).is_accepted() {
+            "send failed"
+        } else if self.frames_sent <= 0 {
+            "not sent"
+        } else if !self.still_connected {
+            "receiver disconnected"
+        } else {
+            "accepted"
+        }
+    }
+}
+
+/// Sends one frame of each codec in `formats` (the first format per codec,
+/// resized to `opts.probe_resolution`) to a connected receiver and records
+/// whether it kept the connection.
+///
+/// libomt doesn't report what a receiver decoded, so acceptance is judged
+/// from the sender side: the frame has to go out and the receiver has to
+/// still be connected `PROBE_SETTLE` later. A receiver that drops on a codec
+/// is waited for again before the next one.
+pub fn run_probe_receiver_formats(
+    formats: &[VideoFormat],
+    opts: &Options,
+) -> Result<Vec<ProbeResult>> {
+    let (width, height) = opts.probe_resolution;
+    let mut probes: Vec<VideoFormat> = Vec::new();
+    for format in formats {
+        if !probes.iter().any(|p| p.codec == format.codec) {
+            probes.push(VideoFormat {
+                width,
+                height,
+                // A stride for the preset's width doesn't fit the probe's
+                stride_override: None,
+                ..*format
+            });
+        }
+    }
+    if probes.is_empty() {
+        bail!("no formats to probe");
+    }
+    println!(
+        "\n=== Probing receiver formats ({} codecs at {}x{}) ===\n",
+        probes.len(),
+        width,
+        height
+    );
+
+    set_log_file(&opts.run_log_file("probe"))?;
+
+    // One sender for every codec, so the same receiver connection is tested
+    let quality = opts.quality.unwrap_or(OMTQuality_OMTQuality_Default);
+    println!("Quality: {}", quality_name(quality));
+    let sender = create_sender("RustSend_probe", quality, opts.create_retries)?;
+    if !wait_for_receiver(&sender, opts.connect_timeout) {
+        bail!(
+            "no receiver connected within {:.1}s to probe",
+            opts.connect_timeout.as_secs_f32()
+        );
+    }
+
+    let mut results = Vec::new();
+    for (i, format) in probes.iter().enumerate() {
+        if !sender.is_connected() && !wait_for_receiver(&sender, opts.connect_timeout) {
+            eprintln!(
+                "Receiver did not reconnect, skipping the remaining {} codec(s)",
+                probes.len() - i
+            );
+            break;
+        }
+        let mut frame_buf = format.create_test_frame(0, FramePattern::Static);
+        let mut frame = video_frame(format, false, &mut frame_buf);
+        frame.Timestamp = pts_for_frame(i as u64, format.fps_n, format.fps_d);
+
+        let before = sender.video_statistics().frames;
+        let rc = sender.send(&mut frame);
+        let frames_sent = sender.flush(opts.drain_timeout) - before;
+        thread::sleep(PROBE_SETTLE);
+        let result = ProbeResult {
+            format: *format,
+            rc,
+            frames_sent,
+            still_connected: sender.is_connected(),
+        };
+        println!(
+            "[{}] {}: {} (rc={}, {} frame(s) sent)",
+            codec_name(format.codec),
+            format.name,
+            result.verdict(),
+            rc,
+            frames_sent
+        );
+        results.push(result);
+    }
+
+    println!("\n=== Receiver compatibility ({}x{}) ===", width, height);
+    for result in &results {
+        println!(
+            "{:<6} {:<8} {}",
+            codec_name(result.format.codec),
+            if result.accepted() { "yes" } else { "no" },
+            result.verdict()
+        );
+    }
+    for format in &probes[results.len()..] {
+        println!("{:<6} {:<8} not probed", codec_name(format.codec), "-");
+    }
+
+    Ok(results)
+}
+
+/// Totals collected by `run_soak`.
+#[derive(Debug, Default)]
+pub struct SoakReport {
+    pub transitions: u32,
+    pub frames: i64,
+    pub elapsed: Duration,
+    pub errors: Vec<String>,
+}
+
+/// Repeatedly creates a sender with a random format and quality, sends for a
+/// random 0.5-10s and destroys it again, until `total` elapses or `stop` is
+/// set. This exercises the library's setup/teardown paths, which a fixed run
+/// only hits once.
+pub fn run_soak(
+    formats: &[VideoFormat],
+    total: Duration,
+    seed: u64,
+    opts: &Options,
+    stop: &AtomicBool,
+) -> Result<SoakReport> {
+    if formats.is_empty() {
+        bail!("no formats to soak");
+    }
+    println!(
+        "\n=== Soak test for {}s (seed {}) ===\n",
+        total.as_secs(),
+        seed
+    );
+
+    set_log_file(&opts.run_log_file("soak"))?;
+
+    let mut rng = XorShift::new(seed);
+    let mut report = SoakReport::default();
+    let start = Instant::now();
+    while start.elapsed() < total && !stop.load(Ordering::Relaxed) {
+        let format = formats[rng.below(formats.len() as u64) as usize];
+        let (quality, quality_name) = QUALITIES[1 + rng.below(3) as usize];
+        let segment = Duration::from_millis(500 + rng.below(9_500))
+            .min(total.saturating_sub(start.elapsed()));
+        report.transitions += 1;
+
+        println!(
+            "[{:.1}s] Transition {}: {} @ {} for {:.1}s",
+            start.elapsed().as_secs_f64(),
+            report.transitions,
+            format.name,
+            quality_name,
+            segment.as_secs_f64()
+        );
+        // A crash inside the library kills the process, so make sure the
+        // transition that caused it is already on screen
+        std::io::stdout().flush().ok();
+
+        match soak_segment(format, quality, segment, stop) {
+            Ok(frames) => report.frames += frames,
+            Err(e) => {
+                let error = format!(
+                    "transition {} ({} @ {}): {}",
+                    report.transitions, format.name, quality_name, e
+                );
+                eprintln!("Error in {}", error);
+                report.errors.push(error);
+            }
+        }
+    }
+    report.elapsed = start.elapsed();
+
+    println!("\n=== Soak report ===");
+    if stop.load(Ordering::Relaxed) {
+        println!("Interrupted");
+    }
+    println!("Seed: {}", seed);
+    println!("Duration: {:.1}s", report.elapsed.as_secs_f64());
+    println!("Transitions: {}", report.transitions);
+    println!("Frames sent: {}", report.frames);
+    println!("Failed transitions: {}", report.errors.len());
+    for error in &report.errors {
+        println!("  {}", error);
+    }
+
+    Ok(report)
+}
+
+/// Sends `format` for `duration` on a fresh sender and returns the frame
+/// count the library reported.
+fn soak_segment(
+    format: VideoFormat,
+    quality: OMTQuality,
+    duration: Duration,
+    stop: &AtomicBool,
+) -> Result<i64> {
+    // No wait for a receiver and no retries: the point is to churn senders
+    // quickly and record every failed creation
+    let sender = create_sender(&format!("RustSend_{}_soak", format.name), quality, 0)?;
+
+    let mut frame_buf = format.create_test_frame(0, FramePattern::Static);
+    let mut frame = video_frame(&format, false, &mut frame_buf);
+
+    let start = Instant::now();
+    let mut schedule = FrameSchedule::new(start, format.fps_n, format.fps_d);
+    let mut i = 0u64;
+    while start.elapsed() < duration && !stop.load(Ordering::Relaxed) {
+        frame.Timestamp = pts_for_frame(i, format.fps_n, format.fps_d);
+        let rc = sender.send(&mut frame);
+        if SendStatus::from(rc).is_fatal() {
+            bail!("omt_send failed at frame {} (rc={})", i, rc);
+        }
+        i += 1;
+        schedule.wait_for(i);
+    }
+
+    Ok(sender.video_statistics().frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_bars_cover_odd_widths() {
+        for (width, bar) in [(1366, 170), (1280, 160), (720, 90), (642, 80), (16, 2)] {
+            let sections: Vec<usize> = (0..width).map(|x| color_bar_section(x, width)).collect();
+            let mut widths = [0usize; 8];
+            for &section in &sections {
+                widths[section] += 1;
+            }
+            assert!(sections.windows(2).all(|w| w[0] <= w[1]), "{}", width);
+            // Seven equal bars of whole macropixels; the last takes the rest
+            assert_eq!(widths[..7], [bar; 7], "{}", width);
+            assert_eq!(widths[7], width - 7 * bar, "{}", width);
+            assert!(widths[7] >= bar, "{}", width);
+        }
+        assert_eq!(color_bar_section(1365, 1366), 7);
+    }
+
+    #[test]
+    fn generate_frame_starts_with_the_first_bar() {
+        let uyvy = VideoFormat::from_parts(OMTCodec_OMTCodec_UYVY, 640, 360, 30, 1).unwrap();
+        let bgra = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 640, 360, 30, 1).unwrap();
+        let nv12 = nv12(640, 360);
+
+        let buf = generate_frame(&uyvy, None, 0).unwrap();
+        assert_eq!(buf.len(), uyvy.buffer_size());
+        assert_eq!(buf[..4], [128, 235, 128, 235]);
+        let buf = generate_frame(&bgra, None, 0).unwrap();
+        assert_eq!(buf.len(), bgra.buffer_size());
+        assert_eq!(buf[..4], [255, 255, 255, 255]);
+        let buf = generate_frame(&nv12, None, 0).unwrap();
+        assert_eq!(buf.len(), nv12.buffer_size());
+        assert_eq!(
+            (buf[0], &buf[nv12.uv_offset()..][..2]),
+            (235, &[128, 128][..])
+        );
+
+        // Renderers: the color sweep starts on red (BT.601 at 360 lines),
+        // noise only fills the frame
+        let red = [
+            (uyvy, vec![90, 81, 240, 81]),
+            (bgra, vec![0, 0, 255, 255]),
+            (nv12, vec![81]),
+        ];
+        for (format, expected) in red {
+            let buf = generate_frame(&format, Some(&patterns::ColorSweepPattern), 0).unwrap();
+            assert_eq!(buf.len(), format.buffer_size(), "{}", format.name);
+            assert_eq!(buf[..expected.len()], expected[..], "{}", format.name);
+            let noise = patterns::NoisePattern { seed: 1 };
+            let buf = generate_frame(&format, Some(&noise), 3).unwrap();
+            assert_eq!(buf.len(), format.buffer_size(), "{}", format.name);
+        }
+    }
+
+    #[test]
+    fn preconverted_frames_match_generate_frame() {
+        let still = still::StillImage::new(image::DynamicImage::ImageRgb8(
+            image::RgbImage::from_fn(64, 48, |x, y| image::Rgb([x as u8 * 4, y as u8 * 5, 128])),
+        ));
+        let renderers: [Option<&dyn FrameRenderer>; 3] =
+            [None, Some(&patterns::ChromaEdgePattern), Some(&still)];
+        for codec in [
+            OMTCodec_OMTCodec_UYVY,
+            OMTCodec_OMTCodec_BGRA,
+            OMTCodec_OMTCodec_NV12,
+        ] {
+            let format = VideoFormat::from_parts(codec, 640, 360, 30, 1).unwrap();
+            for renderer in renderers {
+                let name = renderer.map_or("bars", |r| r.name());
+                let buf = preconvert(&format, renderer, false, false, 64).unwrap();
+                assert_eq!(buf.as_ptr() as usize % 64, 0);
+                assert!(
+                    *buf == generate_frame(&format, renderer, 0).unwrap()[..],
+                    "{} {}",
+                    format.name,
+                    name
+                );
+            }
+        }
+
+        // The BGRA alpha run's ramp and premultiplication are part of it too
+        let format = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 640, 360, 30, 1).unwrap();
+        let buf = preconvert(&format, None, true, true, 64).unwrap();
+        let mut expected = format.create_test_frame(0, FramePattern::Static);
+        convert::apply_alpha_gradient(&mut expected, 640);
+        convert::premultiply_bgra(&mut expected);
+        assert!(*buf == expected[..]);
+    }
+
+    #[test]
+    fn black_frames_are_limited_range_or_opaque_black() {
+        let uyvy = VideoFormat::from_parts(OMTCodec_OMTCodec_UYVY, 1280, 720, 30, 1).unwrap();
+        let buf = uyvy.create_black_frame();
+        assert_eq!(buf.len(), uyvy.buffer_size());
+        assert!(buf.chunks_exact(4).all(|p| p == [128, 16, 128, 16]));
+
+        let bgra = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 1280, 720, 30, 1).unwrap();
+        let buf = bgra.create_black_frame();
+        assert_eq!(buf.len(), bgra.buffer_size());
+        assert!(buf.chunks_exact(4).all(|p| p == [0, 0, 0, 255]));
+
+        // Padded, odd-sized NV12: 641 pixels and 321 UV pairs per row, with
+        // the padding left zeroed
+        let nv12 = VideoFormat {
+            stride_align: 64,
+            ..nv12(641, 361)
+        };
+        let buf = nv12.create_black_frame();
+        let stride = nv12.stride() as usize;
+        assert_eq!(stride, 704);
+        let (y_plane, uv_plane) = buf.split_at(nv12.uv_offset());
+        for row in y_plane.chunks_exact(stride) {
+            assert!(row[..641].iter().all(|&y| y == 16));
+            assert!(row[641..].iter().all(|&b| b == 0));
+        }
+        assert_eq!(uv_plane.len(), stride * 181);
+        for row in uv_plane.chunks_exact(stride) {
+            assert!(row[..642].iter().all(|&c| c == 128));
+            assert!(row[642..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn send_status_maps_the_documented_codes() {
+        // (code, status, fatal, accepted)
+        for (rc, status, fatal, accepted) in [
+            (0, SendStatus::Success, false, true),
+            (12428, SendStatus::Queued(12428), false, true),
+            (19448, SendStatus::Queued(19448), false, true),
+            (29843, SendStatus::Queued(29843), false, true),
+            (39293, SendStatus::Queued(39293), false, true),
+            (26984, SendStatus::BufferOverflow, false, false),
+            (-1, SendStatus::GeneralError, true, false),
+            // Other positive codes have behaved as warnings, negative ones
+            // as errors
+            (1, SendStatus::Unknown(1), false, true),
+            (65535, SendStatus::Unknown(65535), false, true),
+            (-2, SendStatus::Unknown(-2), true, false),
+            (i32::MIN, SendStatus::Unknown(i32::MIN), true, false),
+        ] {
+            assert_eq!(SendStatus::from(rc), status, "rc={}", rc);
+            assert_eq!(status.is_fatal(), fatal, "rc={}", rc);
+            assert_eq!(status.is_accepted(), accepted, "rc={}", rc);
+        }
+    }
+
+    #[test]
+    fn color_space_by_line_count() {
+        const BT601: OMTColorSpace = OMTColorSpace_OMTColorSpace_BT601;
+        const BT709: OMTColorSpace = OMTColorSpace_OMTColorSpace_BT709;
+        for (width, height, expected) in [
+            (1280, 719, (BT601, ColorSpaceSource::Fallback(719))),
+            (1280, 720, (BT709, ColorSpaceSource::Standard(720))),
+            (1280, 721, (BT709, ColorSpaceSource::Fallback(721))),
+            (1920, 1080, (BT709, ColorSpaceSource::Standard(1080))),
+            (3840, 2160, (BT709, ColorSpaceSource::Standard(2160))),
+            (720, 576, (BT601, ColorSpaceSource::Standard(576))),
+            // Vertical video counts its shorter side
+            (1080, 1920, (BT709, ColorSpaceSource::Standard(1080))),
+        ] {
+            let format =
+                VideoFormat::from_parts(OMTCodec_OMTCodec_UYVY, width, height, 30, 1).unwrap();
+            assert_eq!(format.color_space_choice(), expected, "{}", format.name);
+        }
+        let overridden = VideoFormat {
+            colorspace: Some(BT601),
+            ..VideoFormat::from_parts(OMTCodec_OMTCodec_UYVY, 1920, 1080, 30, 1).unwrap()
+        };
+        assert_eq!(
+            overridden.color_space_choice(),
+            (BT601, ColorSpaceSource::Override)
+        );
+    }
+
+    #[test]
+    fn pts_for_frame_is_exact() {
+        assert_eq!(pts_for_frame(0, 30, 1), 0);
+        assert_eq!(pts_for_frame(30, 30, 1), 10_000_000);
+        // 30000 frames at 29.97 are exactly 1001s, with nothing lost to the
+        // 333666.67-tick frame duration
+        assert_eq!(pts_for_frame(30000, 30000, 1001), 10_010_000_000);
+        assert_eq!(pts_for_frame(1, 30000, 1001), 333_666);
+        assert_eq!(pts_for_frame(3, 30000, 1001), 1_001_000);
+        // A year of 60000/1001 frames, whose index * ticks * 1001 is past
+        // i64 and needs the i128 intermediate
+        assert_eq!(
+            pts_for_frame(1_890_269_730, 60000, 1001),
+            315_359_999_955_000
+        );
+        assert_eq!(pts_for_frame(u32::MAX as u64, 60, 1), 715_827_882_500_000);
+    }
+
+    #[test]
+    fn ten_seconds_at_23_976() {
+        let frames = frames_for_duration(10, 24000, 1001);
+        assert_eq!(frames, 240);
+        let last = pts_for_frame(frames as u64 - 1, 24000, 1001);
+        assert_eq!(last, 99_682_916);
+        assert!(last < 10 * TICKS_PER_SEC);
+        // 240 frames of 1001/24000s are exactly 10.01s
+        assert_eq!(pts_for_frame(240, 24000, 1001), 100_100_000);
+        // Integer rates are unchanged
+        assert_eq!(frames_for_duration(10, 24, 1), 240);
+        assert_eq!(frames_for_duration(10, 25, 1), 250);
+        assert_eq!(frames_for_duration(5, 30, 1), 150);
+    }
+
+    fn nv12(width: i32, height: i32) -> VideoFormat {
+        VideoFormat::from_parts(OMTCodec_OMTCodec_NV12, width, height, 30, 1).unwrap()
+    }
+
+    #[test]
+    fn nv12_buffer_size_rounds_the_uv_plane_up() {
+        // (width, height, stride, UV rows, buffer size)
+        for (width, height, stride, rows, size) in [
+            (1280, 720, 1280, 360, 1280 * 720 * 3 / 2),
+            (1920, 1080, 1920, 540, 1920 * 1080 * 3 / 2),
+            (641, 361, 642, 181, 642 * (361 + 181)),
+        ] {
+            let format = nv12(width, height);
+            format.check_size().unwrap();
+            assert_eq!(format.stride(), stride, "{}", format.name);
+            assert_eq!(format.chroma_rows(), rows, "{}", format.name);
+            assert_eq!(
+                format.chroma_row_bytes(),
+                stride as usize,
+                "{}",
+                format.name
+            );
+            assert_eq!(format.buffer_size(), size, "{}", format.name);
+        }
+        // Rounding down would leave the last Y row without chroma
+        assert!(nv12(641, 361).buffer_size() > 642 * (361 + 361 / 2));
+    }
+
+    #[test]
+    fn sender_info_rejects_strings_that_do_not_fit() {
+        let mut field = [0x7f_i8; 8];
+        write_cstr(&mut field, "1.0.0").unwrap();
+        assert_eq!(
+            &field[..6],
+            &[b'1' as i8, b'.' as i8, b'0' as i8, b'.' as i8, b'0' as i8, 0]
+        );
+        // Seven bytes plus the NUL fill the field exactly
+        write_cstr(&mut field, "1234567").unwrap();
+        assert_eq!(field[7], 0);
+        let err = write_cstr(&mut field, "12345678").unwrap_err();
+        assert!(err.to_string().contains("at most 7"), "{}", err);
+        let err = write_cstr(&mut field, "a\0b").unwrap_err();
+        assert!(err.to_string().contains("NUL"), "{}", err);
+
+        let info = sender_info("omt-send-test-rs", "Rust OMT Test", "1.0.0").unwrap();
+        let name: Vec<u8> = info
+            .ProductName
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        assert_eq!(name, b"omt-send-test-rs");
+        let long = "x".repeat(info.ProductName.len());
+        let err = sender_info(&long, "Rust OMT Test", "1.0.0").unwrap_err();
+        assert_eq!(err.to_string(), "invalid product name");
+        let err = sender_info("omt-send-test-rs", "Rust\0OMT", "1.0.0").unwrap_err();
+        assert_eq!(err.to_string(), "invalid manufacturer");
+    }
+
+    #[test]
+    fn sizes_at_8k_do_not_overflow() {
+        for (codec, size) in [
+            (OMTCodec_OMTCodec_UYVY, 7680 * 4320 * 2),
+            (OMTCodec_OMTCodec_BGRA, 7680 * 4320 * 4),
+            (OMTCodec_OMTCodec_NV12, 7680 * 4320 * 3 / 2),
+        ] {
+            let format = VideoFormat::from_parts(codec, 7680, 4320, 30, 1).unwrap();
+            format.check_size().unwrap();
+            assert_eq!(format.buffer_size(), size, "{}", format.name);
+            let mut buf = [0u8; 1];
+            let frame = video_frame(&format, false, &mut buf);
+            assert_eq!(frame.DataLength as usize, size, "{}", format.name);
+        }
+        // Past what an i32 DataLength can describe: rejected, not wrapped
+        let huge = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 32768, 32768, 30, 1).unwrap();
+        assert_eq!(huge.buffer_size(), 1 << 32);
+        let err = huge.check_size().unwrap_err().to_string();
+        assert!(
+            err.contains("more than an OMTMediaFrame can describe"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn nv12_stride_is_padded_to_the_alignment() {
+        for (width, stride) in [(1280, 1280), (1288, 1344)] {
+            let format = VideoFormat {
+                stride_align: 64,
+                ..nv12(width, 720)
+            };
+            assert_eq!(format.stride(), stride, "width {}", width);
+            assert_eq!(format.uv_offset(), stride as usize * 720);
+            assert_eq!(format.buffer_size(), stride as usize * (720 + 360));
+            format.check_size().unwrap();
+        }
+    }
+
+    #[test]
+    fn nv12_stride_override_sets_stride_and_size() {
+        for (width, height) in [(1280, 720), (1366, 768), (641, 361)] {
+            let stride = (width + 31) / 32 * 32;
+            let format = VideoFormat {
+                stride_override: Some(stride),
+                ..nv12(width, height)
+            };
+            format.check_size().unwrap();
+            assert_eq!(format.stride(), stride);
+            let size = stride as usize * (height as usize + format.chroma_rows());
+            assert_eq!(format.buffer_size(), size);
+
+            let mut buf = vec![0u8; size];
+            let frame = video_frame(&format, false, &mut buf);
+            assert_eq!(frame.Stride, stride);
+            assert_eq!(frame.DataLength, size as i32);
+        }
+    }
+
+    #[test]
+    fn nv12_stride_override_narrower_than_a_row_is_rejected() {
+        let format = VideoFormat {
+            stride_override: Some(1278),
+            ..nv12(1280, 720)
+        };
+        let err = format.check_size().unwrap_err().to_string();
+        assert!(err.contains("stride 1278 is narrower"), "{}", err);
+        // 641 pixels need 642 bytes of UV pairs
+        let odd = VideoFormat {
+            stride_override: Some(641),
+            ..nv12(641, 361)
+        };
+        assert!(odd.check_size().is_err());
+    }
+
+    #[test]
+    fn unmet_receiver_minimum_fails() {
+        let timeout = Duration::from_secs(5);
+        let err = check_receiver_count(2, 3, timeout).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "only 2 of 3 required receivers connected within 5.0s"
+        );
+        assert!(check_receiver_count(0, 1, timeout).is_err());
+        assert!(check_receiver_count(3, 3, timeout).is_ok());
+        assert!(check_receiver_count(4, 3, timeout).is_ok());
+    }
+}
@@ -1,424 +1,1350 @@
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use clap::{ArgAction, Parser, Subcommand};
+use omt_send_test_rs::{bindings::*, *};
 use std::{
-    ffi::CString,
-    mem,
+    io::Write,
+    path::PathBuf,
+    str::FromStr,
     sync::atomic::{AtomicBool, Ordering},
-    sync::Arc,
     thread,
-    time::{Duration, Instant},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-mod bindings;
-use bindings::*;
-
-#[derive(Debug, Clone, Copy)]
-struct VideoFormat {
-    codec: OMTCodec,
-    width: i32,
-    height: i32,
-    fps_n: i32,
-    fps_d: i32,
-    name: &'static str,
-}
+/// Set by Ctrl-C so long-running modes can stop and still report.
+static STOP: AtomicBool = AtomicBool::new(false);
 
-impl VideoFormat {
-    fn stride(&self) -> i32 {
-        match self.codec {
-            x if x == OMTCodec_OMTCodec_UYVY => self.width * 2,
-            x if x == OMTCodec_OMTCodec_BGRA => self.width * 4,
-            x if x == OMTCodec_OMTCodec_NV12 => self.width, // Y plane stride
-            _ => self.width * 4,
-        }
+/// Set by SIGUSR1 to ask the send loop for a statistics snapshot.
+static STATS_REQUEST: AtomicBool = AtomicBool::new(false);
+
+/// Makes SIGUSR1 print a statistics snapshot from the send loop without
+/// stopping it. The handler only sets [`STATS_REQUEST`]; the loop prints.
+/// SA_RESTART keeps the signal from failing sleeps and I/O in progress.
+#[cfg(unix)]
+fn install_stats_signal() {
+    extern "C" fn on_sigusr1(_: libc::c_int) {
+        STATS_REQUEST.store(true, Ordering::Relaxed);
+    }
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigusr1 as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut());
     }
+}
 
-    fn buffer_size(&self) -> usize {
-        match self.codec {
-            x if x == OMTCodec_OMTCodec_UYVY => (self.stride() * self.height) as usize,
-            x if x == OMTCodec_OMTCodec_BGRA => (self.stride() * self.height) as usize,
-            x if x == OMTCodec_OMTCodec_NV12 => {
-                // NV12: Y plane (width * height) + UV plane (width * height / 2)
-                ((self.width * self.height) + (self.width * self.height / 2)) as usize
-            }
-            _ => (self.stride() * self.height) as usize,
-        }
-    }
-
-    fn create_test_frame(&self) -> Vec<u8> {
-        let mut buf = vec![0u8; self.buffer_size()];
-
-        match self.codec {
-            x if x == OMTCodec_OMTCodec_UYVY => {
-                // UYVY: Create color bars pattern
-                for (y, row) in buf.chunks_exact_mut(self.stride() as usize).enumerate() {
-                    for (x_pair, pair) in row.chunks_exact_mut(4).enumerate() {
-                        let x = x_pair * 2;
-                        let section = (x * 8) / self.width as usize;
-
-                        // Color bar values (U, Y, V)
-                        let (u, y_val, v) = match section {
-                            0 => (128, 235, 128), // White
-                            1 => (16, 210, 146),  // Yellow
-                            2 => (166, 170, 16),  // Cyan
-                            3 => (54, 145, 34),   // Green
-                            4 => (202, 106, 222), // Magenta
-                            5 => (90, 81, 240),   // Red
-                            6 => (240, 41, 110),  // Blue
-                            _ => (128, 16, 128),  // Black
-                        };
-
-                        pair[0] = u; // U
-                        pair[1] = y_val; // Y0
-                        pair[2] = v; // V
-                        pair[3] = y_val; // Y1
-                    }
-                }
-            }
-            x if x == OMTCodec_OMTCodec_BGRA => {
-                // BGRA: Create gradient pattern
-                for (i, pixel) in buf.chunks_exact_mut(4).enumerate() {
-                    let x = (i % self.width as usize) as f32;
-                    let y = (i / self.width as usize) as f32;
-
-                    pixel[0] = ((x / self.width as f32) * 255.0) as u8; // B
-                    pixel[1] = ((y / self.height as f32) * 255.0) as u8; // G
-                    pixel[2] = (((x + y) / (self.width + self.height) as f32) * 255.0) as u8; // R
-                    pixel[3] = 255; // A
-                }
-            }
-            x if x == OMTCodec_OMTCodec_NV12 => {
-                // NV12: Y plane followed by interleaved UV
-                let y_size = (self.width * self.height) as usize;
-                // Fill Y plane
-                for i in 0..y_size {
-                    buf[i] = 180; // Y value
-                }
-                // Fill UV plane (interleaved U and V)
-                let uv_start = y_size;
-                let uv_size = (self.width * self.height / 2) as usize;
-                for i in 0..(uv_size / 2) {
-                    buf[uv_start + i * 2] = 128; // U
-                    buf[uv_start + i * 2 + 1] = 128; // V
-                }
-            }
-            _ => {}
-        }
+/// There is no SIGUSR1 outside Unix; only the periodic stats line is shown.
+#[cfg(not(unix))]
+fn install_stats_signal() {}
 
-        buf
+/// Reads `var` from the environment, treating an empty value as unset.
+fn env_value<T>(var: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: Into<anyhow::Error>,
+{
+    match std::env::var(var) {
+        Ok(value) if !value.is_empty() => value.parse().map(Some).map_err(|e: T::Err| {
+            e.into()
+                .context(format!("invalid {} value: {}", var, value))
+        }),
+        _ => Ok(None),
     }
 }
 
-fn interpret_return_code(rc: i32) -> &'static str {
-    match rc {
-        0 => "Success",
-        // These appear to be status codes that still result in successful transmission
-        12428 | 19448 | 29843 | 39293 => "Frame queued/processing (non-fatal)",
-        26984 => "Buffer overflow or encoding error",
-        -1 => "General error",
-        _ if rc > 0 => "Status/warning code (may be non-fatal)",
-        _ => "Unknown error",
+fn parse_codec(name: &str) -> Result<OMTCodec> {
+    let names: Vec<&str> = SUPPORTED_CODECS.iter().map(|(_, name)| *name).collect();
+    if name.eq_ignore_ascii_case("NV21") {
+        // OMTCodec has no VU-ordered variant of NV12
+        bail!(
+            "libomt has no NV21 codec, expected one of {} (NV12 is the UV-ordered equivalent)",
+            names.join(", ")
+        );
     }
+    codec_from_name(name).with_context(|| {
+        format!(
+            "unknown codec {}, expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })
 }
 
-fn run_send_test(format: VideoFormat, duration_secs: u32, use_alpha: bool) -> Result<()> {
-    unsafe {
-        println!("\n=== Testing {} ===\n", format.name);
-
-        // Set up logging
-        let logfile = CString::new("/tmp/omt-send.log")?;
-        omt_setloggingfilename(logfile.as_ptr());
-
-        // Create sender
-        let name = CString::new(format!("RustSend_{}", format.name))?;
-        let sender = omt_send_create(name.as_ptr(), OMTQuality_OMTQuality_Medium);
-        if sender.is_null() {
-            bail!("omt_send_create failed");
-        }
-
-        // Wait for receiver connection
-        let mut connected = false;
-        println!("Waiting for receiver connection...");
-        for i in 0..30 {
-            if omt_send_connections(sender) > 0 {
-                connected = true;
-                println!("Receiver connected after {:.1}s", i as f32 * 0.1);
-                break;
-            }
-            thread::sleep(Duration::from_millis(100));
-        }
-        if !connected {
-            eprintln!("Warning: No receivers connected, proceeding anyway");
+/// Fills options not given on the command line from `OMT_*` environment
+/// variables, so the command line wins over the environment, which wins over
+/// the presets.
+fn apply_env(opts: &mut Options) -> Result<()> {
+    if opts.format_name.is_none() {
+        opts.format_name = env_value("OMT_FORMAT")?;
+    }
+    if opts.codec.is_none() {
+        if let Some(name) = env_value::<String>("OMT_CODEC")? {
+            opts.codec = Some(parse_codec(&name).context("invalid OMT_CODEC")?);
         }
+    }
+    if opts.width.is_none() {
+        opts.width = env_value("OMT_WIDTH")?;
+    }
+    if opts.height.is_none() {
+        opts.height = env_value("OMT_HEIGHT")?;
+    }
+    if opts.fps.is_none() {
+        opts.fps = env_value("OMT_FPS")?;
+    }
+    if opts.duration_secs.is_none() {
+        opts.duration_secs = env_value("OMT_DURATION")?;
+    }
+    if opts.port.is_none() {
+        opts.port = env_value("OMT_PORT")?;
+    }
+    Ok(())
+}
 
-        // Set sender info
-        let mut info: OMTSenderInfo = mem::zeroed();
-        fn write_cstr(dst: &mut [i8], s: &str) {
-            let bytes = s.as_bytes();
-            let n = bytes.len().min(dst.len().saturating_sub(1));
-            for i in 0..n {
-                dst[i] = bytes[i] as i8;
-            }
-            if !dst.is_empty() {
-                dst[n] = 0;
-            }
-        }
-        write_cstr(&mut info.ProductName, "omt-send-test-rs");
-        write_cstr(&mut info.Manufacturer, "Rust OMT Test");
-        write_cstr(&mut info.Version, "1.0.0");
-        omt_send_setsenderinformation(sender, &mut info as *mut OMTSenderInfo);
-
-        // Create test frame
-        let mut frame_buf = format.create_test_frame();
-
-        // Setup OMTMediaFrame
-        let mut frame: OMTMediaFrame = mem::zeroed();
-        frame.Type = OMTFrameType_OMTFrameType_Video;
-        frame.Codec = format.codec;
-        frame.Width = format.width;
-        frame.Height = format.height;
-        frame.Stride = format.stride();
-        frame.Flags = if use_alpha && format.codec == OMTCodec_OMTCodec_BGRA {
-            OMTVideoFlags_OMTVideoFlags_Alpha
-        } else {
-            OMTVideoFlags_OMTVideoFlags_None
-        };
-        frame.FrameRateN = format.fps_n;
-        frame.FrameRateD = format.fps_d;
-        frame.AspectRatio = (format.width as f32) / (format.height as f32);
-        frame.ColorSpace = if format.height < 720 {
-            OMTColorSpace_OMTColorSpace_BT601
-        } else {
-            OMTColorSpace_OMTColorSpace_BT709
-        };
-        frame.Data = frame_buf.as_mut_ptr() as *mut _;
-        // DataLength should be the actual data size, not buffer size
-        frame.DataLength = match format.codec {
-            x if x == OMTCodec_OMTCodec_NV12 => {
-                // For NV12, DataLength is Y + UV size
-                ((format.width * format.height) + (format.width * format.height / 2)) as i32
-            }
-            _ => {
-                // For packed formats, it's stride * height
-                (format.stride() * format.height) as i32
-            }
-        };
+fn parse_colorspace(name: &str) -> Result<OMTColorSpace> {
+    if name.eq_ignore_ascii_case("BT2020") {
+        bail!("libomt has no BT2020 color space, UHD is sent as BT709");
+    }
+    colorspace_from_name(name)
+        .with_context(|| format!("unknown colorspace {}, expected BT601 or BT709", name))
+}
 
-        // High-precision timing
-        let ticks_per_sec = 10_000_000i64;
-        let ticks_per_frame = ticks_per_sec * (format.fps_d as i64) / (format.fps_n as i64);
-        let frame_duration = Duration::from_secs_f64(format.fps_d as f64 / format.fps_n as f64);
+fn parse_resolution(value: &str) -> Result<(i32, i32)> {
+    let (width, height) = value
+        .split_once(['x', 'X'])
+        .with_context(|| format!("invalid resolution {}, expected WxH", value))?;
+    let parse = |n: &str| {
+        n.parse::<i32>()
+            .ok()
+            .filter(|&n| n > 0 && n % 2 == 0)
+            .with_context(|| format!("invalid resolution {}, expected positive even WxH", value))
+    };
+    Ok((parse(width)?, parse(height)?))
+}
 
-        let mut pts: i64 = 0;
-        let frames_to_send = duration_secs * (format.fps_n as u32) / (format.fps_d as u32);
-        let start_time = Instant::now();
-        let mut next_frame_time = start_time;
+fn parse_quality(name: &str) -> Result<OMTQuality> {
+    quality_from_name(name).with_context(|| {
+        format!(
+            "unknown quality {}, expected Default, Low, Medium or High",
+            name
+        )
+    })
+}
 
-        // Statistics tracking
-        let mut stats_counter = 0;
-        let stats_interval = format.fps_n; // Print stats every second
+/// Sends test patterns over OMT and reports libomt's statistics.
+///
+/// Runs every preset unless one is named. Options used by `replay` can also
+/// follow it.
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Args {
+    /// Preset to run; see --list-presets
+    format: Option<String>,
 
-        println!(
-            "Sending {} frames at {}x{} {}fps...",
-            frames_to_send,
-            format.width,
-            format.height,
-            format.fps_n as f64 / format.fps_d as f64
-        );
+    #[command(subcommand)]
+    command: Option<Command>,
 
-        for i in 0..frames_to_send {
-            frame.Timestamp = pts;
+    /// More output: frame setup, then per-frame PTS, then per-frame return
+    /// codes and statistics
+    #[arg(short, long = "verbose", action = ArgAction::Count, global = true)]
+    verbose: u8,
 
-            let rc = omt_send(sender, &mut frame as *mut OMTMediaFrame);
-            if rc != 0 {
-                let status = interpret_return_code(rc);
+    // Format
+    /// Preset to run, as an alternative to the positional name
+    #[arg(long = "format", value_name = "NAME", conflicts_with = "format")]
+    format_flag: Option<String>,
+    /// Send BGRA formats only with the alpha flag, instead of once without
+    /// and once with it
+    #[arg(long)]
+    alpha: bool,
+    /// Override the preset codec
+    #[arg(long, value_name = "UYVY|BGRA|NV12", value_parser = parse_codec)]
+    codec: Option<OMTCodec>,
+    /// Override the preset width (positive; even for UYVY)
+    #[arg(long)]
+    width: Option<i32>,
+    /// Override the preset height (positive)
+    #[arg(long)]
+    height: Option<i32>,
+    /// Override the preset frame rate, as N or N/D (e.g. 60000/1001)
+    #[arg(long, value_name = "N[/D]")]
+    fps: Option<FrameRate>,
+    /// Seconds to send per format [default: 5]
+    #[arg(long, value_name = "SECS")]
+    duration: Option<u32>,
+    /// Frames to send per format instead of --duration
+    #[arg(long, value_name = "N")]
+    send_count: Option<u32>,
+    /// Send --send-count frames as fast as possible with nothing depending
+    /// on the clock, and report only the fields two runs reproduce
+    #[arg(long)]
+    deterministic: bool,
+    /// Pad NV12 rows to this many bytes (a power of two up to 4096)
+    #[arg(long, value_name = "N")]
+    nv12_stride_align: Option<u32>,
+    /// Send NV12 with exactly this stride in bytes, at least the width
+    /// rounded up to even
+    #[arg(long, value_name = "BYTES", conflicts_with = "nv12_stride_align")]
+    nv12_stride: Option<u32>,
+    /// Color space to send with [default: by line count, BT601 for SD and
+    /// BT709 for HD and up]
+    #[arg(long, value_name = "BT601|BT709", value_parser = parse_colorspace)]
+    colorspace: Option<OMTColorSpace>,
+    /// OMTQuality to send with [default: per codec, see --show-defaults]
+    #[arg(long, value_name = "Default|Low|Medium|High", value_parser = parse_quality, global = true)]
+    quality: Option<OMTQuality>,
+    /// Print the per-codec default quality and exit
+    #[arg(long)]
+    show_defaults: bool,
+    /// Print every built-in preset with its parameters and exit
+    #[arg(long)]
+    list_presets: bool,
+    /// List the OMT sources discovered on the network and exit
+    #[arg(long)]
+    list: bool,
 
-                // Check if receiver disconnected
-                if omt_send_connections(sender) == 0 {
-                    eprintln!("Receiver disconnected, stopping");
-                    break;
-                }
+    // Content
+    /// Built-in pattern
+    #[arg(
+        long,
+        value_name = "bars|noise|chroma-edge|color-sweep|flicker|flicker-color",
+        default_value = "bars"
+    )]
+    pattern: patterns::Pattern,
+    /// How the built-in bars change per frame, so a frozen receiver shows:
+    /// a sweeping bar, a burned-in frame number, or nothing
+    #[arg(
+        long,
+        value_name = "static|moving-bar|frame-counter",
+        default_value = "moving-bar"
+    )]
+    frame_pattern: patterns::FramePattern,
+    /// Cycle solid colors once per second; same as --pattern color-sweep
+    #[arg(long, conflicts_with = "pattern")]
+    color_sweep: bool,
+    /// Alternate black and white every frame; same as --pattern flicker
+    #[arg(long, conflicts_with_all = ["pattern", "color_sweep"])]
+    flicker: bool,
+    /// Send a still image (PNG/JPEG) letterboxed to the format; BGRA runs
+    /// of an image with alpha set the alpha flag
+    #[arg(long, value_name = "PATH")]
+    image: Option<PathBuf>,
+    /// Send a rhai script pattern (needs `--features script`)
+    #[arg(long, value_name = "PATH")]
+    script: Option<PathBuf>,
+    /// Burn in a SMPTE timecode starting here
+    #[arg(long, value_name = "HH:MM:SS:FF")]
+    start_timecode: Option<String>,
+    /// Burn the frame index into each frame as a black/white barcode in
+    /// this region, for automated drop detection downstream
+    #[arg(long, value_name = "top|bottom|Y:HEIGHT")]
+    frame_barcode: Option<barcode::BarcodeRegion>,
+    /// Premultiply the BGRA alpha run
+    #[arg(long)]
+    alpha_premultiplied: bool,
+    /// Render animated patterns on the send thread
+    #[arg(long)]
+    no_pipeline: bool,
+    /// Fail if static content (bars, --image, solid colors) is converted
+    /// inside the send loop rather than once before it
+    #[arg(long)]
+    preconvert: bool,
 
-                // For buffer overflow, wait a bit and retry
-                if rc == 26984 {
-                    eprintln!("Buffer overflow at frame {}, waiting...", i);
-                    thread::sleep(Duration::from_millis(100));
-                    continue;
-                }
+    // Sending
+    /// Skip every Nth frame (at least 2)
+    #[arg(long, value_name = "N")]
+    drop_every: Option<u64>,
+    /// Skip this percentage of frames at random
+    #[arg(long, value_name = "PCT")]
+    simulate_loss: Option<f64>,
+    /// Delay each send by a random 0 to MS milliseconds
+    #[arg(long, value_name = "MS")]
+    simulate_jitter_ms: Option<u64>,
+    /// Count frames that finish sending more than MS after their scheduled
+    /// time as late
+    #[arg(long, value_name = "MS")]
+    deadline_ms: Option<u64>,
+    /// Stop waiting for omt_send after MS milliseconds and report the hang;
+    /// the run ends after 3 timeouts in a row
+    #[arg(long, value_name = "MS")]
+    send_timeout_ms: Option<u64>,
+    /// Cap the send rate at this many Mbps
+    #[arg(long, value_name = "MBPS")]
+    target_bitrate: Option<f64>,
+    /// Keep sending black while no receiver is connected
+    #[arg(long)]
+    send_black_on_disconnect: bool,
+    /// Don't fail a run that stopped early because the receiver disconnected
+    #[arg(long)]
+    allow_disconnect: bool,
+    /// Alignment of frame buffers in bytes (a power of two up to 4096)
+    #[arg(long, value_name = "N", default_value_t = buffer::DEFAULT_ALIGN, global = true)]
+    buffer_align: usize,
+    /// Milliseconds to wait for queued frames before the final statistics
+    #[arg(long, value_name = "MS", default_value_t = 500, global = true)]
+    drain_ms: u64,
+    /// Extra omt_send_create attempts before giving up, 200ms apart and
+    /// doubling
+    #[arg(long, value_name = "N", default_value_t = 2, global = true)]
+    create_retries: u32,
+    /// Record every sent frame to a raw dump with a `.json` sidecar
+    #[arg(long, value_name = "PATH")]
+    dump: Option<PathBuf>,
 
-                // Non-fatal status codes - continue normally
-                if status.contains("non-fatal") {
-                    // Frame was likely still sent, continue
-                } else {
-                    // Fatal error
-                    eprintln!("Fatal error at frame {}: {} (rc={})", i, status, rc);
-                    bail!("omt_send failed at frame {} (rc={})", i, rc);
-                }
-            }
+    // Receivers
+    /// Fail instead of sending to nobody
+    #[arg(long, global = true)]
+    require_receiver: bool,
+    /// Wait for the receiver whose metadata contains this name
+    #[arg(long, value_name = "NAME", global = true)]
+    target_receiver: Option<String>,
+    /// Wait for at least this many receivers
+    #[arg(long, value_name = "N", global = true)]
+    count_receivers_min: Option<u32>,
+    /// Log changes in the connection count
+    #[arg(long)]
+    list_connections: bool,
 
-            pts = pts.saturating_add(ticks_per_frame);
-            stats_counter += 1;
+    // Network
+    /// Fix the sending port (1024-65535)
+    #[arg(long, global = true)]
+    port: Option<u16>,
+    /// Listen address (libomt picks it; ignored with a warning)
+    #[arg(long, value_name = "ADDR", global = true)]
+    address: Option<String>,
+    /// List network interfaces and exit
+    #[arg(long)]
+    interface_list: bool,
+    /// List network interfaces as JSON and exit
+    #[arg(long)]
+    interface_list_json: bool,
 
-            // Print statistics periodically
-            if stats_counter >= stats_interval {
-                let mut vstats: OMTStatistics = mem::zeroed();
-                omt_send_getvideostatistics(sender, &mut vstats as *mut OMTStatistics);
-                println!(
-                    "[{:.1}s] Sent: {} bytes, {} frames, dropped: {}, codec_time: {}ms",
-                    start_time.elapsed().as_secs_f64(),
-                    vstats.BytesSent,
-                    vstats.Frames,
-                    vstats.FramesDropped,
-                    vstats.CodecTimeSinceLast
-                );
-                stats_counter = 0;
-            }
+    // Reporting
+    /// Print statistics every N frames [default: about once a second]
+    #[arg(long, value_name = "FRAMES", conflicts_with = "stats_interval_secs")]
+    stats_interval: Option<u32>,
+    /// Print statistics every SECS seconds
+    #[arg(long, value_name = "SECS")]
+    stats_interval_secs: Option<f64>,
+    /// Print the settings banner as JSON
+    #[arg(long)]
+    banner_json: bool,
+    /// Check the frame fields before the first send
+    #[arg(long)]
+    validate_frame: bool,
+    /// Fail before sending if Stride is too small for the codec and width
+    /// or the buffer is shorter than Stride x height
+    #[arg(long, global = true)]
+    strict_stride: bool,
+    /// Print an xxh3 hash of every frame before it is sent
+    #[arg(long)]
+    hash_frames: bool,
+    /// Log a warning with the frame index whenever omt_send returns this
+    /// code (repeatable)
+    #[arg(long, value_name = "CODE", action = ArgAction::Append, allow_negative_numbers = true)]
+    warn_on_status_code: Vec<i32>,
+    /// Sent frames to remember and print when a run fails or panics
+    /// (0 disables)
+    #[arg(long, value_name = "N", default_value_t = 32)]
+    frame_history: usize,
+    /// Fail if the peak or mean codec time exceeds this budget
+    #[arg(long, value_name = "MS")]
+    max_codec_time_ms: Option<i64>,
+    /// Fail if libomt reports more than this many dropped frames
+    #[arg(long, value_name = "N")]
+    max_drops: Option<i64>,
+    /// Fail if dropped frames exceed this percentage of sent frames
+    #[arg(long, value_name = "PCT")]
+    max_drop_rate: Option<f64>,
+    /// Save the results as a baseline report
+    #[arg(long, value_name = "JSON")]
+    save_baseline: Option<PathBuf>,
+    /// Compare the results against a saved baseline
+    #[arg(long, value_name = "JSON")]
+    baseline: Option<PathBuf>,
+    /// POST the final report as JSON to this URL (needs `--features
+    /// webhook`); a failed delivery only warns
+    #[arg(long, value_name = "URL")]
+    report_webhook: Option<String>,
+    /// Write every run of the suite, failed runs and gate failures, with
+    /// totals and host metadata, to one JSON file
+    #[arg(long, value_name = "JSON")]
+    session_report: Option<PathBuf>,
+    /// How to print the final report on stdout; json and csv move the
+    /// progress output to stderr
+    #[arg(long, value_name = "human|json|csv", default_value = "human")]
+    output_format: report::OutputFormat,
+    /// Skip the pauses between formats
+    #[arg(long)]
+    no_pause: bool,
+    /// Give each run its own library log, omt-send-<format>-<timestamp>.log
+    /// next to the default one, and scan them all at the end
+    #[arg(long, global = true)]
+    log_per_run: bool,
 
-            // High-precision frame timing
-            next_frame_time += frame_duration;
-            let now = Instant::now();
-            if next_frame_time > now {
-                thread::sleep(next_frame_time - now);
-            } else if (now - next_frame_time) > frame_duration * 2 {
-                // If we're more than 2 frames behind, reset timing
-                eprintln!("Timing drift detected, resynchronizing");
-                next_frame_time = now + frame_duration;
-            }
+    // Modes
+    /// Measure encoding with an in-process receiver and no pacing
+    #[arg(long)]
+    encode_only: bool,
+    /// Send one frame per codec to a connected receiver and report which
+    /// it accepts
+    #[arg(long)]
+    probe_receiver_formats: bool,
+    /// Frame size for --probe-receiver-formats (positive, even) [default:
+    /// 64x64]
+    #[arg(long, value_name = "WxH", value_parser = parse_resolution)]
+    codec_probe_resolution: Option<(i32, i32)>,
+    /// Send the color bars through an in-process receiver and check each bar
+    /// decodes close to the value sent (UYVY formats)
+    #[arg(long)]
+    verify_bars: bool,
+    /// Allowed error of --verify-bars in 8-bit code values [default: 10]
+    #[arg(long, value_name = "CODES")]
+    bar_tolerance: Option<f64>,
+    /// Send a single frame of the first selected format and exit non-zero
+    /// unless it was accepted
+    #[arg(long)]
+    once: bool,
+    /// Recreate senders with random formats and qualities for SECS seconds
+    #[arg(long, value_name = "SECS")]
+    soak: Option<u64>,
+    /// Seed for --soak [default: from the clock]
+    #[arg(long, value_name = "N")]
+    soak_seed: Option<u64>,
+    /// Raise the frame rate until losses exceed the threshold
+    #[arg(long)]
+    fps_ramp: bool,
+    /// Starting fps and increment for --fps-ramp [default: 5]
+    #[arg(long, value_name = "FPS")]
+    ramp_step: Option<i32>,
+    /// Seconds per --fps-ramp step [default: 3]
+    #[arg(long, value_name = "SECS")]
+    ramp_interval: Option<u32>,
+    /// Loss percentage that ends --fps-ramp [default: 1.0]
+    #[arg(long, value_name = "PCT")]
+    ramp_drop_threshold: Option<f64>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Re-send a raw dump recorded with --dump at its original frame rate
+    Replay {
+        dump: PathBuf,
+        sidecar: PathBuf,
+        /// Send frames straight from a memory mapping of the dump instead of
+        /// reading them, for dumps larger than RAM
+        #[arg(long)]
+        mmap: bool,
+    },
+    /// Receive video from SOURCE (a name or `HOST (Name)` address) and print
+    /// each frame until Ctrl-C
+    Receive {
+        source: String,
+        /// Fail if any frame's size or frame rate differs from this preset
+        #[arg(long, value_name = "PRESET")]
+        expect: Option<String>,
+    },
+    /// Write every preset's test frame with a sidecar and a hashed manifest
+    /// to DIR, without sending
+    ExportVectors { dir: PathBuf },
+    /// Print the sizes, field offsets and sample values of the FFI structs as
+    /// this build sees them, for debugging layout mismatches
+    #[command(hide = true)]
+    DumpBindings,
+}
+
+fn parse_args() -> Result<Options> {
+    options_from_args(Args::parse())
+}
+
+/// Maps parsed arguments to [`Options`], fills the rest from the
+/// environment and validates the result.
+fn options_from_args(mut args: Args) -> Result<Options> {
+    args.format = args.format.take().or(args.format_flag.take());
+    let fps_ramp = (args.fps_ramp
+        || args.ramp_step.is_some()
+        || args.ramp_interval.is_some()
+        || args.ramp_drop_threshold.is_some())
+    .then(|| {
+        let default = FpsRamp::default();
+        FpsRamp {
+            step: args.ramp_step.unwrap_or(default.step),
+            interval_secs: args.ramp_interval.unwrap_or(default.interval_secs),
+            drop_threshold_pct: args
+                .ramp_drop_threshold
+                .unwrap_or(default.drop_threshold_pct),
         }
+    });
+    let stats_interval = match (args.stats_interval, args.stats_interval_secs) {
+        (Some(frames), _) => Some(StatsInterval::Frames(frames)),
+        (None, Some(secs)) => Some(StatsInterval::Secs(secs)),
+        (None, None) => None,
+    };
+    let dump_bindings = matches!(args.command, Some(Command::DumpBindings));
+    let (replay, replay_mmap, export_vectors, receive) = match args.command {
+        Some(Command::Replay {
+            dump,
+            sidecar,
+            mmap,
+        }) => (Some((dump, sidecar)), mmap, None, None),
+        Some(Command::ExportVectors { dir }) => (None, false, Some(dir), None),
+        Some(Command::Receive { source, expect }) => (None, false, None, Some((source, expect))),
+        Some(Command::DumpBindings) | None => (None, false, None, None),
+    };
+    if args.format.is_some() && replay.is_some() {
+        bail!("replay does not take a format name");
+    }
+    if args.format.is_some() && receive.is_some() {
+        bail!("receive does not take a format name; use receive --expect <preset>");
+    }
+    let (receive, receive_expect) = match receive {
+        Some((source, expect)) => (Some(source), expect),
+        None => (None, None),
+    };
+    let mut opts = Options {
+        format_name: args.format,
+        replay,
+        replay_mmap,
+        export_vectors,
+        receive,
+        receive_expect,
+        verbosity: args.verbose,
+        codec: args.codec,
+        width: args.width,
+        height: args.height,
+        fps: args.fps,
+        duration_secs: args.duration,
+        frame_count: args.send_count,
+        deterministic: args.deterministic,
+        nv12_stride_align: args.nv12_stride_align.map(|n| n as i32),
+        nv12_stride: args.nv12_stride.map(|n| n as i32),
+        colorspace: args.colorspace,
+        quality: args.quality,
+        show_defaults: args.show_defaults,
+        dump_bindings,
+        list_presets: args.list_presets,
+        list_sources: args.list,
+        pattern: if args.color_sweep {
+            patterns::Pattern::ColorSweep
+        } else if args.flicker {
+            patterns::Pattern::Flicker
+        } else {
+            args.pattern
+        },
+        frame_pattern: args.frame_pattern,
+        image: args.image,
+        script: args.script,
+        start_timecode: args.start_timecode,
+        frame_barcode: args.frame_barcode,
+        alpha_premultiplied: args.alpha_premultiplied,
+        alpha_only: args.alpha,
+        no_pipeline: args.no_pipeline,
+        preconvert: args.preconvert,
+        drop_every: args.drop_every,
+        simulate_loss_pct: args.simulate_loss,
+        simulate_jitter: args.simulate_jitter_ms.map(Duration::from_millis),
+        deadline: args.deadline_ms.map(Duration::from_millis),
+        target_bitrate: args.target_bitrate,
+        send_timeout: args.send_timeout_ms.map(Duration::from_millis),
+        send_black_on_disconnect: args.send_black_on_disconnect,
+        allow_disconnect: args.allow_disconnect,
+        buffer_align: args.buffer_align,
+        drain_timeout: Duration::from_millis(args.drain_ms),
+        create_retries: args.create_retries,
+        dump: args.dump,
+        require_receiver: args.require_receiver,
+        target_receiver: args.target_receiver,
+        min_receivers: args.count_receivers_min,
+        list_connections: args.list_connections,
+        port: args.port,
+        address: args.address,
+        interface_list: args.interface_list,
+        interface_list_json: args.interface_list_json,
+        stats_interval,
+        banner_json: args.banner_json,
+        validate_frame: args.validate_frame,
+        strict_stride: args.strict_stride,
+        max_codec_time_ms: args.max_codec_time_ms,
+        max_drops: args.max_drops,
+        max_drop_rate: args.max_drop_rate,
+        frame_history: args.frame_history,
+        save_baseline: args.save_baseline,
+        baseline: args.baseline,
+        report_webhook: args.report_webhook,
+        session_report: args.session_report,
+        no_pause: args.no_pause,
+        log_stamp: args.log_per_run.then(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        }),
+        output_format: args.output_format,
+        encode_only: args.encode_only,
+        probe_receiver_formats: args.probe_receiver_formats,
+        probe_resolution: args
+            .codec_probe_resolution
+            .unwrap_or(DEFAULT_PROBE_RESOLUTION),
+        once: args.once,
+        verify_bars: args.verify_bars,
+        bar_tolerance: args.bar_tolerance,
+        hash_frames: args.hash_frames,
+        warn_status_codes: args.warn_on_status_code,
+        soak_secs: args.soak,
+        soak_seed: args.soak_seed,
+        fps_ramp,
+        ..Options::default()
+    };
+    apply_env(&mut opts)?;
 
-        // Final statistics
-        let mut vstats: OMTStatistics = mem::zeroed();
-        omt_send_getvideostatistics(sender, &mut vstats as *mut OMTStatistics);
-        println!("\n=== Final Statistics for {} ===", format.name);
-        println!("Total bytes sent: {}", vstats.BytesSent);
-        println!("Total frames sent: {}", vstats.Frames);
-        println!("Frames dropped: {}", vstats.FramesDropped);
-        println!(
-            "Average bitrate: {:.2} Mbps",
-            (vstats.BytesSent as f64 * 8.0) / (duration_secs as f64 * 1_000_000.0)
+    // Checked after the environment fallback so both sources are validated
+    if let Some(port) = opts.port.filter(|&p| p < 1024) {
+        bail!("--port must be in the range 1024-65535, got {}", port);
+    }
+    if opts.duration_secs == Some(0) {
+        bail!("--duration must be positive");
+    }
+    // Evenness depends on the codec and is checked by VideoFormat::from_parts
+    for (flag, size) in [("--width", opts.width), ("--height", opts.height)] {
+        if let Some(size) = size.filter(|&n| n <= 0) {
+            bail!("{} must be positive, got {}", flag, size);
+        }
+    }
+    if opts.min_receivers.is_some() && opts.target_receiver.is_some() {
+        bail!("--count-receivers-min and --target-receiver cannot be combined");
+    }
+    let sources = [
+        opts.image.is_some(),
+        opts.script.is_some(),
+        opts.pattern != patterns::Pattern::default(),
+    ];
+    if sources.iter().filter(|&&set| set).count() > 1 {
+        bail!("only one of --image, --script and --pattern can be used");
+    }
+    if opts.replay.is_some() && opts.dump.is_some() {
+        bail!("--dump cannot be used with replay");
+    }
+    if opts.export_vectors.is_some() && opts.dump.is_some() {
+        bail!("--dump cannot be used with export-vectors");
+    }
+    if let Some(ramp) = &opts.fps_ramp {
+        if ramp.step <= 0 || ramp.interval_secs == 0 {
+            bail!("--ramp-step and --ramp-interval must be positive");
+        }
+    }
+    if opts.min_receivers == Some(0) {
+        bail!("--count-receivers-min must be at least 1");
+    }
+    match opts.stats_interval {
+        Some(StatsInterval::Frames(0)) => bail!("--stats-interval must be at least 1 frame"),
+        Some(StatsInterval::Secs(secs)) if !(secs.is_finite() && secs > 0.0) => {
+            bail!("--stats-interval-secs must be positive, got {}", secs)
+        }
+        _ => {}
+    }
+    if !opts.buffer_align.is_power_of_two() || opts.buffer_align > 4096 {
+        bail!(
+            "--buffer-align must be a power of two up to 4096, got {}",
+            opts.buffer_align
+        );
+    }
+    if let Some(align) = opts
+        .nv12_stride_align
+        .filter(|&n| !(n as u32).is_power_of_two() || n > 4096)
+    {
+        bail!(
+            "--nv12-stride-align must be a power of two up to 4096, got {}",
+            align
+        );
+    }
+    if let Some(n) = opts.drop_every.filter(|&n| n < 2) {
+        bail!("--drop-every must be at least 2, got {}", n);
+    }
+    if let Some(pct) = opts
+        .simulate_loss_pct
+        .filter(|&p| !(0.0..100.0).contains(&p))
+    {
+        bail!(
+            "--simulate-loss must be at least 0 and below 100, got {}",
+            pct
         );
+    }
+    if let Some(n) = opts.max_drops.filter(|&n| n < 0) {
+        bail!("--max-drops must not be negative, got {}", n);
+    }
+    if let Some(pct) = opts.max_drop_rate.filter(|&p| !(0.0..=100.0).contains(&p)) {
+        bail!("--max-drop-rate must be between 0 and 100, got {}", pct);
+    }
+    if opts.report_webhook.is_some() && !cfg!(feature = "webhook") {
+        bail!("--report-webhook requires building with `--features webhook`");
+    }
+    if let Some(mbps) = opts.target_bitrate.filter(|&m| m.is_nan() || m <= 0.0) {
+        bail!("--target-bitrate must be positive, got {}", mbps);
+    }
+    if opts.soak_secs == Some(0) {
+        bail!("--soak duration must be positive");
+    }
+    if let Some(codes) = opts.bar_tolerance.filter(|&c| c.is_nan() || c < 0.0) {
+        bail!("--bar-tolerance must not be negative, got {}", codes);
+    }
+    if opts.send_timeout == Some(Duration::ZERO) {
+        bail!("--send-timeout-ms must be positive");
+    }
+    if opts.frame_count == Some(0) {
+        bail!("--send-count must be positive");
+    }
+    if opts.deterministic {
+        if opts.frame_count.is_none() {
+            bail!("--deterministic needs --send-count");
+        }
+        // These pace the run by the clock or change what is sent when
+        // receivers come and go
+        let timed = [
+            ("--target-bitrate", opts.target_bitrate.is_some()),
+            ("--deadline-ms", opts.deadline.is_some()),
+            ("--soak", opts.soak_secs.is_some()),
+            ("--fps-ramp", opts.fps_ramp.is_some()),
+            ("--send-black-on-disconnect", opts.send_black_on_disconnect),
+        ];
+        if let Some((flag, _)) = timed.iter().find(|(_, set)| *set) {
+            bail!("{} can't be combined with --deterministic", flag);
+        }
+        opts.no_pause = true;
+    }
+    Ok(opts)
+}
+
+/// Points stdout at stderr until dropped, so with `--output-format json|csv`
+/// the progress output stays on the terminal and only the report printed
+/// after the drop reaches stdout.
+struct StdoutToStderr {
+    saved: i32,
+}
+
+impl StdoutToStderr {
+    fn new() -> Result<Self> {
+        std::io::stdout().flush()?;
+        let saved = unsafe { libc::dup(libc::STDOUT_FILENO) };
+        if saved < 0 || unsafe { libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) } < 0 {
+            bail!(
+                "failed to redirect stdout: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(StdoutToStderr { saved })
+    }
+}
+
+impl Drop for StdoutToStderr {
+    fn drop(&mut self) {
+        std::io::stdout().flush().ok();
+        unsafe {
+            libc::dup2(self.saved, libc::STDOUT_FILENO);
+            libc::close(self.saved);
+        }
+    }
+}
+
+/// Applies `--codec`/`--width`/`--height`/`--fps` to a preset and renames it
+/// to match, e.g. `NV12_1920x1080_60fps`.
+fn override_format(preset: VideoFormat, opts: &Options) -> Result<VideoFormat> {
+    let fps = opts.fps.unwrap_or(FrameRate {
+        n: preset.fps_n,
+        d: preset.fps_d,
+    });
+    let format = VideoFormat::from_parts(
+        opts.codec.unwrap_or(preset.codec),
+        opts.width.unwrap_or(preset.width),
+        opts.height.unwrap_or(preset.height),
+        fps.n,
+        fps.d,
+    )?;
+    Ok(VideoFormat {
+        stride_align: preset.stride_align,
+        stride_override: preset.stride_override,
+        colorspace: preset.colorspace,
+        ..format
+    })
+}
+
+/// Prints the per-codec default quality table.
+fn show_defaults() {
+    println!("Default quality per codec (used when --quality is not given):");
+    for (codec, quality, why) in DEFAULT_QUALITIES {
         println!(
-            "Success rate: {:.2}%",
-            (vstats.Frames as f64 / frames_to_send as f64) * 100.0
+            "  {:<5} {:<7} {}",
+            codec_name(codec),
+            quality_name(quality),
+            why
         );
+    }
+}
 
-        omt_send_destroy(sender);
-        println!("Test completed successfully\n");
+/// Returns a failure message if the run exceeded the codec time budget.
+fn check_codec_time_budget(result: &TestResult, budget_ms: i64) -> Option<String> {
+    let name = &result.name;
+    let peak = result.codec_time_peak_ms;
+    let mean = result.codec_time_mean_ms;
+    println!(
+        "Codec time budget for {}: peak {}ms / mean {:.1}ms vs budget {}ms",
+        name, peak, mean, budget_ms
+    );
+    if peak > budget_ms || mean > budget_ms as f64 {
+        Some(format!(
+            "{}: codec time peak {}ms / mean {:.1}ms exceeds budget {}ms",
+            name, peak, mean, budget_ms
+        ))
+    } else {
+        None
     }
+}
 
-    Ok(())
+/// Returns a failure message if the run dropped more frames than allowed by
+/// `--max-drops` or `--max-drop-rate`.
+fn check_drop_threshold(result: &TestResult, opts: &Options) -> Option<String> {
+    let name = &result.name;
+    let dropped = result.frames_dropped;
+    let rate = dropped as f64 * 100.0 / result.frames_sent.max(1) as f64;
+    let mut exceeded = Vec::new();
+    if let Some(max) = opts.max_drops {
+        println!("Dropped frames for {}: {} vs max {}", name, dropped, max);
+        if dropped > max {
+            exceeded.push(format!("{} dropped frames exceeds max {}", dropped, max));
+        }
+    }
+    if let Some(max) = opts.max_drop_rate {
+        println!("Drop rate for {}: {:.2}% vs max {}%", name, rate, max);
+        if rate > max {
+            exceeded.push(format!("drop rate {:.2}% exceeds max {}%", rate, max));
+        }
+    }
+    if exceeded.is_empty() {
+        None
+    } else {
+        Some(format!("{}: {}", name, exceeded.join(", ")))
+    }
+}
+
+/// Returns a failure message if the run stopped before attempting every
+/// requested frame. A disconnect passes with `--allow-disconnect`, and a
+/// Ctrl-C always does since it was asked for.
+fn check_outcome(result: &TestResult, opts: &Options) -> Option<String> {
+    match result.outcome {
+        RunOutcome::Completed | RunOutcome::Interrupted { .. } => None,
+        RunOutcome::Disconnected { .. } if opts.allow_disconnect => None,
+        outcome => Some(format!(
+            "{}: {} ({} of {} frames attempted)",
+            result.name,
+            outcome,
+            outcome.frames_attempted(result.frames_requested),
+            result.frames_requested
+        )),
+    }
+}
+
+/// Everything that makes the process exit non-zero: the gate failures, then
+/// the runs that ended in an error instead of a result, such as a receiver
+/// that never connected under `--require-receiver` or `--target-receiver`.
+fn exit_failures(gate_failures: &[String], failed_runs: &[report::FailedRun]) -> Vec<String> {
+    gate_failures
+        .iter()
+        .cloned()
+        .chain(
+            failed_runs
+                .iter()
+                .map(|run| format!("{}: {}", run.name, run.error)),
+        )
+        .collect()
+}
+
+/// Runs the per-run pass/fail gates and returns their failures.
+fn check_gates(result: &TestResult, opts: &Options) -> Vec<String> {
+    let mut failures: Vec<String> = check_outcome(result, opts).into_iter().collect();
+    if let Some(budget) = opts.max_codec_time_ms {
+        failures.extend(check_codec_time_budget(result, budget));
+    }
+    failures.extend(check_drop_threshold(result, opts));
+    failures
 }
 
 fn main() -> Result<()> {
+    sender::install_crash_handler();
     // Parse command line arguments
-    let args: Vec<String> = std::env::args().collect();
-    let test_format = args.get(1).map(|s| s.as_str());
-
-    // Test configurations
-    let formats = vec![
-        // Current stable format
-        VideoFormat {
-            codec: OMTCodec_OMTCodec_UYVY,
-            width: 1280,
-            height: 720,
-            fps_n: 30,
-            fps_d: 1,
-            name: "UYVY_720p30",
-        },
-        // Test higher resolution UYVY
-        VideoFormat {
-            codec: OMTCodec_OMTCodec_UYVY,
-            width: 1920,
-            height: 1080,
-            fps_n: 30,
-            fps_d: 1,
-            name: "UYVY_1080p30",
-        },
-        // Test BGRA 720p
-        VideoFormat {
-            codec: OMTCodec_OMTCodec_BGRA,
-            width: 1280,
-            height: 720,
-            fps_n: 30,
-            fps_d: 1,
-            name: "BGRA_720p30",
-        },
-        // Test BGRA 1080p
-        VideoFormat {
-            codec: OMTCodec_OMTCodec_BGRA,
-            width: 1920,
-            height: 1080,
-            fps_n: 30,
-            fps_d: 1,
-            name: "BGRA_1080p30",
-        },
-        // Test NV12 format
-        VideoFormat {
-            codec: OMTCodec_OMTCodec_NV12,
-            width: 1280,
-            height: 720,
-            fps_n: 30,
-            fps_d: 1,
-            name: "NV12_720p30",
-        },
-    ];
+    let mut opts = parse_args()?;
+    if opts.show_defaults {
+        show_defaults();
+        return Ok(());
+    }
+    if opts.dump_bindings {
+        layout::dump_bindings();
+        return Ok(());
+    }
+    if opts.list_presets {
+        presets::print_list();
+        return Ok(());
+    }
+    if opts.interface_list || opts.interface_list_json {
+        return net::print_interfaces(opts.interface_list_json);
+    }
+    if opts.list_sources {
+        discovery::print_sources(discovery::DISCOVERY_TIMEOUT);
+        return Ok(());
+    }
+    let redirect = (opts.output_format != report::OutputFormat::Human)
+        .then(StdoutToStderr::new)
+        .transpose()?;
+    prepare_log_file(&mut opts);
+    // Soak and keepalive runs stop cleanly on Ctrl-C and still report
+    if opts.soak_secs.is_some() || opts.send_black_on_disconnect || opts.receive.is_some() {
+        ctrlc::set_handler(|| STOP.store(true, Ordering::Relaxed))
+            .context("failed to install the Ctrl-C handler")?;
+        opts.stop = Some(&STOP);
+    }
+    install_stats_signal();
+    opts.stats_request = Some(&STATS_REQUEST);
+    let opts = opts;
+    let test_format = opts.format_name.as_deref();
+
+    apply_network_settings(&opts)?;
+
+    if let Some((dump, sidecar)) = &opts.replay {
+        run_replay(dump, sidecar, &opts)?;
+        report_log_errors(&opts, 10);
+        return Ok(());
+    }
+    if let Some(source) = &opts.receive {
+        let expect = match opts.receive_expect.as_deref() {
+            Some(name) => match presets::find(name) {
+                Some(format) => Some(format),
+                None => bail!("unknown preset {} for --expect; see --list-presets", name),
+            },
+            None => None,
+        };
+        receive::run_receive(source, expect.as_ref(), &opts)?;
+        return Ok(());
+    }
+
+    #[cfg(feature = "script")]
+    let script = opts
+        .script
+        .as_deref()
+        .map(script::ScriptPattern::load)
+        .transpose()?;
+    #[cfg(feature = "script")]
+    let renderer = script.as_ref().map(|s| s as &dyn FrameRenderer);
+    #[cfg(not(feature = "script"))]
+    let renderer: Option<&dyn FrameRenderer> = match opts.script {
+        Some(_) => bail!("--script requires building with `--features script`"),
+        None => None,
+    };
+    let still = opts
+        .image
+        .as_deref()
+        .map(still::StillImage::load)
+        .transpose()?;
+    let pattern = opts.pattern.renderer();
+    let renderer = still
+        .as_ref()
+        .map(|s| s as &dyn FrameRenderer)
+        .or(pattern.as_deref())
+        .or(renderer);
+    let duration_secs = opts.duration_secs.unwrap_or(5);
 
     println!("OMT Send Test Suite");
     println!("==================");
-    println!("Usage: cargo run [format_name]");
+    println!("Usage: cargo run -- [format_name] [options] (see --help)");
+    let names: Vec<&str> = presets::SUITE.iter().map(|f| f.name).collect();
     println!(
-        "Available formats: UYVY_720p30, UYVY_1080p30, BGRA_720p30, BGRA_1080p30, NV12_720p30\n"
+        "Available formats: {} (--list-presets for more)\n",
+        names.join(", ")
     );
 
     // Filter formats based on command line argument
-    let formats_to_test: Vec<VideoFormat> = if let Some(name) = test_format {
-        formats.into_iter().filter(|f| f.name == name).collect()
-    } else {
-        formats
+    presets::check_all()?;
+    if let Some((video, audio)) = test_format.and_then(presets::find_av) {
+        run_av_test(video, audio, duration_secs, &opts)?;
+        report_log_errors(&opts, 10);
+        return Ok(());
+    }
+    if let Some(audio) = test_format.and_then(audio::find) {
+        run_audio_test(audio, duration_secs, &opts)?;
+        report_log_errors(&opts, 10);
+        return Ok(());
+    }
+    let mut formats_to_test: Vec<VideoFormat> = match test_format {
+        Some(name) => presets::find(name).into_iter().collect(),
+        None => presets::SUITE.to_vec(),
     };
+    if opts.codec.is_some() || opts.width.is_some() || opts.height.is_some() || opts.fps.is_some() {
+        let mut seen = std::collections::HashSet::new();
+        for format in &mut formats_to_test {
+            *format = override_format(*format, &opts)?;
+        }
+        // Overriding e.g. the codec can turn several presets into the same format
+        formats_to_test.retain(|f| seen.insert(f.name));
+    }
+    if let Some(align) = opts.nv12_stride_align {
+        for format in &mut formats_to_test {
+            format.stride_align = align;
+        }
+    }
+    if let Some(stride) = opts.nv12_stride {
+        if !formats_to_test
+            .iter()
+            .any(|f| f.codec == OMTCodec_OMTCodec_NV12)
+        {
+            bail!("--nv12-stride needs an NV12 format, e.g. NV12_720p30 or --codec NV12");
+        }
+        for format in &mut formats_to_test {
+            format.stride_override = Some(stride);
+        }
+    }
+    if let Some(colorspace) = opts.colorspace {
+        for format in &mut formats_to_test {
+            format.colorspace = Some(colorspace);
+        }
+    }
+    for format in &formats_to_test {
+        format.check_size()?;
+    }
 
     if formats_to_test.is_empty() {
-        eprintln!("Error: Unknown format specified");
+        let names: Vec<&str> = presets::SUITE
+            .iter()
+            .chain(presets::EXTENDED)
+            .map(|f| f.name)
+            .chain(audio::AUDIO_FORMATS.iter().map(|a| a.name))
+            .chain(presets::AV_PRESETS.iter().map(|(name, _, _)| *name))
+            .collect();
+        bail!(
+            "unknown format {}; valid names are {}",
+            test_format.unwrap_or_default(),
+            names.join(", ")
+        );
+    }
+    if opts.alpha_only
+        && !formats_to_test
+            .iter()
+            .any(|f| f.codec == OMTCodec_OMTCodec_BGRA)
+    {
+        bail!("--alpha needs a BGRA format, e.g. BGRA_720p30 or --codec BGRA");
+    }
+    if let Some(dir) = &opts.export_vectors {
+        vectors::export_vectors(&formats_to_test, dir)?;
+        return Ok(());
+    }
+    if opts.dump.is_some() && formats_to_test.len() > 1 {
+        bail!("--dump records a single run; select one format, e.g. UYVY_720p30");
+    }
+
+    if opts.encode_only {
+        let mut failures = Vec::new();
+        for format in formats_to_test {
+            if let Err(e) = run_encode_benchmark(format, &opts) {
+                eprintln!("Encode benchmark failed for {}: {}", format.name, e);
+                failures.push(format.name);
+            }
+        }
+        if !failures.is_empty() {
+            bail!("encode benchmark failed for {}", failures.join(", "));
+        }
+        return Ok(());
+    }
+
+    if opts.verify_bars {
+        let mut failures = Vec::new();
+        for format in formats_to_test {
+            if roundtrip::default_tolerance(format.codec).is_none() {
+                println!(
+                    "Skipping {}: no color bar tolerance for its codec",
+                    format.name
+                );
+                continue;
+            }
+            if let Err(e) = run_verify_bars(format, &opts) {
+                eprintln!("Color bar round trip failed for {}: {:#}", format.name, e);
+                failures.push(format.name);
+            }
+        }
+        if !failures.is_empty() {
+            bail!("color bars did not round-trip for {}", failures.join(", "));
+        }
         return Ok(());
     }
 
+    if opts.probe_receiver_formats {
+        run_probe_receiver_formats(&formats_to_test, &opts)?;
+        return Ok(());
+    }
+
+    if opts.once {
+        if formats_to_test.len() > 1 {
+            println!(
+                "--once sends {} only; name a format to check another",
+                formats_to_test[0].name
+            );
+        }
+        return run_once(formats_to_test[0], &opts);
+    }
+
+    if let Some(secs) = opts.soak_secs {
+        let seed = opts.soak_seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(1, |d| d.as_nanos() as u64)
+        });
+        let report = run_soak(
+            &formats_to_test,
+            Duration::from_secs(secs),
+            seed,
+            &opts,
+            &STOP,
+        )?;
+        report_log_errors(&opts, 10);
+        if !report.errors.is_empty() {
+            bail!("{} soak transition(s) failed", report.errors.len());
+        }
+        return Ok(());
+    }
+
+    if let Some(ramp) = opts.fps_ramp {
+        let mut failed = 0;
+        for format in formats_to_test {
+            if let Err(e) = run_fps_ramp(format, ramp, &opts) {
+                eprintln!("FPS ramp failed for {}: {}", format.name, e);
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            bail!("{} fps ramp(s) failed", failed);
+        }
+        return Ok(());
+    }
+
+    // Load the baseline up front so a bad path fails before the run
+    let baseline = opts
+        .baseline
+        .as_deref()
+        .map(report::TestReport::load)
+        .transpose()?;
+
     // Run tests
+    let mut gate_failures: Vec<String> = Vec::new();
+    let mut test_report = report::TestReport::new();
+    let mut session = report::SessionReport::begin();
+    let mut failed_runs: Vec<report::FailedRun> = Vec::new();
     for format in formats_to_test {
-        if let Err(e) = run_send_test(format, 5, false) {
-            eprintln!("Test failed for {}: {}", format.name, e);
-            // Continue with next test instead of stopping
-            thread::sleep(Duration::from_secs(2));
-            continue;
+        // An image with alpha goes out with the alpha flag, like --alpha
+        let image_alpha = still.as_ref().is_some_and(|s| s.has_alpha());
+        let alpha_only = (opts.alpha_only || image_alpha) && format.codec == OMTCodec_OMTCodec_BGRA;
+        if alpha_only && !opts.alpha_only {
+            println!(
+                "The image has alpha; sending {} with the alpha flag",
+                format.name
+            );
+        }
+        match run_send_test(format, duration_secs, alpha_only, renderer, &opts) {
+            Ok(result) => {
+                gate_failures.extend(check_gates(&result, &opts));
+                test_report.results.push(result);
+            }
+            Err(e) => {
+                eprintln!("Test failed for {}: {}", format.name, e);
+                failed_runs.push(report::FailedRun {
+                    name: format.name.to_string(),
+                    error: format!("{:#}", e),
+                });
+                // Continue with next test instead of stopping
+                if !opts.no_pause {
+                    thread::sleep(Duration::from_secs(2));
+                }
+                continue;
+            }
         }
 
-        // Test with alpha flag for BGRA
-        if format.codec == OMTCodec_OMTCodec_BGRA {
+        // Test with alpha flag for BGRA; a dump holds only the first run
+        if format.codec == OMTCodec_OMTCodec_BGRA && opts.dump.is_none() && !alpha_only {
             println!("\nTesting {} with alpha flag...", format.name);
-            if let Err(e) = run_send_test(format, 5, true) {
-                eprintln!("Test with alpha failed for {}: {}", format.name, e);
+            match run_send_test(format, duration_secs, true, renderer, &opts) {
+                Ok(result) => {
+                    gate_failures.extend(check_gates(&result, &opts));
+                    test_report.results.push(result);
+                }
+                Err(e) => {
+                    eprintln!("Test with alpha failed for {}: {}", format.name, e);
+                    failed_runs.push(report::FailedRun {
+                        name: format!("{} (alpha)", format.name),
+                        error: format!("{:#}", e),
+                    });
+                }
             }
         }
 
-        thread::sleep(Duration::from_secs(1)); // Brief pause between tests
+        if !opts.no_pause {
+            thread::sleep(Duration::from_secs(1)); // Brief pause between tests
+        }
     }
 
     println!("\nAll tests completed!");
 
     // Check log file for errors
-    println!("\nChecking log file for errors...");
-    if let Ok(log_content) = std::fs::read_to_string("/tmp/omt-send.log") {
-        let error_lines: Vec<&str> = log_content
-            .lines()
-            .filter(|line| line.contains("ERROR") || line.contains("WARN"))
-            .collect();
+    report_log_errors(&opts, 10);
 
-        if !error_lines.is_empty() {
-            println!("Found {} warnings/errors in log:", error_lines.len());
-            for line in error_lines.iter().take(10) {
-                println!("  {}", line);
-            }
-        } else {
-            println!("No errors found in log file");
+    if let Some(path) = &opts.save_baseline {
+        test_report.save(path)?;
+        println!("\nSaved baseline to {}", path.display());
+    }
+    if let Some(baseline) = &baseline {
+        println!("\nComparing against baseline:");
+        let regressions = test_report.regressions(baseline, &report::Tolerance::default());
+        if regressions.is_empty() {
+            println!("No regressions");
+        }
+        gate_failures.extend(regressions);
+    }
+
+    // The session report takes the failed runs, so collect them first
+    let failures = exit_failures(&gate_failures, &failed_runs);
+    let errored = failed_runs.len();
+    if let Some(path) = &opts.session_report {
+        session.finish(&test_report, failed_runs, &gate_failures);
+        session.save(path)?;
+        println!("\nSaved session report to {}", path.display());
+    }
+
+    drop(redirect);
+    if opts.deterministic {
+        test_report.deterministic().print(opts.output_format)?;
+    } else {
+        test_report.print(opts.output_format)?;
+    }
+    #[cfg(feature = "webhook")]
+    if let Some(url) = &opts.report_webhook {
+        // Delivery is best effort; the run's outcome doesn't depend on it
+        match test_report.post(url) {
+            Ok(()) => eprintln!("\nPosted the report to {}", url),
+            Err(e) => eprintln!("\nWarning: {:#}", e),
+        }
+    }
+
+    if !failures.is_empty() {
+        eprintln!("\nFAILED:");
+        for failure in &failures {
+            eprintln!("  {}", failure);
         }
+        bail!(
+            "{} run(s) ended with an error, {} failed pass/fail gates",
+            errored,
+            gate_failures.len()
+        );
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(outcome: RunOutcome) -> TestResult {
+        TestResult {
+            name: "UYVY_720p30".to_string(),
+            bytes_sent: 0,
+            frames_sent: 0,
+            frames_dropped: 0,
+            bitrate_mbps: 0.0,
+            success_rate_pct: 0.0,
+            codec_time_peak_ms: 0,
+            codec_time_mean_ms: 0.0,
+            status_codes: Default::default(),
+            outcome,
+            frames_requested: 300,
+            frames_skipped: 0,
+            stream_digest: None,
+        }
+    }
+
+    #[test]
+    fn outcome_tells_completed_from_stopped_early() {
+        let opts = Options::default();
+        assert_eq!(check_outcome(&result(RunOutcome::Completed), &opts), None);
+
+        let disconnected = result(RunOutcome::Disconnected { frame: 149 });
+        assert_eq!(
+            check_outcome(&disconnected, &opts).as_deref(),
+            Some("UYVY_720p30: stopped early, receiver disconnected at frame 149 (150 of 300 frames attempted)")
+        );
+        let allow = Options {
+            allow_disconnect: true,
+            ..Options::default()
+        };
+        assert_eq!(check_outcome(&disconnected, &allow), None);
+
+        // Errors fail even with --allow-disconnect
+        for outcome in [
+            RunOutcome::Failed { frame: 10, rc: -1 },
+            RunOutcome::Hung { frame: 10 },
+        ] {
+            let failure = check_outcome(&result(outcome), &allow).unwrap();
+            assert!(failure.contains("stopped early, omt_send"), "{}", failure);
+            assert!(
+                failure.ends_with("(11 of 300 frames attempted)"),
+                "{}",
+                failure
+            );
+        }
+    }
+
+    fn options(args: &[&str]) -> Result<Options> {
+        let args = Args::try_parse_from(["omt-send-test-rs"].iter().chain(args))?;
+        options_from_args(args)
+    }
+
+    #[test]
+    fn unknown_codecs_list_the_valid_ones() {
+        for name in ["NV21", "nv21", "YUY2", "h264"] {
+            let err = parse_codec(name).unwrap_err().to_string();
+            assert!(err.contains("expected one of UYVY, BGRA, NV12"), "{}", err);
+            assert!(Args::try_parse_from(["omt-send-test-rs", "--codec", name]).is_err());
+        }
+        assert_eq!(parse_codec("nv12").unwrap(), OMTCodec_OMTCodec_NV12);
+        let opts = options(&["--codec", "bgra"]).unwrap();
+        assert_eq!(opts.codec, Some(OMTCodec_OMTCodec_BGRA));
+    }
+
+    #[test]
+    fn zero_duration_and_send_count_are_rejected() {
+        let err = options(&["--duration", "0"]).unwrap_err();
+        assert_eq!(err.to_string(), "--duration must be positive");
+        let err = options(&["--send-count", "0"]).unwrap_err();
+        assert_eq!(err.to_string(), "--send-count must be positive");
+        let err = options(&["--soak", "0"]).unwrap_err();
+        assert_eq!(err.to_string(), "--soak duration must be positive");
+
+        let opts = options(&["--duration", "1", "--send-count", "1"]).unwrap();
+        assert_eq!((opts.duration_secs, opts.frame_count), (Some(1), Some(1)));
+    }
+
+    #[test]
+    fn fps_above_the_ramp_ceiling_is_accepted() {
+        let opts = options(&["--codec", "UYVY", "--fps", "300/1"]).unwrap();
+        let fps = opts.fps.unwrap();
+        assert_eq!((fps.n, fps.d), (300, 1));
+        assert!(options(&["--fps", "0"]).is_err());
+        assert!(options(&["--fps", "60/0"]).is_err());
+    }
+
+    #[test]
+    fn errored_runs_fail_the_exit_status() {
+        assert!(exit_failures(&[], &[]).is_empty());
+        let error = check_receiver_count(1, 3, Duration::from_secs(5)).unwrap_err();
+        let failed = [report::FailedRun {
+            name: "UYVY_720p30".to_string(),
+            error: format!("{:#}", error),
+        }];
+        assert_eq!(
+            exit_failures(&[], &failed),
+            ["UYVY_720p30: only 1 of 3 required receivers connected within 5.0s"]
+        );
+        let gates = ["BGRA_720p30: 3 dropped frames exceeds max 0".to_string()];
+        assert_eq!(exit_failures(&gates, &failed).len(), 2);
+    }
+}
@@ -0,0 +1,47 @@
+// ネットワークインターフェースの列挙（--interface-list）
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// One address of a network interface.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfaceAddress {
+    pub name: String,
+    pub address: String,
+    pub ipv6: bool,
+    pub loopback: bool,
+}
+
+/// Lists the addresses of every network interface, sorted by interface name.
+pub fn interface_addresses() -> Result<Vec<InterfaceAddress>> {
+    let mut addresses: Vec<InterfaceAddress> = if_addrs::get_if_addrs()
+        .context("failed to enumerate network interfaces")?
+        .into_iter()
+        .map(|iface| InterfaceAddress {
+            address: iface.ip().to_string(),
+            ipv6: iface.ip().is_ipv6(),
+            loopback: iface.is_loopback(),
+            name: iface.name,
+        })
+        .collect();
+    addresses.sort_by(|a, b| (&a.name, a.ipv6).cmp(&(&b.name, b.ipv6)));
+    Ok(addresses)
+}
+
+/// Prints the interface list as a table, or as a JSON array.
+pub fn print_interfaces(json: bool) -> Result<()> {
+    let addresses = interface_addresses()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&addresses)?);
+        return Ok(());
+    }
+    println!("Network interfaces:");
+    for a in &addresses {
+        println!(
+            "  {:<12} {}{}",
+            a.name,
+            a.address,
+            if a.loopback { " (loopback)" } else { "" }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,105 @@
+// フレームバッファへの文字の焼き込み（数字と区切り記号のみ）
+use crate::bindings::*;
+use crate::VideoFormat;
+
+const GLYPH_W: usize = 3;
+const GLYPH_H: usize = 5;
+
+/// 3x5 bitmaps, one row per entry, most significant of the low 3 bits is the
+/// leftmost pixel.
+fn glyph(c: char) -> [u8; GLYPH_H] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        ';' => [0b000, 0b010, 0b000, 0b010, 0b100],
+        _ => [0; GLYPH_H],
+    }
+}
+
+/// Fills a rectangle with white or black, clipped to the frame.
+pub fn fill_rect(
+    buf: &mut [u8],
+    format: &VideoFormat,
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+    white: bool,
+) {
+    let (width, height) = (format.width as usize, format.height as usize);
+    let stride = format.stride() as usize;
+    let luma = if white { 235 } else { 16 };
+    let rgb = if white { 255 } else { 0 };
+
+    for py in y..(y + h).min(height) {
+        for px in x..(x + w).min(width) {
+            match format.codec {
+                c if c == OMTCodec_OMTCodec_UYVY => {
+                    let pair = py * stride + (px / 2) * 4;
+                    buf[pair] = 128; // U
+                    buf[pair + 2] = 128; // V
+                    buf[pair + 1 + (px % 2) * 2] = luma;
+                }
+                c if c == OMTCodec_OMTCodec_NV12 => {
+                    buf[py * stride + px] = luma;
+                    let uv = stride * height + (py / 2) * stride + (px / 2) * 2;
+                    buf[uv] = 128;
+                    buf[uv + 1] = 128;
+                }
+                _ => {
+                    let i = py * stride + px * 4;
+                    buf[i..i + 4].copy_from_slice(&[rgb, rgb, rgb, 255]);
+                }
+            }
+        }
+    }
+}
+
+/// Width in pixels of `text` drawn at `scale`.
+pub fn text_width(text: &str, scale: usize) -> usize {
+    text.chars().count() * (GLYPH_W + 1) * scale
+}
+
+/// Draws `text` in white on a black box with its top-left corner at (x, y).
+/// Each glyph pixel becomes a `scale`x`scale` block.
+pub fn draw_text(
+    buf: &mut [u8],
+    format: &VideoFormat,
+    x: usize,
+    y: usize,
+    scale: usize,
+    text: &str,
+) {
+    let pad = scale;
+    fill_rect(
+        buf,
+        format,
+        x,
+        y,
+        text_width(text, scale) + pad,
+        GLYPH_H * scale + pad * 2,
+        false,
+    );
+
+    for (i, c) in text.chars().enumerate() {
+        let gx = x + pad + i * (GLYPH_W + 1) * scale;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for col in 0..GLYPH_W {
+                if bits & (0b100 >> col) != 0 {
+                    let px = gx + col * scale;
+                    let py = y + pad + row * scale;
+                    fill_rect(buf, format, px, py, scale, scale, true);
+                }
+            }
+        }
+    }
+}
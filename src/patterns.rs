@@ -0,0 +1,305 @@
+// 組み込みのテストパターン（--pattern）
+use anyhow::{bail, Result};
+use image::RgbaImage;
+
+use crate::rng::XorShift;
+use crate::{overlay, FrameRenderer, VideoFormat};
+
+/// Built-in pattern selected with `--pattern`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Pattern {
+    /// The per-codec static frame from `VideoFormat::create_test_frame`
+    #[default]
+    Bars,
+    Noise,
+    ChromaEdge,
+    ColorSweep,
+    Flicker,
+    FlickerColor,
+}
+
+impl std::str::FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "bars" => Ok(Pattern::Bars),
+            "noise" => Ok(Pattern::Noise),
+            "chroma-edge" => Ok(Pattern::ChromaEdge),
+            "color-sweep" => Ok(Pattern::ColorSweep),
+            "flicker" => Ok(Pattern::Flicker),
+            "flicker-color" => Ok(Pattern::FlickerColor),
+            _ => bail!(
+                "unknown pattern {}, expected bars, noise, chroma-edge, color-sweep, flicker or flicker-color",
+                s
+            ),
+        }
+    }
+}
+
+impl Pattern {
+    /// Renderer for the pattern; `None` for bars, which each codec
+    /// generates directly.
+    pub fn renderer(self) -> Option<Box<dyn FrameRenderer>> {
+        match self {
+            Pattern::Bars => None,
+            Pattern::Noise => Some(Box::new(NoisePattern { seed: 1 })),
+            Pattern::ChromaEdge => Some(Box::new(ChromaEdgePattern)),
+            Pattern::ColorSweep => Some(Box::new(ColorSweepPattern)),
+            Pattern::Flicker => Some(Box::new(FlickerPattern {
+                name: "flicker",
+                fields: [[0, 0, 0], [255, 255, 255]],
+            })),
+            Pattern::FlickerColor => Some(Box::new(FlickerPattern {
+                name: "flicker-color",
+                fields: [[255, 0, 255], [0, 255, 0]],
+            })),
+        }
+    }
+}
+
+/// How the built-in bars change from frame to frame (`--frame-pattern`), so
+/// a receiver showing a frozen stream can be told from a live one. Drawn in
+/// the codec's own format over the bars, so unlike the renderers it costs no
+/// RGB conversion per frame.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FramePattern {
+    /// The same bars in every frame
+    Static,
+    /// A vertical bar stepping across the frame, one bar width per frame:
+    /// black with a white center, so it shows over every color bar
+    #[default]
+    MovingBar,
+    /// The frame index burned into the center of the frame
+    FrameCounter,
+}
+
+impl std::str::FromStr for FramePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "static" => Ok(FramePattern::Static),
+            "moving-bar" => Ok(FramePattern::MovingBar),
+            "frame-counter" => Ok(FramePattern::FrameCounter),
+            _ => bail!(
+                "unknown frame pattern {}, expected static, moving-bar or frame-counter",
+                s
+            ),
+        }
+    }
+}
+
+impl FramePattern {
+    pub fn name(self) -> &'static str {
+        match self {
+            FramePattern::Static => "static",
+            FramePattern::MovingBar => "moving-bar",
+            FramePattern::FrameCounter => "frame-counter",
+        }
+    }
+
+    /// Width in pixels of the [`FramePattern::MovingBar`] bar: about 1/64 of
+    /// the frame, a multiple of 8 so the bar and its white center both cover
+    /// whole chroma pairs.
+    pub fn bar_width(format: &VideoFormat) -> usize {
+        (format.width as usize / 64).max(8) & !7
+    }
+
+    /// Draws frame `frame_index` of the pattern over the bars in `buf`.
+    /// Nothing is erased, so `buf` must hold the plain bars.
+    pub fn draw(self, buf: &mut [u8], format: &VideoFormat, frame_index: u64) {
+        let (width, height) = (format.width as usize, format.height as usize);
+        match self {
+            FramePattern::Static => {}
+            FramePattern::MovingBar => {
+                let bar = Self::bar_width(format);
+                let positions = (width / bar).max(1) as u64;
+                let x = (frame_index % positions) as usize * bar;
+                overlay::fill_rect(buf, format, x, 0, bar, height, false);
+                overlay::fill_rect(buf, format, x + bar / 4, 0, bar / 2, height, true);
+            }
+            FramePattern::FrameCounter => {
+                let text = frame_index.to_string();
+                let scale = (height / 36).max(1);
+                let x = width.saturating_sub(overlay::text_width(&text, scale)) / 2;
+                let y = height.saturating_sub(7 * scale) / 2;
+                overlay::draw_text(buf, format, x, y, scale, &text);
+            }
+        }
+    }
+}
+
+/// Opaque random noise that changes every frame. Nothing compresses well and
+/// every frame costs a full render and conversion, which makes it the
+/// worst case for both the encoder and frame generation.
+pub struct NoisePattern {
+    pub seed: u64,
+}
+
+impl FrameRenderer for NoisePattern {
+    fn name(&self) -> &'static str {
+        "noise"
+    }
+
+    fn render_rgba(&self, format: &VideoFormat, frame_index: u64) -> Result<RgbaImage> {
+        let mut rng = XorShift::new(self.seed ^ frame_index.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        let mut image = RgbaImage::new(format.width as u32, format.height as u32);
+        for pixel in image.chunks_exact_mut(4) {
+            let bits = rng.next_u64().to_le_bytes();
+            pixel.copy_from_slice(&[bits[0], bits[1], bits[2], 255]);
+        }
+        Ok(image)
+    }
+}
+
+/// Width in pixels of each stripe of [`ChromaEdgePattern`]. Odd, so the
+/// edges alternate between even and odd positions.
+pub const CHROMA_EDGE_STRIPE: u32 = 7;
+
+/// Red and green stripes with sharp edges for checking chroma siting:
+/// vertical edges in the top half, horizontal edges in the bottom half.
+///
+/// The UYVY and NV12 converters average chroma over each pixel pair (and
+/// NV12 over each 2x2 block), i.e. centered siting. Edges on even positions
+/// fall between chroma samples and stay clean. Edges on odd positions share a
+/// chroma sample between a red and a green pixel, which a receiver shows as
+/// a one-pixel blend; a receiver that upsamples assuming co-sited chroma
+/// shifts that blend half a pixel, so the fringe sits on one side of the odd
+/// edges only. `OMTMediaFrame` has no siting field to declare either choice.
+pub struct ChromaEdgePattern;
+
+impl FrameRenderer for ChromaEdgePattern {
+    fn name(&self) -> &'static str {
+        "chroma-edge"
+    }
+
+    fn animated(&self) -> bool {
+        false
+    }
+
+    fn render_rgba(&self, format: &VideoFormat, _frame_index: u64) -> Result<RgbaImage> {
+        let (width, height) = (format.width as u32, format.height as u32);
+        Ok(RgbaImage::from_fn(width, height, |x, y| {
+            let stripe = if y < height / 2 { x } else { y - height / 2 } / CHROMA_EDGE_STRIPE;
+            if stripe.is_multiple_of(2) {
+                image::Rgba([255, 0, 0, 255])
+            } else {
+                image::Rgba([0, 255, 0, 255])
+            }
+        }))
+    }
+}
+
+/// Colors shown by [`ColorSweepPattern`], one per second, in order.
+pub const SWEEP_COLORS: [(&str, [u8; 3]); 6] = [
+    ("red", [255, 0, 0]),
+    ("green", [0, 255, 0]),
+    ("blue", [0, 0, 255]),
+    ("white", [255, 255, 255]),
+    ("gray", [128, 128, 128]),
+    ("black", [0, 0, 0]),
+];
+
+/// Full-frame solid colors from [`SWEEP_COLORS`], changing every second and
+/// converted with the format's color space like any rendered content. A
+/// receiver showing blue for red has swapped channels; wrong levels on white,
+/// gray or black point at the matrix or range.
+pub struct ColorSweepPattern;
+
+impl ColorSweepPattern {
+    fn color(format: &VideoFormat, frame_index: u64) -> (&'static str, [u8; 3]) {
+        let second = frame_index * format.fps_d.max(1) as u64 / format.fps_n.max(1) as u64;
+        SWEEP_COLORS[(second % SWEEP_COLORS.len() as u64) as usize]
+    }
+}
+
+impl FrameRenderer for ColorSweepPattern {
+    fn name(&self) -> &'static str {
+        "color-sweep"
+    }
+
+    fn render_rgba(&self, format: &VideoFormat, frame_index: u64) -> Result<RgbaImage> {
+        let (_, [r, g, b]) = Self::color(format, frame_index);
+        Ok(RgbaImage::from_pixel(
+            format.width as u32,
+            format.height as u32,
+            image::Rgba([r, g, b, 255]),
+        ))
+    }
+
+    fn label(&self, format: &VideoFormat, frame_index: u64) -> Option<String> {
+        let (name, [r, g, b]) = Self::color(format, frame_index);
+        Some(format!("color {} ({}, {}, {})", name, r, g, b))
+    }
+}
+
+/// Alternates between two solid fields every frame: black and white, or
+/// magenta and green, which are complementary and also swap the chroma
+/// planes' extremes. Nothing carries over from one frame to the next, so
+/// inter-frame prediction gains nothing and the bitrate should spike well
+/// above a static pattern's; an encoder that smears or lags on it shows
+/// temporal artifacts.
+pub struct FlickerPattern {
+    pub name: &'static str,
+    pub fields: [[u8; 3]; 2],
+}
+
+impl FrameRenderer for FlickerPattern {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn render_rgba(&self, format: &VideoFormat, frame_index: u64) -> Result<RgbaImage> {
+        let [r, g, b] = self.fields[(frame_index % 2) as usize];
+        Ok(RgbaImage::from_pixel(
+            format.width as u32,
+            format.height as u32,
+            image::Rgba([r, g, b, 255]),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::*;
+    use crate::pattern_frame_into;
+
+    fn frames(format: &VideoFormat, pattern: FramePattern, alpha: bool) -> [Vec<u8>; 2] {
+        let plain = format.create_test_frame(0, FramePattern::Static);
+        [0, 1].map(|i| {
+            let mut buf = vec![0u8; format.buffer_size()];
+            pattern_frame_into(format, &plain, pattern, i, alpha, false, &mut buf);
+            buf
+        })
+    }
+
+    #[test]
+    fn animated_patterns_change_every_frame() {
+        for codec in [
+            OMTCodec_OMTCodec_UYVY,
+            OMTCodec_OMTCodec_BGRA,
+            OMTCodec_OMTCodec_NV12,
+        ] {
+            let format = VideoFormat::from_parts(codec, 1280, 720, 30, 1).unwrap();
+            for pattern in [FramePattern::MovingBar, FramePattern::FrameCounter] {
+                let [first, second] = frames(&format, pattern, false);
+                assert_ne!(first, second, "{} {}", format.name, pattern.name());
+            }
+            let [first, second] = frames(&format, FramePattern::Static, false);
+            assert_eq!(first, second, "{}", format.name);
+        }
+    }
+
+    #[test]
+    fn moving_bar_keeps_the_alpha_ramp() {
+        let format = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 1280, 720, 30, 1).unwrap();
+        let mut ramp = format.create_test_frame(0, FramePattern::Static);
+        crate::convert::apply_alpha_gradient(&mut ramp, 1280);
+        for buf in frames(&format, FramePattern::MovingBar, true) {
+            let alpha = |b: &[u8]| b.chunks_exact(4).map(|p| p[3]).collect::<Vec<_>>();
+            assert_eq!(alpha(&buf), alpha(&ramp));
+        }
+    }
+}
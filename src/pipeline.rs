@@ -0,0 +1,85 @@
+// フレーム生成と送信の並行化（生成スレッド＋バッファプール）
+use anyhow::{anyhow, Result};
+use std::{
+    ops::Range,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread::Scope,
+    time::{Duration, Instant},
+};
+
+use crate::{buffer::AlignedBuffer, generate_frame_into, FrameRenderer, VideoFormat};
+
+/// Frames rendered ahead of the send loop.
+pub const PIPELINE_DEPTH: usize = 3;
+
+/// Renders frames on a generator thread so rendering and conversion overlap
+/// with `omt_send`.
+///
+/// Rendered buffers arrive over a bounded channel; the send loop hands each
+/// buffer back with [`FramePipeline::recycle`] once it's been sent, so the
+/// steady state allocates nothing.
+pub struct FramePipeline {
+    ready: Receiver<Result<AlignedBuffer>>,
+    free: SyncSender<AlignedBuffer>,
+    /// Time the send loop spent waiting for the generator
+    pub wait_time: Duration,
+}
+
+impl FramePipeline {
+    /// Starts rendering `frames` on a thread in `scope`. The thread stops once
+    /// the range is done, a render fails or the pipeline is dropped.
+    pub fn spawn<'scope, 'env>(
+        scope: &'scope Scope<'scope, 'env>,
+        renderer: &'env dyn FrameRenderer,
+        format: VideoFormat,
+        premultiplied: bool,
+        align: usize,
+        frames: Range<u64>,
+    ) -> Self {
+        let (ready_tx, ready) = mpsc::sync_channel(PIPELINE_DEPTH);
+        // One extra slot for the buffer the send loop is holding
+        let (free, free_rx) = mpsc::sync_channel::<AlignedBuffer>(PIPELINE_DEPTH + 1);
+        for _ in 0..PIPELINE_DEPTH {
+            free.send(AlignedBuffer::zeroed(format.buffer_size(), align))
+                .ok();
+        }
+
+        scope.spawn(move || {
+            for i in frames {
+                let Ok(mut buf) = free_rx.recv() else {
+                    return;
+                };
+                let result =
+                    generate_frame_into(&format, Some(renderer), i, false, premultiplied, &mut buf)
+                        .map(|()| buf);
+                let failed = result.is_err();
+                if ready_tx.send(result).is_err() || failed {
+                    return;
+                }
+            }
+        });
+
+        FramePipeline {
+            ready,
+            free,
+            wait_time: Duration::ZERO,
+        }
+    }
+
+    /// Returns the next rendered frame, blocking until the generator has it.
+    pub fn next_frame(&mut self) -> Result<AlignedBuffer> {
+        let start = Instant::now();
+        let frame = self
+            .ready
+            .recv()
+            .map_err(|_| anyhow!("frame generator stopped early"))?;
+        self.wait_time += start.elapsed();
+        frame
+    }
+
+    /// Returns a sent buffer to the pool.
+    pub fn recycle(&self, buf: AlignedBuffer) {
+        // Only fails once the generator has finished, when the buffer isn't needed
+        self.free.try_send(buf).ok();
+    }
+}
@@ -0,0 +1,176 @@
+// 組み込みのフォーマットプリセット（--list-presets）
+use anyhow::{bail, Result};
+
+use crate::audio::{self, AudioFormat};
+use crate::{bindings::*, codec_name, VideoFormat};
+
+const UYVY: OMTCodec = OMTCodec_OMTCodec_UYVY;
+const NV12: OMTCodec = OMTCodec_OMTCodec_NV12;
+const BGRA: OMTCodec = OMTCodec_OMTCodec_BGRA;
+
+const fn preset(
+    codec: OMTCodec,
+    width: i32,
+    height: i32,
+    fps_n: i32,
+    fps_d: i32,
+    name: &'static str,
+) -> VideoFormat {
+    VideoFormat {
+        codec,
+        width,
+        height,
+        fps_n,
+        fps_d,
+        name,
+        stride_align: 1,
+        stride_override: None,
+        colorspace: None,
+    }
+}
+
+/// Presets run when no format is named.
+pub const SUITE: &[VideoFormat] = &[
+    // Current stable format
+    preset(UYVY, 1280, 720, 30, 1, "UYVY_720p30"),
+    // Test higher resolution UYVY
+    preset(UYVY, 1920, 1080, 30, 1, "UYVY_1080p30"),
+    // Test BGRA 720p
+    preset(BGRA, 1280, 720, 30, 1, "BGRA_720p30"),
+    // Test BGRA 1080p
+    preset(BGRA, 1920, 1080, 30, 1, "BGRA_1080p30"),
+    // Test NV12 format
+    preset(NV12, 1280, 720, 30, 1, "NV12_720p30"),
+];
+
+/// Presets that only run when named: larger, non-16:9 and SD formats that
+/// would make the default suite much longer.
+pub const EXTENDED: &[VideoFormat] = &[
+    // UHD and DCI cinema
+    preset(UYVY, 3840, 2160, 30, 1, "UYVY_2160p30"),
+    preset(NV12, 3840, 2160, 30, 1, "NV12_2160p30"),
+    preset(UYVY, 2048, 1080, 24, 1, "UYVY_2048x1080p24"),
+    preset(UYVY, 4096, 2160, 24, 1, "UYVY_4096x2160p24"),
+    // Vertical and square video for phone and social receivers
+    preset(UYVY, 1080, 1920, 30, 1, "UYVY_1080x1920p30"),
+    preset(NV12, 1080, 1920, 30, 1, "NV12_1080x1920p30"),
+    preset(BGRA, 1080, 1920, 30, 1, "BGRA_1080x1920p30"),
+    preset(UYVY, 1080, 1080, 30, 1, "UYVY_1080x1080p30"),
+    // Ultrawide
+    preset(UYVY, 2560, 1080, 30, 1, "UYVY_2560x1080p30"),
+    // Film and PAL rates; 23.976 exercises the fractional PTS math
+    preset(UYVY, 1920, 1080, 24000, 1001, "UYVY_1080p23.976"),
+    preset(UYVY, 1920, 1080, 24, 1, "UYVY_1080p24"),
+    preset(UYVY, 1920, 1080, 25, 1, "UYVY_1080p25"),
+    // SD: NTSC is 720x486 at 30000/1001, PAL 720x576 at 25
+    preset(UYVY, 720, 486, 30000, 1001, "UYVY_486p29.97"),
+    preset(UYVY, 720, 576, 25, 1, "UYVY_576p25"),
+    // High frame rates
+    preset(UYVY, 1920, 1080, 60000, 1001, "UYVY_1080p59.94"),
+    preset(UYVY, 1920, 1080, 60, 1, "UYVY_1080p60"),
+];
+
+/// Audio and video presets sent together by `run_av_test`: name, video
+/// preset, audio preset. 29.97 and 59.94 don't divide 48 kHz evenly and
+/// exercise the fractional sample cadence.
+pub const AV_PRESETS: &[(&str, &str, &str)] = &[
+    ("AV_720p30_48k", "UYVY_720p30", "audio_48k_stereo"),
+    ("AV_1080p30_48k", "UYVY_1080p30", "audio_48k_stereo"),
+    ("AV_1080p23.976_48k", "UYVY_1080p23.976", "audio_48k_stereo"),
+    ("AV_1080p25_48k", "UYVY_1080p25", "audio_48k_stereo"),
+    ("AV_1080p59.94_48k", "UYVY_1080p59.94", "audio_48k_stereo"),
+    ("AV_486p29.97_48k", "UYVY_486p29.97", "audio_48k_stereo"),
+];
+
+/// Looks up an audio and video preset by name.
+pub fn find_av(name: &str) -> Option<(VideoFormat, AudioFormat)> {
+    let &(_, video, audio) = AV_PRESETS.iter().find(|(n, _, _)| *n == name)?;
+    Some((find(video)?, audio::find(audio)?))
+}
+
+/// Looks up a preset by name in both tables.
+pub fn find(name: &str) -> Option<VideoFormat> {
+    SUITE
+        .iter()
+        .chain(EXTENDED)
+        .find(|f| f.name == name)
+        .copied()
+}
+
+/// Checks that the dimensions fit the codec's chroma subsampling: UYVY
+/// packs each pixel pair into one macropixel, so the width must be even.
+/// NV12 shares chroma between 2x2 blocks but rounds its UV plane up, so an
+/// odd last row or column gets chroma of its own.
+pub fn check_subsampling(format: &VideoFormat) -> Result<()> {
+    let codec = format.codec;
+    if codec == OMTCodec_OMTCodec_UYVY && format.width % 2 != 0 {
+        bail!(
+            "{}: width {} must be even for {}",
+            format.name,
+            format.width,
+            codec_name(codec)
+        );
+    }
+    Ok(())
+}
+
+/// Checks every preset in both tables; called at startup so a bad table
+/// entry fails before anything is sent.
+pub fn check_all() -> Result<()> {
+    for format in SUITE.iter().chain(EXTENDED) {
+        check_subsampling(format)?;
+        format.check_size()?;
+    }
+    for (name, video, audio) in AV_PRESETS {
+        if find_av(name).is_none() {
+            bail!("{}: unknown preset {} or {}", name, video, audio);
+        }
+    }
+    Ok(())
+}
+
+/// Prints both tables with their parameters.
+pub fn print_list() {
+    for (title, formats) in [
+        ("Default suite (run when no format is named)", SUITE),
+        ("Extended (run by name)", EXTENDED),
+    ] {
+        println!("{}:", title);
+        for f in formats {
+            let fps = if f.fps_d == 1 {
+                f.fps_n.to_string()
+            } else {
+                format!("{}/{}", f.fps_n, f.fps_d)
+            };
+            println!(
+                "  {:<20} {:<5} {:>4}x{:<4} {:>10} fps  {}",
+                f.name,
+                codec_name(f.codec),
+                f.width,
+                f.height,
+                fps,
+                aspect_ratio(f.width, f.height)
+            );
+        }
+    }
+    println!("Audio (1 kHz tone, planar float):");
+    for a in audio::AUDIO_FORMATS {
+        println!(
+            "  {:<20} {:>5} Hz  {} channel(s)",
+            a.name, a.sample_rate, a.channels
+        );
+    }
+    println!("Audio and video (run_av_test):");
+    for (name, video, audio) in AV_PRESETS {
+        println!("  {:<20} {} + {}", name, video, audio);
+    }
+}
+
+/// Width:height in lowest terms, e.g. `16:9` or `256:135` for DCI.
+fn aspect_ratio(width: i32, height: i32) -> String {
+    let (mut a, mut b) = (width, height);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    format!("{}:{}", width / a, height / a)
+}
@@ -0,0 +1,202 @@
+// OMT ソースの受信とフレームの検証（receive サブコマンド）
+use std::{
+    ffi::CString,
+    mem,
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+
+use crate::bindings::*;
+use crate::stats::Statistics;
+use crate::{codec_name, discovery, Options, VideoFormat};
+
+/// How long each `omt_receive` call waits for a frame before the loop
+/// reports that nothing arrived and polls again.
+pub const RECEIVE_TIMEOUT_MS: i32 = 1000;
+
+/// Owns an `omt_receive_t` and destroys it on drop, so leaving the receive
+/// loop by Ctrl-C or an error still disconnects from the sender.
+pub struct Receiver {
+    ptr: *mut omt_receive_t,
+    address: String,
+}
+
+impl Receiver {
+    /// Connects to `address`, asking for video frames decoded to `preferred`.
+    pub fn create(address: &str, preferred: OMTPreferredVideoFormat) -> Result<Self> {
+        let c_address = CString::new(address)?;
+        let ptr = unsafe {
+            omt_receive_create(
+                c_address.as_ptr(),
+                OMTFrameType_OMTFrameType_Video,
+                preferred,
+                OMTReceiveFlags_OMTReceiveFlags_None,
+            )
+        };
+        if ptr.is_null() {
+            bail!("omt_receive_create failed for {}", address);
+        }
+        Ok(Receiver {
+            ptr,
+            address: address.to_string(),
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Waits up to `timeout_ms` for the next video frame. The frame belongs
+    /// to libomt and stays valid until the next call, hence the `&mut self`.
+    pub fn receive(&mut self, timeout_ms: i32) -> Option<&OMTMediaFrame> {
+        let frame = unsafe { omt_receive(self.ptr, OMTFrameType_OMTFrameType_Video, timeout_ms) };
+        unsafe { frame.as_ref() }
+    }
+
+    /// Reads the receive-side video statistics.
+    pub fn video_statistics(&self) -> Statistics {
+        let mut stats: OMTStatistics = unsafe { mem::zeroed() };
+        unsafe { omt_receive_getvideostatistics(self.ptr, &mut stats as *mut OMTStatistics) };
+        stats.into()
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        unsafe { omt_receive_destroy(self.ptr) };
+    }
+}
+
+/// Turns a bare source name into a full `HOST (Name)` address using
+/// [`discovery::discover`]. Addresses, and names discovery doesn't find,
+/// are passed to libomt as given.
+pub fn resolve_source(source: &str) -> String {
+    if discovery::source_name(source) != source {
+        return source.to_string();
+    }
+    println!("Looking up {}...", source);
+    discovery::discover(discovery::DISCOVERY_TIMEOUT)
+        .into_iter()
+        .find(|address| discovery::source_name(address) == source)
+        .unwrap_or_else(|| source.to_string())
+}
+
+/// Differences between a received frame and `expect`, empty if it matches.
+pub fn check_geometry(frame: &OMTMediaFrame, expect: &VideoFormat) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if (frame.Width, frame.Height) != (expect.width, expect.height) {
+        mismatches.push(format!(
+            "size {}x{}, expected {}x{}",
+            frame.Width, frame.Height, expect.width, expect.height
+        ));
+    }
+    // Compared as a ratio, so 30/1 and 30000/1000 agree
+    if frame.FrameRateN as i64 * expect.fps_d as i64
+        != expect.fps_n as i64 * frame.FrameRateD as i64
+    {
+        mismatches.push(format!(
+            "frame rate {}/{}, expected {}/{}",
+            frame.FrameRateN, frame.FrameRateD, expect.fps_n, expect.fps_d
+        ));
+    }
+    mismatches
+}
+
+/// Receives video from `source` until Ctrl-C (or `--duration` /
+/// `--send-count`, if given), printing each frame and running totals.
+/// With `expect`, every frame's size and rate are checked against that
+/// preset and any mismatch fails the run.
+pub fn run_receive(source: &str, expect: Option<&VideoFormat>, opts: &Options) -> Result<()> {
+    let address = resolve_source(source);
+    println!("\n=== Receiving from {} ===\n", address);
+    if let Some(expect) = expect {
+        println!(
+            "Expecting {} ({}x{} at {}/{} fps)",
+            expect.name, expect.width, expect.height, expect.fps_n, expect.fps_d
+        );
+    }
+    let mut receiver = Receiver::create(
+        &address,
+        OMTPreferredVideoFormat_OMTPreferredVideoFormat_UYVY,
+    )?;
+
+    let deadline = opts
+        .duration_secs
+        .map(|secs| Duration::from_secs(secs as u64));
+    let start_time = Instant::now();
+    let (mut frames, mut bytes, mut mismatched) = (0u64, 0u64, 0u64);
+    let mut last_pts: Option<i64> = None;
+    let mut waiting_since = Instant::now();
+    loop {
+        if opts.stop.is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            println!("Stopped after {} frames", frames);
+            break;
+        }
+        if deadline.is_some_and(|d| start_time.elapsed() >= d)
+            || opts.frame_count.is_some_and(|n| frames >= n as u64)
+        {
+            break;
+        }
+        let Some(frame) = receiver.receive(RECEIVE_TIMEOUT_MS) else {
+            println!(
+                "No frame within {}ms, still waiting ({:.0}s without frames)",
+                RECEIVE_TIMEOUT_MS,
+                waiting_since.elapsed().as_secs_f64()
+            );
+            continue;
+        };
+        waiting_since = Instant::now();
+        frames += 1;
+        bytes += frame.DataLength.max(0) as u64;
+        let delta = last_pts.map(|pts| frame.Timestamp - pts);
+        last_pts = Some(frame.Timestamp);
+        println!(
+            "Frame {}: {} {}x{} PTS={}{} ({} bytes)",
+            frames - 1,
+            codec_name(frame.Codec),
+            frame.Width,
+            frame.Height,
+            frame.Timestamp,
+            delta.map(|d| format!(", delta {}", d)).unwrap_or_default(),
+            frame.DataLength
+        );
+        if let Some(expect) = expect {
+            let mismatches = check_geometry(frame, expect);
+            if !mismatches.is_empty() {
+                mismatched += 1;
+                eprintln!("  Mismatch: {}", mismatches.join(", "));
+            }
+        }
+        if frames.is_multiple_of(30) {
+            println!(
+                "[{:.1}s] Received: {} frames, {} bytes",
+                start_time.elapsed().as_secs_f64(),
+                frames,
+                bytes
+            );
+        }
+    }
+
+    let vstats = receiver.video_statistics();
+    println!(
+        "\n=== Final Receive Statistics for {} ===",
+        receiver.address()
+    );
+    println!("Frames received: {}", frames);
+    println!("Bytes received: {}", bytes);
+    println!("Frames dropped (libomt): {}", vstats.frames_dropped);
+    if expect.is_some() {
+        println!("Frames with mismatched geometry: {}", mismatched);
+    }
+    drop(receiver);
+    if mismatched > 0 {
+        bail!(
+            "{} of {} frames did not match the expected format",
+            mismatched,
+            frames
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,495 @@
+// テスト結果の保存とベースラインとの比較（--save-baseline / --baseline）
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+#[cfg(feature = "webhook")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{RunOutcome, TestResult};
+
+/// Effective settings of one test, printed before sending so pasted output
+/// is self-describing.
+#[derive(Debug, Clone, Serialize)]
+pub struct Banner {
+    pub source: String,
+    pub codec: String,
+    pub width: i32,
+    pub height: i32,
+    pub fps_n: i32,
+    pub fps_d: i32,
+    pub quality: String,
+    pub duration_secs: f64,
+    pub frames: u32,
+    pub pattern: String,
+    /// "none", "straight" or "premultiplied"
+    pub alpha: String,
+    pub colorspace: String,
+    pub encoder: String,
+    pub log_file: String,
+}
+
+impl Banner {
+    /// Prints the settings as an aligned block, or as one line of JSON.
+    pub fn print(&self, json: bool) -> Result<()> {
+        if json {
+            println!("{}", serde_json::to_string(self)?);
+            return Ok(());
+        }
+        println!("Settings:");
+        println!("  source:     {}", self.source);
+        println!("  codec:      {}", self.codec);
+        println!("  resolution: {}x{}", self.width, self.height);
+        println!(
+            "  fps:        {}/{} ({:.3})",
+            self.fps_n,
+            self.fps_d,
+            self.fps_n as f64 / self.fps_d as f64
+        );
+        println!("  quality:    {}", self.quality);
+        println!(
+            "  duration:   {:.2}s ({} frames)",
+            self.duration_secs, self.frames
+        );
+        println!("  pattern:    {}", self.pattern);
+        println!("  alpha:      {}", self.alpha);
+        println!("  colorspace: {}", self.colorspace);
+        println!("  encoder:    {}", self.encoder);
+        println!("  log file:   {}", self.log_file);
+        Ok(())
+    }
+}
+
+/// How the final report is written to stdout (`--output-format`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Only the per-test summaries printed as the tests run
+    #[default]
+    Human,
+    /// The [`TestReport`] as JSON
+    Json,
+    /// One CSV row per [`TestResult`]
+    Csv,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "human" => Ok(OutputFormat::Human),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            _ => bail!("unknown output format {}, expected human, json or csv", s),
+        }
+    }
+}
+
+/// Attempts at delivering the report to `--report-webhook`.
+#[cfg(feature = "webhook")]
+pub const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// Timeout of each webhook request, from connecting to reading the response.
+#[cfg(feature = "webhook")]
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Wait before the first webhook retry; doubles per attempt.
+#[cfg(feature = "webhook")]
+const WEBHOOK_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Results of one run of the suite.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TestReport {
+    /// Version of this tool that wrote the report
+    pub version: String,
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    pub fn new() -> Self {
+        TestReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            results: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid report {}", path.display()))
+    }
+
+    /// Renders the results as CSV with a header row.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "name,bytes_sent,frames_sent,frames_dropped,bitrate_mbps,success_rate_pct,codec_time_peak_ms,codec_time_mean_ms,outcome\n",
+        );
+        for r in &self.results {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.3},{:.2},{},{:.2},{}\n",
+                csv_field(&r.name),
+                r.bytes_sent,
+                r.frames_sent,
+                r.frames_dropped,
+                r.bitrate_mbps,
+                r.success_rate_pct,
+                r.codec_time_peak_ms,
+                r.codec_time_mean_ms,
+                csv_field(&r.outcome.to_string())
+            ));
+        }
+        csv
+    }
+
+    /// Prints the report to stdout in `format`; `Human` prints nothing since
+    /// the summaries were already printed.
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Human => {}
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Csv => print!("{}", self.to_csv()),
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+    }
+
+    /// POSTs the report as JSON to `url` (`--report-webhook`). Connection
+    /// errors, timeouts, 429 and 5xx responses are retried up to
+    /// [`WEBHOOK_ATTEMPTS`] times with a doubling backoff; other responses
+    /// fail at once.
+    #[cfg(feature = "webhook")]
+    pub fn post(&self, url: &str) -> Result<()> {
+        let json = serde_json::to_string(self)?;
+        let agent: ureq::Agent = ureq::Agent::config_builder()
+            .timeout_global(Some(WEBHOOK_TIMEOUT))
+            .build()
+            .into();
+        let mut backoff = WEBHOOK_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let error = match agent
+                .post(url)
+                .header("Content-Type", "application/json")
+                .send(&json)
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+            let transient = match &error {
+                ureq::Error::StatusCode(code) => *code == 429 || *code >= 500,
+                ureq::Error::Io(_)
+                | ureq::Error::Timeout(_)
+                | ureq::Error::HostNotFound
+                | ureq::Error::ConnectionFailed => true,
+                _ => false,
+            };
+            if !transient || attempt >= WEBHOOK_ATTEMPTS {
+                return Err(error).with_context(|| {
+                    format!(
+                        "failed to post the report to {} after {} attempt(s)",
+                        url, attempt
+                    )
+                });
+            }
+            eprintln!(
+                "Posting the report to {} failed ({}), retrying in {}ms",
+                url,
+                error,
+                backoff.as_millis()
+            );
+            std::thread::sleep(backoff);
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    /// The fields of the report that `--deterministic` reproduces.
+    pub fn deterministic(&self) -> DeterministicReport {
+        DeterministicReport {
+            version: self.version.clone(),
+            results: self
+                .results
+                .iter()
+                .map(|r| DeterministicResult {
+                    name: r.name.clone(),
+                    frames_requested: r.frames_requested,
+                    frames_skipped: r.frames_skipped,
+                    outcome: r.outcome,
+                    stream_digest: r.stream_digest.clone().unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Compares each result against the baseline result of the same name and
+    /// returns a message per regression. Formats missing from either side are
+    /// reported but not counted.
+    pub fn regressions(&self, baseline: &TestReport, tolerance: &Tolerance) -> Vec<String> {
+        let mut regressions = Vec::new();
+        for base in &baseline.results {
+            let Some(current) = self.results.iter().find(|r| r.name == base.name) else {
+                println!("  {}: not run, skipped", base.name);
+                continue;
+            };
+
+            let bitrate_change = pct_change(base.bitrate_mbps, current.bitrate_mbps);
+            let codec_time_change = pct_change(base.codec_time_mean_ms, current.codec_time_mean_ms);
+            let success_drop = base.success_rate_pct - current.success_rate_pct;
+            println!(
+                "  {}: bitrate {:+.1}%, success rate {:+.2} points, codec time {:+.1}%",
+                current.name, bitrate_change, -success_drop, codec_time_change
+            );
+
+            // Bitrate moving either way means the encoder output changed
+            if bitrate_change.abs() > tolerance.bitrate_pct {
+                regressions.push(format!(
+                    "{}: bitrate {:.2} -> {:.2} Mbps ({:+.1}%, tolerance {}%)",
+                    current.name,
+                    base.bitrate_mbps,
+                    current.bitrate_mbps,
+                    bitrate_change,
+                    tolerance.bitrate_pct
+                ));
+            }
+            if success_drop > tolerance.success_rate_points {
+                regressions.push(format!(
+                    "{}: success rate {:.2}% -> {:.2}%",
+                    current.name, base.success_rate_pct, current.success_rate_pct
+                ));
+            }
+            if codec_time_change > tolerance.codec_time_pct {
+                regressions.push(format!(
+                    "{}: codec time {:.1} -> {:.1}ms ({:+.1}%, tolerance {}%)",
+                    current.name,
+                    base.codec_time_mean_ms,
+                    current.codec_time_mean_ms,
+                    codec_time_change,
+                    tolerance.codec_time_pct
+                ));
+            }
+        }
+        for current in &self.results {
+            if !baseline.results.iter().any(|b| b.name == current.name) {
+                println!("  {}: not in baseline, skipped", current.name);
+            }
+        }
+        regressions
+    }
+}
+
+/// What `--deterministic` prints instead of a [`TestReport`]: only what two
+/// runs with the same arguments reproduce exactly. Everything libomt counts
+/// or times is left out, since it depends on the machine, the receivers and
+/// the network: bytes and frames sent, dropped frames, bitrate, success
+/// rate, codec time and status codes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeterministicReport {
+    pub version: String,
+    pub results: Vec<DeterministicResult>,
+}
+
+/// One run in a [`DeterministicReport`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeterministicResult {
+    pub name: String,
+    pub frames_requested: u32,
+    pub frames_skipped: u32,
+    pub outcome: RunOutcome,
+    /// [`TestResult::stream_digest`]
+    pub stream_digest: String,
+}
+
+impl DeterministicReport {
+    /// Prints the report like [`TestReport::print`].
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        match format {
+            OutputFormat::Human => {}
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(self)?),
+            OutputFormat::Csv => {
+                println!("name,frames_requested,frames_skipped,outcome,stream_digest");
+                for r in &self.results {
+                    println!(
+                        "{},{},{},{},{}",
+                        csv_field(&r.name),
+                        r.frames_requested,
+                        r.frames_skipped,
+                        csv_field(&r.outcome.to_string()),
+                        r.stream_digest
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A run of the suite that failed before producing a [`TestResult`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedRun {
+    pub name: String,
+    pub error: String,
+}
+
+/// Totals over every run in a [`SessionReport`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionSummary {
+    /// Runs that produced a result
+    pub runs: usize,
+    /// Runs that failed before producing one
+    pub failed_runs: usize,
+    /// Runs that produced a result but stopped before the last frame
+    pub early_stops: usize,
+    pub bytes_sent: i64,
+    pub frames_sent: i64,
+    pub frames_dropped: i64,
+    /// Dropped frames as a percentage of sent frames, over all runs
+    pub drop_rate_pct: f64,
+    /// Mean of the runs' bitrates, unweighted
+    pub mean_bitrate_mbps: f64,
+    pub min_success_rate_pct: f64,
+    pub worst_codec_time_peak_ms: i64,
+    /// Runs that failed a pass/fail gate or regressed against the baseline
+    pub gate_failures: usize,
+}
+
+/// Every run of one invocation in a single file (`--session-report`), with
+/// rollups and enough metadata to tell sessions apart once collected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionReport {
+    /// Unix time in seconds when the session started
+    pub started_at: u64,
+    /// Version of this tool
+    pub version: String,
+    /// libomt has no call that reports its version, so this is only known
+    /// when the build sets `LIBOMT_VERSION` for the vendored library
+    pub library_version: Option<String>,
+    pub host: String,
+    /// The command line, program name first
+    pub args: Vec<String>,
+    pub summary: SessionSummary,
+    pub runs: Vec<TestResult>,
+    pub failed_runs: Vec<FailedRun>,
+    pub gate_failures: Vec<String>,
+}
+
+impl SessionReport {
+    /// Starts a session now, on this host, with this process's arguments.
+    pub fn begin() -> Self {
+        SessionReport {
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            library_version: option_env!("LIBOMT_VERSION").map(str::to_string),
+            host: hostname(),
+            args: std::env::args().collect(),
+            summary: SessionSummary::default(),
+            runs: Vec::new(),
+            failed_runs: Vec::new(),
+            gate_failures: Vec::new(),
+        }
+    }
+
+    /// Fills in the runs from the suite's report and computes the summary.
+    pub fn finish(
+        &mut self,
+        report: &TestReport,
+        failed_runs: Vec<FailedRun>,
+        gate_failures: &[String],
+    ) {
+        self.runs = report.results.clone();
+        self.failed_runs = failed_runs;
+        self.gate_failures = gate_failures.to_vec();
+
+        let runs = &self.runs;
+        let frames_sent: i64 = runs.iter().map(|r| r.frames_sent).sum();
+        let frames_dropped: i64 = runs.iter().map(|r| r.frames_dropped).sum();
+        self.summary = SessionSummary {
+            runs: runs.len(),
+            failed_runs: self.failed_runs.len(),
+            early_stops: runs
+                .iter()
+                .filter(|r| r.outcome != RunOutcome::Completed)
+                .count(),
+            bytes_sent: runs.iter().map(|r| r.bytes_sent).sum(),
+            frames_sent,
+            frames_dropped,
+            drop_rate_pct: if frames_sent > 0 {
+                frames_dropped as f64 * 100.0 / frames_sent as f64
+            } else {
+                0.0
+            },
+            mean_bitrate_mbps: if runs.is_empty() {
+                0.0
+            } else {
+                runs.iter().map(|r| r.bitrate_mbps).sum::<f64>() / runs.len() as f64
+            },
+            min_success_rate_pct: runs
+                .iter()
+                .map(|r| r.success_rate_pct)
+                .reduce(f64::min)
+                .unwrap_or(0.0),
+            worst_codec_time_peak_ms: runs.iter().map(|r| r.codec_time_peak_ms).max().unwrap_or(0),
+            gate_failures: self.gate_failures.len(),
+        };
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("failed to write {}", path.display()))
+    }
+}
+
+/// How far a run may drift from the baseline before it's a regression.
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Allowed bitrate change in either direction
+    pub bitrate_pct: f64,
+    /// Allowed drop in success rate, in percentage points
+    pub success_rate_points: f64,
+    /// Allowed increase in mean codec time
+    pub codec_time_pct: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            bitrate_pct: 10.0,
+            success_rate_points: 1.0,
+            codec_time_pct: 20.0,
+        }
+    }
+}
+
+/// This machine's host name, or "unknown" if it can't be read.
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let rc = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if rc != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+/// Quotes a CSV field if it contains a separator, quote or newline.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Percent change from `base` to `current`; zero when the baseline is zero.
+fn pct_change(base: f64, current: f64) -> f64 {
+    if base == 0.0 {
+        0.0
+    } else {
+        (current / base - 1.0) * 100.0
+    }
+}
@@ -0,0 +1,158 @@
+// カラーバーのループバック往復検証（--verify-bars）
+use anyhow::{bail, Result};
+
+use crate::bars::BAR_NAMES;
+use crate::bindings::*;
+use crate::{codec_name, color_bar_section};
+
+/// Allowed difference, in 8-bit code values, between a decoded bar and the
+/// value sent, per source codec. VMX is lossy, so flat bars come back a few
+/// codes off; a channel swap or a wrong matrix is off by tens. BGRA goes
+/// through libomt's own RGB to YUV conversion, which may round differently.
+pub const BAR_TOLERANCES: &[(OMTCodec, f64)] = &[
+    (OMTCodec_OMTCodec_UYVY, 10.0),
+    (OMTCodec_OMTCodec_NV12, 10.0),
+    (OMTCodec_OMTCodec_BGRA, 12.0),
+];
+
+/// Rows and pixels averaged around the center of each bar, so single-pixel
+/// encoder noise doesn't decide the result.
+const SAMPLE_ROWS: usize = 8;
+const SAMPLE_PIXELS: usize = 16;
+
+/// The default tolerance for bars sent as `codec`, if it has bars.
+pub fn default_tolerance(codec: OMTCodec) -> Option<f64> {
+    BAR_TOLERANCES
+        .iter()
+        .find(|(c, _)| *c == codec)
+        .map(|(_, tolerance)| *tolerance)
+}
+
+/// How far one decoded bar is from the value sent.
+#[derive(Debug, Clone, Copy)]
+pub struct BarError {
+    pub name: &'static str,
+    /// `(U, Y, V)` as from [`crate::bars::yuv_bars`]
+    pub expected: (u8, u8, u8),
+    /// Mean `(U, Y, V)` of the sampled window in the worst frame
+    pub measured: (f64, f64, f64),
+    /// Largest difference of any channel over every frame checked
+    pub error: f64,
+}
+
+/// Mean `(U, Y, V)` around the center of each of the 8 bars in a decoded
+/// UYVY frame.
+pub fn sample_uyvy_bars(frame: &OMTMediaFrame) -> Result<[(f64, f64, f64); 8]> {
+    if frame.Codec != OMTCodec_OMTCodec_UYVY {
+        bail!(
+            "the loopback receiver returned {}, not UYVY",
+            codec_name(frame.Codec)
+        );
+    }
+    let (width, height, stride) = (
+        frame.Width as usize,
+        frame.Height as usize,
+        frame.Stride as usize,
+    );
+    if frame.Data.is_null() || stride < width * 2 || (frame.DataLength as usize) < stride * height {
+        bail!(
+            "the loopback receiver returned a {}x{} frame with stride {} and {} bytes",
+            width,
+            height,
+            stride,
+            frame.DataLength
+        );
+    }
+    let data = unsafe { std::slice::from_raw_parts(frame.Data as *const u8, stride * height) };
+
+    let rows = SAMPLE_ROWS.min(height);
+    let first_row = (height - rows) / 2;
+    let mut samples = [(0.0, 0.0, 0.0); 8];
+    for (bar, sample) in samples.iter_mut().enumerate() {
+        // The bar's pixel range; the last bar runs to the right edge
+        let start = (0..width)
+            .find(|&x| color_bar_section(x, width) == bar)
+            .unwrap_or(width);
+        let end = (start..width)
+            .find(|&x| color_bar_section(x, width) != bar)
+            .unwrap_or(width);
+        let pixels = SAMPLE_PIXELS.min(end - start) & !1;
+        if pixels == 0 {
+            bail!("{} pixels is too narrow to sample 8 bars", width);
+        }
+        // Whole macropixels, so U and V belong to the window's own pixels
+        let x0 = ((start + end - pixels) / 2) & !1;
+
+        let (mut u, mut y, mut v) = (0u64, 0u64, 0u64);
+        for row in first_row..first_row + rows {
+            let line = &data[row * stride..];
+            for pair in line[x0 * 2..(x0 + pixels) * 2].chunks_exact(4) {
+                u += pair[0] as u64;
+                y += pair[1] as u64 + pair[3] as u64;
+                v += pair[2] as u64;
+            }
+        }
+        let pairs = (rows * pixels / 2) as f64;
+        *sample = (u as f64 / pairs, y as f64 / (pairs * 2.0), v as f64 / pairs);
+    }
+    Ok(samples)
+}
+
+/// Folds one frame's samples into the running per-bar worst errors against
+/// the `expected` bars.
+pub fn update_errors(
+    errors: &mut Vec<BarError>,
+    samples: &[(f64, f64, f64); 8],
+    expected: &[(u8, u8, u8); 8],
+) {
+    if errors.is_empty() {
+        *errors = (0..8)
+            .map(|bar| BarError {
+                name: BAR_NAMES[bar],
+                expected: expected[bar],
+                measured: samples[bar],
+                error: 0.0,
+            })
+            .collect();
+    }
+    for (e, &(u, y, v)) in errors.iter_mut().zip(samples) {
+        let (eu, ey, ev) = e.expected;
+        let error = (u - eu as f64)
+            .abs()
+            .max((y - ey as f64).abs())
+            .max((v - ev as f64).abs());
+        if error >= e.error {
+            e.error = error;
+            e.measured = (u, y, v);
+        }
+    }
+}
+
+/// Prints one line per bar and returns the bars outside `tolerance`.
+pub fn print_errors(errors: &[BarError], tolerance: f64) -> Vec<String> {
+    let mut failures = Vec::new();
+    for e in errors {
+        let (eu, ey, ev) = e.expected;
+        let (u, y, v) = e.measured;
+        let ok = e.error <= tolerance;
+        println!(
+            "  {:<8} expected Y={:>3} U={:>3} V={:>3}  decoded Y={:>5.1} U={:>5.1} V={:>5.1}  error {:>4.1} {}",
+            e.name,
+            ey,
+            eu,
+            ev,
+            y,
+            u,
+            v,
+            e.error,
+            if ok { "ok" } else { "FAIL" }
+        );
+        if !ok {
+            failures.push(format!(
+                "{} bar off by {:.1} (tolerance {})",
+                e.name, e.error, tolerance
+            ));
+        }
+    }
+    failures
+}
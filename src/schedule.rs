@@ -0,0 +1,118 @@
+// 絶対時刻に基づくフレーム送信スケジュール
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Frame deadlines computed from a start instant and the frame index.
+///
+/// Adding a rounded `Duration` per frame accumulates error: 1/30s and
+/// 1001/30000s aren't whole nanoseconds, so `next += frame_duration` drifts
+/// by up to a nanosecond per frame. Here every deadline is derived from the
+/// index with integer arithmetic, so frame N is due at exactly
+/// `N * fps_d / fps_n` seconds (rounded down to a nanosecond) however long
+/// the run.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameSchedule {
+    /// When frame `base` is due
+    origin: Instant,
+    base: u64,
+    fps_n: u64,
+    fps_d: u64,
+}
+
+impl FrameSchedule {
+    /// Schedules frame 0 at `start`.
+    pub fn new(start: Instant, fps_n: i32, fps_d: i32) -> Self {
+        FrameSchedule {
+            origin: start,
+            base: 0,
+            fps_n: fps_n.max(1) as u64,
+            fps_d: fps_d.max(1) as u64,
+        }
+    }
+
+    /// Time from frame 0 to frame `index`.
+    pub fn offset(&self, index: u64) -> Duration {
+        let nanos = index as u128 * self.fps_d as u128 * 1_000_000_000 / self.fps_n as u128;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+
+    /// When frame `index` is due.
+    pub fn deadline(&self, index: u64) -> Instant {
+        self.origin + (self.offset(index) - self.offset(self.base.min(index)))
+    }
+
+    /// Shifts the schedule so frame `index` is due at `at`; later frames keep
+    /// their exact spacing from it.
+    pub fn rebase(&mut self, index: u64, at: Instant) {
+        self.origin = at;
+        self.base = index;
+    }
+
+    /// Sleeps until frame `index` is due. If it is already more than two
+    /// frame periods late the schedule is rebased to now rather than sending
+    /// a burst to catch up, and this returns true.
+    pub fn wait_for(&mut self, index: u64) -> bool {
+        let deadline = self.deadline(index);
+        let now = Instant::now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+            false
+        } else if now - deadline > self.offset(2) {
+            self.rebase(index, now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deadlines_do_not_drift() {
+        let start = Instant::now();
+        let schedule = FrameSchedule::new(start, 30000, 1001);
+        let period = Duration::from_nanos(33_366_666);
+        let mut accumulated = start;
+        for index in 0..=10_000u64 {
+            let exact = index as u128 * 1001 * 1_000_000_000 / 30000;
+            assert_eq!(
+                schedule.deadline(index) - start,
+                Duration::from_nanos(exact as u64)
+            );
+            if index < 10_000 {
+                accumulated += period;
+            }
+        }
+        // 10k frames are exactly 333.666666666s; adding the rounded period
+        // falls 6.67us behind
+        assert_eq!(
+            schedule.deadline(10_000) - start,
+            Duration::from_nanos(333_666_666_666)
+        );
+        assert_eq!(
+            schedule.deadline(10_000) - accumulated,
+            Duration::from_nanos(6_666)
+        );
+    }
+
+    #[test]
+    fn rebase_keeps_the_cadence() {
+        let start = Instant::now();
+        let mut schedule = FrameSchedule::new(start, 30000, 1001);
+        // 3000 frames are exactly 100.1s, so the grid repeats from there
+        let at = start + Duration::from_secs(200);
+        schedule.rebase(3000, at);
+        assert_eq!(schedule.deadline(3000), at);
+        for k in 1..=10_000u64 {
+            assert_eq!(schedule.deadline(3000 + k) - at, schedule.offset(k));
+        }
+    }
+}
@@ -0,0 +1,78 @@
+// rhai スクリプトによるカスタムパターン生成（`script` フィーチャー）
+use anyhow::{anyhow, bail, Result};
+use image::{Rgba, RgbaImage};
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use std::path::Path;
+
+use crate::{FrameRenderer, VideoFormat};
+
+/// A test pattern defined by a rhai script.
+///
+/// The script must define `fn fill_pixel(x, y, frame)` returning an array
+/// `[r, g, b, a]` of 0-255 integers. It is called once per pixel per frame,
+/// so expect it to be orders of magnitude slower than the native generators.
+pub struct ScriptPattern {
+    engine: Engine,
+    ast: AST,
+}
+
+impl ScriptPattern {
+    pub fn load(path: &Path) -> Result<Self> {
+        let engine = Engine::new();
+        let ast = engine
+            .compile_file(path.into())
+            .map_err(|e| anyhow!("failed to compile {}: {}", path.display(), e))?;
+
+        if !ast
+            .iter_functions()
+            .any(|f| f.name == "fill_pixel" && f.params.len() == 3)
+        {
+            bail!(
+                "{} does not define fn fill_pixel(x, y, frame)",
+                path.display()
+            );
+        }
+
+        Ok(Self { engine, ast })
+    }
+}
+
+fn channel(value: &Dynamic) -> Result<u8> {
+    let v = value
+        .as_int()
+        .map_err(|t| anyhow!("fill_pixel must return integers, got {}", t))?;
+    Ok(v.clamp(0, 255) as u8)
+}
+
+impl FrameRenderer for ScriptPattern {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn render_rgba(&self, format: &VideoFormat, frame_index: u64) -> Result<RgbaImage> {
+        let mut scope = Scope::new();
+        let mut image = RgbaImage::new(format.width as u32, format.height as u32);
+
+        for (x, y, out) in image.enumerate_pixels_mut() {
+            let args = (x as i64, y as i64, frame_index as i64);
+            let pixel: Array = self
+                .engine
+                .call_fn(&mut scope, &self.ast, "fill_pixel", args)
+                .map_err(|e| anyhow!("fill_pixel({}, {}, {}) failed: {}", x, y, frame_index, e))?;
+            if pixel.len() != 4 {
+                bail!(
+                    "fill_pixel must return [r, g, b, a], got {} values",
+                    pixel.len()
+                );
+            }
+            *out = Rgba([
+                channel(&pixel[0])?,
+                channel(&pixel[1])?,
+                channel(&pixel[2])?,
+                channel(&pixel[3])?,
+            ]);
+        }
+
+        Ok(image)
+    }
+}
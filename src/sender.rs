@@ -0,0 +1,475 @@
+// omt_send_t の安全なラッパー（Drop で omt_send_destroy）
+use anyhow::{bail, Context, Result};
+use std::{
+    cell::Cell,
+    ffi::{CStr, CString},
+    mem,
+    os::raw::{c_char, c_int},
+    ptr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc, Arc, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::bindings::*;
+use crate::stats::Statistics;
+use crate::{quality_name, video_frame, VideoFormat};
+
+/// Names of the libomt calls made through [`Sender`], indexed by
+/// `CURRENT_CALL`; 0 means no call is in progress.
+const CALLS: [&str; 10] = [
+    "",
+    "omt_send_create",
+    "omt_send_setsenderinformation",
+    "omt_send",
+    "omt_send_connections",
+    "omt_send_receive",
+    "omt_send_getaddress",
+    "omt_send_getvideostatistics",
+    "omt_send_getaudiostatistics",
+    "omt_send_destroy",
+];
+
+/// The libomt call in progress, so a crash inside the library can be
+/// attributed to it.
+static CURRENT_CALL: AtomicUsize = AtomicUsize::new(0);
+/// Completed `omt_send` calls since the last sender was created, i.e. the
+/// index of the frame being sent.
+static SENT_FRAMES: AtomicU64 = AtomicU64::new(0);
+
+/// Marks a libomt call as in progress until dropped.
+struct CallGuard;
+
+impl CallGuard {
+    fn enter(name: &str) -> CallGuard {
+        let index = CALLS.iter().position(|&c| c == name).unwrap_or(0);
+        CURRENT_CALL.store(index, Ordering::Relaxed);
+        CallGuard
+    }
+}
+
+impl Drop for CallGuard {
+    fn drop(&mut self) {
+        CURRENT_CALL.store(0, Ordering::Relaxed);
+    }
+}
+
+const FATAL_SIGNALS: [c_int; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGABRT,
+];
+static PREVIOUS_ACTIONS: OnceLock<[libc::sigaction; 5]> = OnceLock::new();
+
+/// Makes a crash inside libomt print which call it happened in (and for
+/// `omt_send`, the frame index) before the process dies.
+///
+/// Nothing in Rust can unwind across the library, which takes no callbacks,
+/// but a fault inside it kills the process with no hint of the operation.
+/// The handler only writes that line and then restores the previous action
+/// (the default, or std's stack overflow handler), so the signal still ends
+/// the process the same way.
+pub fn install_crash_handler() {
+    PREVIOUS_ACTIONS.get_or_init(|| {
+        FATAL_SIGNALS.map(|signal| unsafe {
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_sigaction = on_fatal_signal as extern "C" fn(c_int) as libc::sighandler_t;
+            // std's alternate signal stack, so a stack overflow still
+            // reaches its handler
+            action.sa_flags = libc::SA_ONSTACK;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous: libc::sigaction = mem::zeroed();
+            libc::sigaction(signal, &action, &mut previous);
+            previous
+        })
+    });
+}
+
+extern "C" fn on_fatal_signal(signal: c_int) {
+    let call = CURRENT_CALL.load(Ordering::Relaxed);
+    if call != 0 {
+        // Only async-signal-safe calls here: no allocation, no formatting
+        let mut line = [0u8; 128];
+        let mut len = 0;
+        let mut push = |bytes: &[u8]| {
+            let n = bytes.len().min(line.len() - len);
+            line[len..len + n].copy_from_slice(&bytes[..n]);
+            len += n;
+        };
+        push(b"\nFatal signal ");
+        push(decimal(signal as u64, &mut [0; 20]));
+        push(b" inside ");
+        push(CALLS[call].as_bytes());
+        if CALLS[call] == "omt_send" {
+            push(b" at frame ");
+            push(decimal(SENT_FRAMES.load(Ordering::Relaxed), &mut [0; 20]));
+        }
+        push(b"\n");
+        unsafe { libc::write(libc::STDERR_FILENO, line.as_ptr().cast(), len) };
+    }
+    // Returning re-runs the faulting instruction (or abort re-raises) under
+    // the restored action
+    if let Some(previous) = PREVIOUS_ACTIONS.get() {
+        if let Some(i) = FATAL_SIGNALS.iter().position(|&s| s == signal) {
+            unsafe { libc::sigaction(signal, &previous[i], ptr::null_mut()) };
+        }
+    }
+}
+
+/// Formats `n` into the end of `buf` without allocating.
+fn decimal(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+    let mut start = buf.len();
+    loop {
+        start -= 1;
+        buf[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            return &buf[start..];
+        }
+    }
+}
+
+/// An OMT sender, destroyed when dropped.
+///
+/// The raw pointer keeps this `!Send` and `!Sync`; libomt doesn't document
+/// its sender API as thread-safe.
+pub struct Sender {
+    ptr: *mut omt_send_t,
+    name: String,
+    /// `omt_send` calls started by [`Sender::send_with_timeout`] that
+    /// haven't returned yet
+    in_flight: Arc<AtomicUsize>,
+    /// The last values read from libomt, returned while a timed-out
+    /// `omt_send` still holds the sender
+    last_connections: Cell<i32>,
+    last_video: Cell<Statistics>,
+    last_audio: Cell<Statistics>,
+}
+
+/// The sender and frame moved into a [`Sender::send_with_timeout`] worker.
+/// Only one call runs at a time, so the sender is never used from two
+/// threads at once, and the frame's pointers are replaced by the worker's
+/// own copies before the call.
+struct SendCall {
+    sender: *mut omt_send_t,
+    frame: OMTMediaFrame,
+}
+
+unsafe impl Send for SendCall {}
+
+impl Sender {
+    /// Creates a sender advertised as `name`. `quality` is the only encoder
+    /// setting libomt exposes; there are no GOP, bitrate or thread setters.
+    pub fn create(name: &str, quality: OMTQuality) -> Result<Sender> {
+        let c_name = CString::new(name)
+            .with_context(|| format!("sender name {:?} contains a NUL byte", name))?;
+        let ptr = {
+            let _call = CallGuard::enter("omt_send_create");
+            unsafe { omt_send_create(c_name.as_ptr(), quality) }
+        };
+        if ptr.is_null() {
+            bail!(
+                "omt_send_create returned null for sender {} at quality {}",
+                name,
+                quality_name(quality)
+            );
+        }
+        SENT_FRAMES.store(0, Ordering::Relaxed);
+        Ok(Sender {
+            ptr,
+            name: name.to_string(),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            last_connections: Cell::new(0),
+            last_video: Cell::new(Statistics::default()),
+            last_audio: Cell::new(Statistics::default()),
+        })
+    }
+
+    /// Creates a sender and sets its product information before returning
+    /// it, so no caller ever holds a sender without its information. The
+    /// sender is owned from the moment `omt_send_create` returns: if a later
+    /// step fails, the early return drops it and destroys the sender.
+    /// `omt_send_setsenderinformation` itself returns nothing, so `info` is
+    /// the only thing that can be wrong, and it is built before any sender
+    /// exists.
+    pub fn create_with_info(
+        name: &str,
+        quality: OMTQuality,
+        info: &OMTSenderInfo,
+    ) -> Result<Sender> {
+        let sender = Sender::create(name, quality)?;
+        sender.set_sender_information(info);
+        Ok(sender)
+    }
+
+    /// The name the sender is advertised as.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn as_ptr(&self) -> *mut omt_send_t {
+        self.ptr
+    }
+
+    /// Sends one frame and returns the library's return code.
+    pub fn send(&self, frame: &mut OMTMediaFrame) -> i32 {
+        let rc = {
+            let _call = CallGuard::enter("omt_send");
+            unsafe { omt_send(self.ptr, frame as *mut OMTMediaFrame) }
+        };
+        SENT_FRAMES.fetch_add(1, Ordering::Relaxed);
+        rc
+    }
+
+    /// Like [`Sender::send`], but gives up waiting after `timeout` so a hung
+    /// encoder is reported instead of freezing the caller.
+    ///
+    /// The call runs on a worker thread with its own copy of the frame data
+    /// and metadata, so the caller's buffers can be reused whatever happens.
+    /// A call that times out can't be aborted: the worker stays blocked in
+    /// libomt and the sender isn't touched again until it returns: later
+    /// sends fail at once, [`Sender::connections`] and the statistics return
+    /// the last values read, [`Sender::receive_metadata`] and
+    /// [`Sender::address`] return `None`, and dropping the sender leaks it
+    /// rather than destroying it under the call. Nothing else enters libomt
+    /// meanwhile, so a crash in the stuck call is still reported as
+    /// `omt_send`. One timeout may be a stall; repeated ones mean the
+    /// library is stuck and the process should be restarted.
+    pub fn send_with_timeout(&self, frame: &OMTMediaFrame, timeout: Duration) -> Result<i32> {
+        if self.is_send_stuck() {
+            bail!("a previous omt_send on {} has not returned", self.name);
+        }
+        let copy = |data: *mut std::ffi::c_void, len: c_int| -> Vec<u8> {
+            if data.is_null() || len <= 0 {
+                return Vec::new();
+            }
+            unsafe { std::slice::from_raw_parts(data as *const u8, len as usize) }.to_vec()
+        };
+        let mut data = copy(frame.Data, frame.DataLength);
+        let mut metadata = copy(frame.FrameMetadata, frame.FrameMetadataLength);
+        let call = SendCall {
+            sender: self.ptr,
+            frame: *frame,
+        };
+        let in_flight = Arc::clone(&self.in_flight);
+        let (done, result) = mpsc::sync_channel(1);
+
+        in_flight.fetch_add(1, Ordering::AcqRel);
+        thread::Builder::new()
+            .name("omt_send".to_string())
+            .spawn(move || {
+                // Bound whole first: a closure capturing only the fields
+                // would capture the raw pointers, which aren't Send
+                let call = call;
+                let SendCall { sender, mut frame } = call;
+                if !data.is_empty() {
+                    frame.Data = data.as_mut_ptr() as *mut _;
+                }
+                if !metadata.is_empty() {
+                    frame.FrameMetadata = metadata.as_mut_ptr() as *mut _;
+                }
+                let rc = {
+                    let _call = CallGuard::enter("omt_send");
+                    unsafe { omt_send(sender, &mut frame as *mut OMTMediaFrame) }
+                };
+                SENT_FRAMES.fetch_add(1, Ordering::Relaxed);
+                in_flight.fetch_sub(1, Ordering::AcqRel);
+                // The caller may have stopped waiting
+                done.send(rc).ok();
+            })
+            .inspect_err(|_| {
+                self.in_flight.fetch_sub(1, Ordering::AcqRel);
+            })
+            .context("failed to start the omt_send worker thread")?;
+
+        match result.recv_timeout(timeout) {
+            Ok(rc) => Ok(rc),
+            Err(_) => bail!(
+                "omt_send on {} did not return within {}ms",
+                self.name,
+                timeout.as_millis()
+            ),
+        }
+    }
+
+    /// Sends one black frame of `format`: Y=16 with neutral chroma (limited
+    /// range) for UYVY and NV12, opaque black for BGRA. Useful as a
+    /// keepalive, to clear a receiver's screen or to mark a test boundary.
+    /// Returns the library's return code.
+    pub fn send_blank(&self, format: &VideoFormat, timestamp: i64) -> i32 {
+        let mut buf = format.create_black_frame();
+        let mut frame = video_frame(format, false, &mut buf);
+        frame.Timestamp = timestamp;
+        self.send(&mut frame)
+    }
+
+    /// Whether a timed-out `omt_send` is still running on the sender.
+    pub fn is_send_stuck(&self) -> bool {
+        self.in_flight.load(Ordering::Acquire) > 0
+    }
+
+    /// Number of connected receivers, or the last count read while a send
+    /// is stuck.
+    pub fn connections(&self) -> i32 {
+        if self.is_send_stuck() {
+            return self.last_connections.get();
+        }
+        let count = {
+            let _call = CallGuard::enter("omt_send_connections");
+            unsafe { omt_send_connections(self.ptr) }
+        };
+        self.last_connections.set(count);
+        count
+    }
+
+    /// Number of connected receivers, treating a negative count as none.
+    pub fn connection_count(&self) -> u32 {
+        self.connections().max(0) as u32
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.connections() > 0
+    }
+
+    /// Waits up to `timeout` for a metadata frame sent by a receiver and
+    /// returns its XML text. `None` at once while a send is stuck.
+    pub fn receive_metadata(&self, timeout: Duration) -> Option<String> {
+        if self.is_send_stuck() {
+            return None;
+        }
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        let frame = {
+            let _call = CallGuard::enter("omt_send_receive");
+            unsafe { omt_send_receive(self.ptr, timeout_ms) }
+        };
+        if frame.is_null() {
+            return None;
+        }
+        // The frame stays valid until the next omt_send_receive call
+        let frame = unsafe { &*frame };
+        if frame.Type != OMTFrameType_OMTFrameType_Metadata
+            || frame.Data.is_null()
+            || frame.DataLength <= 0
+        {
+            return None;
+        }
+        let bytes = unsafe {
+            std::slice::from_raw_parts(frame.Data as *const u8, frame.DataLength as usize)
+        };
+        let text = bytes.split(|&b| b == 0).next().unwrap_or_default();
+        Some(String::from_utf8_lossy(text).into_owned())
+    }
+
+    pub fn set_sender_information(&self, info: &OMTSenderInfo) {
+        // libomt copies the strings and doesn't write through the pointer
+        let _call = CallGuard::enter("omt_send_setsenderinformation");
+        unsafe {
+            omt_send_setsenderinformation(self.ptr, info as *const OMTSenderInfo as *mut _);
+        }
+    }
+
+    /// Returns the address receivers use to connect to this sender, or
+    /// `None` while a send is stuck.
+    pub fn address(&self) -> Option<String> {
+        if self.is_send_stuck() {
+            return None;
+        }
+        let mut address = [0 as c_char; 1024];
+        let len = {
+            let _call = CallGuard::enter("omt_send_getaddress");
+            unsafe { omt_send_getaddress(self.ptr, address.as_mut_ptr(), address.len() as i32) }
+        };
+        if len > 0 {
+            let address = unsafe { CStr::from_ptr(address.as_ptr()) };
+            Some(address.to_string_lossy().into_owned())
+        } else {
+            None
+        }
+    }
+
+    /// Reads the video statistics. The `*_since_last` fields are relative to
+    /// the previous call. While a send is stuck this returns the last
+    /// totals read, with nothing new since then.
+    pub fn video_statistics(&self) -> Statistics {
+        if self.is_send_stuck() {
+            return unchanged(self.last_video.get());
+        }
+        let mut stats: OMTStatistics = unsafe { mem::zeroed() };
+        {
+            let _call = CallGuard::enter("omt_send_getvideostatistics");
+            unsafe { omt_send_getvideostatistics(self.ptr, &mut stats as *mut OMTStatistics) };
+        }
+        self.last_video.set(stats.into());
+        stats.into()
+    }
+
+    /// Reads the audio statistics, like [`Sender::video_statistics`].
+    pub fn audio_statistics(&self) -> Statistics {
+        if self.is_send_stuck() {
+            return unchanged(self.last_audio.get());
+        }
+        let mut stats: OMTStatistics = unsafe { mem::zeroed() };
+        {
+            let _call = CallGuard::enter("omt_send_getaudiostatistics");
+            unsafe { omt_send_getaudiostatistics(self.ptr, &mut stats as *mut OMTStatistics) };
+        }
+        self.last_audio.set(stats.into());
+        stats.into()
+    }
+
+    /// Waits for frames still queued in the encoder to go out, so statistics
+    /// read afterwards cover everything that was actually transmitted.
+    ///
+    /// libomt has no flush call, so this polls the frame count until it stops
+    /// changing for a few polls or `timeout` expires. Returns the final count.
+    pub fn flush(&self, timeout: Duration) -> i64 {
+        const POLL: Duration = Duration::from_millis(20);
+        const STABLE_POLLS: u32 = 3;
+
+        let start = Instant::now();
+        let mut frames = self.video_statistics().frames;
+        let mut stable = 0;
+        while stable < STABLE_POLLS && start.elapsed() < timeout {
+            thread::sleep(POLL);
+            let now = self.video_statistics().frames;
+            if now == frames {
+                stable += 1;
+            } else {
+                frames = now;
+                stable = 0;
+            }
+        }
+        frames
+    }
+}
+
+/// `stats` read again with nothing sent since: the same totals and no
+/// change since the last call.
+fn unchanged(stats: Statistics) -> Statistics {
+    Statistics {
+        bytes_sent_since_last: 0,
+        bytes_received_since_last: 0,
+        frames_since_last: 0,
+        codec_time_since_last: 0,
+        ..stats
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        if self.is_send_stuck() {
+            eprintln!(
+                "Warning: omt_send on {} is still blocked, leaking the sender instead of destroying it",
+                self.name
+            );
+            return;
+        }
+        let _call = CallGuard::enter("omt_send_destroy");
+        unsafe { omt_send_destroy(self.ptr) };
+    }
+}
@@ -0,0 +1,86 @@
+// OMTStatistics の Rust 側表現
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::bindings::*;
+
+/// Sender/receiver statistics as reported by libomt. Times are milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    pub bytes_sent_since_last: i64,
+    pub bytes_received_since_last: i64,
+    pub frames: i64,
+    pub frames_since_last: i64,
+    pub frames_dropped: i64,
+    pub codec_time: i64,
+    pub codec_time_since_last: i64,
+}
+
+impl From<OMTStatistics> for Statistics {
+    fn from(s: OMTStatistics) -> Self {
+        Statistics {
+            bytes_sent: s.BytesSent,
+            bytes_received: s.BytesReceived,
+            bytes_sent_since_last: s.BytesSentSinceLast,
+            bytes_received_since_last: s.BytesReceivedSinceLast,
+            frames: s.Frames,
+            frames_since_last: s.FramesSinceLast,
+            frames_dropped: s.FramesDropped,
+            codec_time: s.CodecTime,
+            codec_time_since_last: s.CodecTimeSinceLast,
+        }
+    }
+}
+
+/// Receiver count sampled once per stats interval, so the periodic stats
+/// line and the summary show the connection state throughout the run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectionGauge {
+    pub current: u32,
+    pub min: u32,
+    pub max: u32,
+    pub samples: u32,
+}
+
+impl ConnectionGauge {
+    pub fn update(&mut self, count: u32) {
+        if self.samples == 0 {
+            self.min = count;
+            self.max = count;
+        } else {
+            self.min = self.min.min(count);
+            self.max = self.max.max(count);
+        }
+        self.current = count;
+        self.samples += 1;
+    }
+}
+
+/// Minimum, mean and 99th percentile (nearest rank) of a set of latencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub samples: usize,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p99: Duration,
+}
+
+impl LatencySummary {
+    /// Summarizes `samples`, sorting them; `None` when there are none.
+    pub fn from_samples(samples: &mut [Duration]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort_unstable();
+        let n = samples.len();
+        let total: Duration = samples.iter().sum();
+        Some(LatencySummary {
+            samples: n,
+            min: samples[0],
+            mean: total / n as u32,
+            p99: samples[(n * 99).div_ceil(100) - 1],
+        })
+    }
+}
@@ -0,0 +1,163 @@
+// 静止画像の読み込み（--image）
+use anyhow::{Context, Result};
+use image::{imageops, DynamicImage, RgbaImage};
+use std::path::Path;
+
+use crate::{FrameRenderer, VideoFormat};
+
+/// A still image letterboxed to the format's size and sent unchanged every
+/// frame.
+pub struct StillImage {
+    image: RgbaImage,
+    has_alpha: bool,
+}
+
+impl StillImage {
+    pub fn load(path: &Path) -> Result<Self> {
+        let decoded = image::open(path)
+            .with_context(|| format!("failed to decode image {} (PNG or JPEG)", path.display()))?;
+        Ok(Self::new(decoded))
+    }
+
+    pub fn new(image: DynamicImage) -> Self {
+        Self {
+            has_alpha: image.color().has_alpha(),
+            image: image.to_rgba8(),
+        }
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    /// Whether the file carries an alpha channel, so BGRA frames of it
+    /// should be sent with `OMTVideoFlags_Alpha`.
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+}
+
+impl FrameRenderer for StillImage {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    /// Scales the image to fit the format with its aspect ratio kept and
+    /// centers it. The borders are black, or transparent for images with
+    /// alpha.
+    fn render_rgba(&self, format: &VideoFormat, _frame_index: u64) -> Result<RgbaImage> {
+        let (width, height) = (format.width as u32, format.height as u32);
+        let (image_width, image_height) = self.image.dimensions();
+        if (image_width, image_height) == (width, height) {
+            return Ok(self.image.clone());
+        }
+        let scale = (width as f64 / image_width as f64).min(height as f64 / image_height as f64);
+        let fit_width = ((image_width as f64 * scale).round() as u32).clamp(1, width);
+        let fit_height = ((image_height as f64 * scale).round() as u32).clamp(1, height);
+        let scaled = imageops::resize(
+            &self.image,
+            fit_width,
+            fit_height,
+            imageops::FilterType::Triangle,
+        );
+        let border = if self.has_alpha { 0 } else { 255 };
+        let mut frame = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, border]));
+        // Copied, not blended, so transparent pixels stay transparent
+        imageops::replace(
+            &mut frame,
+            &scaled,
+            ((width - fit_width) / 2) as i64,
+            ((height - fit_height) / 2) as i64,
+        );
+        Ok(frame)
+    }
+
+    fn animated(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bindings::*;
+    use image::{RgbImage, Rgba};
+
+    #[test]
+    fn a_4_3_image_is_pillarboxed_into_16_9() {
+        let red = RgbImage::from_pixel(640, 480, image::Rgb([255, 0, 0]));
+        let still = StillImage::new(DynamicImage::ImageRgb8(red));
+        let format = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 1280, 720, 30, 1).unwrap();
+        let frame = still.render_rgba(&format, 0).unwrap();
+        assert_eq!(frame.dimensions(), (1280, 720));
+        // Scaled 1.5x to 960x720 and centered, with 160-pixel black bars
+        for y in [0, 360, 719] {
+            for x in [0, 159, 1120, 1279] {
+                assert_eq!(
+                    *frame.get_pixel(x, y),
+                    Rgba([0, 0, 0, 255]),
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+            for x in [160, 640, 1119] {
+                assert_eq!(
+                    *frame.get_pixel(x, y),
+                    Rgba([255, 0, 0, 255]),
+                    "({}, {})",
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn borders_are_transparent_for_images_with_alpha() {
+        let image = RgbaImage::from_pixel(100, 100, Rgba([0, 0, 255, 128]));
+        let still = StillImage::new(DynamicImage::ImageRgba8(image));
+        let format = VideoFormat::from_parts(OMTCodec_OMTCodec_BGRA, 200, 100, 30, 1).unwrap();
+        let frame = still.render_rgba(&format, 0).unwrap();
+        assert_eq!(*frame.get_pixel(0, 50), Rgba([0, 0, 0, 0]));
+        assert_eq!(*frame.get_pixel(100, 50), Rgba([0, 0, 255, 128]));
+    }
+
+    #[test]
+    fn has_alpha_follows_the_decoded_color_type() {
+        let rgba = RgbaImage::from_pixel(4, 4, Rgba([1, 2, 3, 255]));
+        assert!(StillImage::new(DynamicImage::ImageRgba8(rgba)).has_alpha());
+        let rgb = RgbImage::from_pixel(4, 4, image::Rgb([1, 2, 3]));
+        assert!(!StillImage::new(DynamicImage::ImageRgb8(rgb)).has_alpha());
+    }
+
+    #[test]
+    fn from_image_uses_the_image_size_with_an_even_width_for_yuv() {
+        let path = std::env::temp_dir().join(format!("still-test-{}.png", std::process::id()));
+        RgbImage::from_pixel(101, 51, image::Rgb([0, 255, 0]))
+            .save(&path)
+            .unwrap();
+        for (codec, width) in [
+            (OMTCodec_OMTCodec_UYVY, 102),
+            (OMTCodec_OMTCodec_NV12, 102),
+            (OMTCodec_OMTCodec_BGRA, 101),
+        ] {
+            let (format, buf) = VideoFormat::from_image(&path, codec, 30, 1).unwrap();
+            assert_eq!(
+                (format.width, format.height),
+                (width, 51),
+                "{}",
+                format.name
+            );
+            assert_eq!(buf.len(), format.buffer_size(), "{}", format.name);
+        }
+        std::fs::write(&path, b"not a png").unwrap();
+        let err = VideoFormat::from_image(&path, OMTCodec_OMTCodec_BGRA, 30, 1).unwrap_err();
+        assert!(
+            err.to_string().starts_with("failed to decode image"),
+            "{}",
+            err
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
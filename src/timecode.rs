@@ -0,0 +1,107 @@
+// SMPTE タイムコード（29.97/59.94 のドロップフレーム対応）
+use anyhow::{bail, Context, Result};
+
+/// A SMPTE timecode expressed as a frame count from 00:00:00:00.
+#[derive(Debug, Clone, Copy)]
+pub struct Timecode {
+    frame: u64,
+    /// Nominal integer rate (30 for 29.97)
+    fps: u64,
+    drop_frame: bool,
+}
+
+impl Timecode {
+    /// Parses `HH:MM:SS:FF` (or `HH:MM:SS;FF`) for the given frame rate.
+    /// Drop-frame counting is used for 30000/1001 and 60000/1001.
+    pub fn parse(s: &str, fps_n: i32, fps_d: i32) -> Result<Self> {
+        if fps_n <= 0 || fps_d <= 0 {
+            bail!("invalid frame rate {}/{}", fps_n, fps_d);
+        }
+        let fps = (fps_n as u64).div_ceil(fps_d as u64);
+        let drop_frame = fps_d == 1001 && (fps == 30 || fps == 60);
+
+        let parts: Vec<&str> = s.split([':', ';']).collect();
+        if parts.len() != 4 {
+            bail!("timecode must be HH:MM:SS:FF, got {}", s);
+        }
+        let mut fields = [0u64; 4];
+        for (field, part) in fields.iter_mut().zip(&parts) {
+            *field = part
+                .parse()
+                .with_context(|| format!("invalid timecode {}", s))?;
+        }
+        let [h, m, sec, f] = fields;
+        if h > 23 || m > 59 || sec > 59 || f >= fps {
+            bail!("timecode {} is out of range for {}fps", s, fps);
+        }
+
+        let drop = Self::drop_count(fps, drop_frame);
+        if drop > 0 && sec == 0 && m % 10 != 0 && f < drop {
+            bail!("timecode {} does not exist in drop-frame counting", s);
+        }
+
+        let total_minutes = h * 60 + m;
+        let frame =
+            fps * (h * 3600 + m * 60 + sec) + f - drop * (total_minutes - total_minutes / 10);
+
+        Ok(Timecode {
+            frame,
+            fps,
+            drop_frame,
+        })
+    }
+
+    fn drop_count(fps: u64, drop_frame: bool) -> u64 {
+        if drop_frame {
+            fps / 15
+        } else {
+            0
+        }
+    }
+
+    pub fn is_drop_frame(&self) -> bool {
+        self.drop_frame
+    }
+
+    /// Returns the timecode `frames` frames later, wrapping at 24 hours.
+    pub fn offset(&self, frames: u64) -> Timecode {
+        let drop = Self::drop_count(self.fps, self.drop_frame);
+        let frames_per_day = (self.fps * 86_400) - drop * (24 * 60 - 24 * 6);
+        Timecode {
+            frame: (self.frame + frames) % frames_per_day,
+            ..*self
+        }
+    }
+}
+
+impl std::fmt::Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let drop = Self::drop_count(self.fps, self.drop_frame);
+        let mut n = self.frame;
+
+        if drop > 0 {
+            // Re-insert the frame numbers skipped at the start of each minute
+            let frames_per_10min = self.fps * 600 - drop * 9;
+            let frames_per_min = self.fps * 60 - drop;
+            let tens = n / frames_per_10min;
+            let rem = n % frames_per_10min;
+            n += drop * 9 * tens;
+            if rem > drop {
+                n += drop * ((rem - drop) / frames_per_min);
+            }
+        }
+
+        let frames = n % self.fps;
+        let secs = n / self.fps;
+        let sep = if self.drop_frame { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            secs / 3600,
+            (secs / 60) % 60,
+            secs % 60,
+            sep,
+            frames
+        )
+    }
+}
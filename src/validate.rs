@@ -0,0 +1,237 @@
+// OMTMediaFrame のフィールド整合性チェック（--validate-frame / --strict-stride）
+use anyhow::{bail, Result};
+
+use crate::bindings::*;
+
+/// Checks the cross-field invariants of a video frame whose `Data` points at
+/// `buf_len` bytes and returns every violation found.
+///
+/// Expected sizes are derived from the frame's own codec and dimensions rather
+/// than from `VideoFormat`, so a mistake there is caught too.
+pub fn validate_video_frame(frame: &OMTMediaFrame, buf_len: usize) -> Vec<String> {
+    let mut errors = Vec::new();
+    let (width, height) = (frame.Width as i64, frame.Height as i64);
+    let codec = frame.Codec;
+    let is_yuv = codec == OMTCodec_OMTCodec_UYVY || codec == OMTCodec_OMTCodec_NV12;
+
+    if frame.Type != OMTFrameType_OMTFrameType_Video {
+        errors.push(format!("Type is {}, expected Video", frame.Type));
+    }
+    if width <= 0 || height <= 0 {
+        errors.push(format!("size {}x{} is not positive", width, height));
+    } else if codec == OMTCodec_OMTCodec_UYVY && width % 2 != 0 {
+        errors.push(format!(
+            "Width {} must be even for chroma subsampling",
+            width
+        ));
+    }
+
+    let (stride, data_length) = match codec {
+        c if c == OMTCodec_OMTCodec_UYVY => (width * 2, width * 2 * height),
+        c if c == OMTCodec_OMTCodec_BGRA => (width * 4, width * 4 * height),
+        c if c == OMTCodec_OMTCodec_NV12 => {
+            // Rows may be padded to an aligned stride shared by both planes
+            let stride = (frame.Stride as i64).max(min_stride_for(codec, width as i32));
+            (stride, min_buffer_for(codec, stride as i32, height as i32))
+        }
+        _ => {
+            errors.push(format!("Codec {} is not UYVY, BGRA or NV12", codec));
+            (frame.Stride as i64, frame.DataLength as i64)
+        }
+    };
+    if frame.Stride as i64 != stride {
+        errors.push(format!(
+            "Stride {} does not match {} for width {}",
+            frame.Stride, stride, width
+        ));
+    }
+    if frame.DataLength as i64 != data_length {
+        errors.push(format!(
+            "DataLength {} does not match {} for {}x{}",
+            frame.DataLength, data_length, width, height
+        ));
+    }
+    if frame.DataLength < 0 || frame.DataLength as usize > buf_len {
+        errors.push(format!(
+            "DataLength {} exceeds the {}-byte buffer",
+            frame.DataLength, buf_len
+        ));
+    }
+    if frame.Data.is_null() {
+        errors.push("Data is null".to_string());
+    }
+
+    if !frame.AspectRatio.is_finite() || frame.AspectRatio <= 0.0 {
+        errors.push(format!("AspectRatio {} is not positive", frame.AspectRatio));
+    }
+    if frame.FrameRateN <= 0 || frame.FrameRateD <= 0 {
+        errors.push(format!(
+            "frame rate {}/{} must be positive",
+            frame.FrameRateN, frame.FrameRateD
+        ));
+    }
+
+    let colorspace = frame.ColorSpace;
+    let known_colorspace = colorspace == OMTColorSpace_OMTColorSpace_BT601
+        || colorspace == OMTColorSpace_OMTColorSpace_BT709;
+    if is_yuv && !known_colorspace {
+        errors.push(format!(
+            "ColorSpace {} must be BT601 or BT709 for YUV",
+            colorspace
+        ));
+    } else if !known_colorspace && colorspace != OMTColorSpace_OMTColorSpace_Undefined {
+        errors.push(format!("ColorSpace {} is not a known value", colorspace));
+    }
+
+    let alpha_flags = OMTVideoFlags_OMTVideoFlags_Alpha | OMTVideoFlags_OMTVideoFlags_PreMultiplied;
+    if codec != OMTCodec_OMTCodec_BGRA && frame.Flags & alpha_flags != 0 {
+        errors.push(format!(
+            "Flags {:#x} set alpha on a codec without an alpha channel",
+            frame.Flags
+        ));
+    }
+    if frame.Flags & OMTVideoFlags_OMTVideoFlags_PreMultiplied != 0
+        && frame.Flags & OMTVideoFlags_OMTVideoFlags_Alpha == 0
+    {
+        errors.push("PreMultiplied is set without Alpha".to_string());
+    }
+
+    errors
+}
+
+/// Smallest `Stride` that holds a row of `width` pixels of `codec`: two bytes
+/// per pixel for UYVY, four for BGRA, one Y byte for NV12, rounded up to
+/// whole UV pairs.
+pub fn min_stride_for(codec: OMTCodec, width: i32) -> i64 {
+    let width = width as i64;
+    match codec {
+        c if c == OMTCodec_OMTCodec_UYVY => width * 2,
+        c if c == OMTCodec_OMTCodec_NV12 => (width + 1) & !1,
+        _ => width * 4,
+    }
+}
+
+/// Bytes libomt reads from `Data` for `height` rows of `stride`: the NV12 UV
+/// plane follows the Y plane with the same stride and half the rows,
+/// rounded up.
+pub fn min_buffer_for(codec: OMTCodec, stride: i32, height: i32) -> i64 {
+    let plane = stride as i64 * height as i64;
+    if codec == OMTCodec_OMTCodec_NV12 {
+        plane + stride as i64 * (height.max(0) as u64).div_ceil(2) as i64
+    } else {
+        plane
+    }
+}
+
+/// Rejects a frame whose `Stride` is too small for its codec and width, or
+/// whose `buf_len`-byte buffer is shorter than the rows libomt will read
+/// (`--strict-stride`). Either would make the library read past the row or
+/// past the buffer.
+pub fn check_stride(frame: &OMTMediaFrame, buf_len: usize) -> Result<()> {
+    let min_stride = min_stride_for(frame.Codec, frame.Width);
+    if (frame.Stride as i64) < min_stride {
+        bail!(
+            "Stride {} is below the {} bytes a {}-pixel row needs",
+            frame.Stride,
+            min_stride,
+            frame.Width
+        );
+    }
+    let needed = min_buffer_for(frame.Codec, frame.Stride, frame.Height);
+    if (buf_len as i64) < needed {
+        bail!(
+            "buffer of {} bytes is smaller than the {} bytes of {} rows at stride {}",
+            buf_len,
+            needed,
+            frame.Height,
+            frame.Stride
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{video_frame, VideoFormat};
+
+    fn format(codec: OMTCodec) -> VideoFormat {
+        VideoFormat::from_parts(codec, 1280, 720, 30, 1).unwrap()
+    }
+
+    #[test]
+    fn frames_built_from_a_format_are_valid() {
+        for codec in [
+            OMTCodec_OMTCodec_UYVY,
+            OMTCodec_OMTCodec_BGRA,
+            OMTCodec_OMTCodec_NV12,
+        ] {
+            let format = format(codec);
+            let mut buf = vec![0u8; format.buffer_size()];
+            let frame = video_frame(&format, false, &mut buf);
+            assert_eq!(
+                validate_video_frame(&frame, buf.len()),
+                Vec::<String>::new()
+            );
+            check_stride(&frame, buf.len()).unwrap();
+        }
+    }
+
+    #[test]
+    fn too_small_stride_is_rejected() {
+        for codec in [
+            OMTCodec_OMTCodec_UYVY,
+            OMTCodec_OMTCodec_BGRA,
+            OMTCodec_OMTCodec_NV12,
+        ] {
+            let format = format(codec);
+            let mut buf = vec![0u8; format.buffer_size()];
+            let mut frame = video_frame(&format, false, &mut buf);
+            frame.Stride -= 2;
+            let err = check_stride(&frame, buf.len()).unwrap_err().to_string();
+            assert!(
+                err.starts_with(&format!("Stride {} is below", frame.Stride)),
+                "{}",
+                err
+            );
+            let errors = validate_video_frame(&frame, buf.len());
+            assert!(
+                errors.iter().any(|e| e.starts_with("Stride")),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    #[test]
+    fn short_buffer_is_rejected() {
+        let format = format(OMTCodec_OMTCodec_NV12);
+        let mut buf = vec![0u8; format.buffer_size()];
+        let frame = video_frame(&format, false, &mut buf);
+        // Sized for the Y plane only
+        let err = check_stride(&frame, 1280 * 720).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "buffer of 921600 bytes is smaller than the 1382400 bytes of 720 rows at stride 1280"
+        );
+    }
+
+    #[test]
+    fn wrong_data_length_is_rejected() {
+        let format = format(OMTCodec_OMTCodec_UYVY);
+        let mut buf = vec![0u8; format.buffer_size()];
+        let mut frame = video_frame(&format, false, &mut buf);
+        frame.DataLength -= 1;
+        assert_eq!(
+            validate_video_frame(&frame, buf.len()),
+            ["DataLength 1843199 does not match 1843200 for 1280x720"]
+        );
+        frame.DataLength = buf.len() as i32 + 4;
+        let errors = validate_video_frame(&frame, buf.len());
+        assert!(
+            errors.contains(&"DataLength 1843204 exceeds the 1843200-byte buffer".to_string()),
+            "{:?}",
+            errors
+        );
+    }
+}
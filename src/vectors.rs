@@ -0,0 +1,80 @@
+// 相互運用テスト用の参照フレームの書き出し（export-vectors）
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::dump::{sidecar_path, DumpHeader, DumpWriter};
+use crate::patterns::FramePattern;
+use crate::{video_frame, VideoFormat};
+
+/// Version of the manifest layout written by [`export_vectors`].
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// One exported frame. The geometry fields are those of the `--dump`
+/// sidecar written next to it, so `replay` can send a vector as is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorEntry {
+    /// Raw frame file, relative to the manifest
+    pub file: String,
+    pub sidecar: String,
+    /// Lowercase hex SHA-256 of the raw frame file
+    pub sha256: String,
+    #[serde(flatten)]
+    pub header: DumpHeader,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VectorManifest {
+    pub version: u32,
+    pub vectors: Vec<VectorEntry>,
+}
+
+/// Writes the built-in test frame of each format to `dir` as `<name>.raw`
+/// with its sidecar, plus a `manifest.json` listing them with their hashes.
+///
+/// Only the static per-codec patterns are exported and nothing depends on
+/// the clock or the library, so the output is byte-identical across runs.
+pub fn export_vectors(formats: &[VideoFormat], dir: &Path) -> Result<VectorManifest> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut vectors = Vec::new();
+    for format in formats {
+        let mut frame_buf = format.create_test_frame(0, FramePattern::Static);
+        let frame = video_frame(format, false, &mut frame_buf);
+        let header = DumpHeader::new(format, &frame);
+        let data = &frame_buf[..header.data_length as usize];
+
+        let file = format!("{}.raw", format.name);
+        let path = dir.join(&file);
+        let mut writer = DumpWriter::create(&path, &header)?;
+        writer.write_frame(data)?;
+        writer.finish()?;
+
+        let sha256 = format!("{:x}", Sha256::digest(data));
+        println!(
+            "{}: {}x{} stride {}, {} bytes, sha256 {}",
+            file, header.width, header.height, header.stride, header.data_length, sha256
+        );
+        vectors.push(VectorEntry {
+            sidecar: sidecar_path(Path::new(&file)).display().to_string(),
+            file,
+            sha256,
+            header,
+        });
+    }
+
+    let manifest = VectorManifest {
+        version: MANIFEST_VERSION,
+        vectors,
+    };
+    let path = dir.join("manifest.json");
+    std::fs::write(&path, serde_json::to_string_pretty(&manifest)? + "\n")
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    println!(
+        "Wrote {} vectors and {}",
+        manifest.vectors.len(),
+        path.display()
+    );
+    Ok(manifest)
+}